@@ -0,0 +1,200 @@
+use std::{env, path::PathBuf};
+
+use cursive::event::Event;
+
+use crate::{
+    render::theme::Theme,
+    renderer::{keyregistry::parse_key, renderer::RendererOptions},
+};
+
+/// Settings read from the config file, each left unset when the file doesn't give it
+///
+/// The file holds one `key = value` per line. Blank lines and lines starting with `#`
+/// are skipped, and `bind.<key> = <key>` moves a shortcut to another key.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Config {
+    pub theme: Option<Theme>,
+    /// Widest a page is laid out, in cells; not yet used by layout
+    pub max_content_width: Option<usize>,
+    pub javascript: Option<bool>,
+    pub user_stylesheet: Option<PathBuf>,
+    pub px_per_cell: Option<f32>,
+    /// Shortcuts to move, from their default key to another
+    pub key_bindings: Vec<(Event, Event)>,
+}
+
+impl Config {
+    /// Parse the text of a config file, with a warning for each line that was skipped
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::config::config::Config;
+    /// use tiny_browserbook::render::theme::Theme;
+    /// let (config, warnings) = Config::parse("theme = light\nzoom = 2\n");
+    /// assert_eq!(config.theme, Some(Theme::light()));
+    /// assert_eq!(warnings, vec!["line 2: unknown key zoom"]);
+    /// ```
+    pub fn parse(raw: &str) -> (Config, Vec<String>) {
+        let mut config = Config::default();
+        let mut warnings = vec![];
+        for (i, line) in raw.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let result = match line.split_once('=') {
+                Some((key, value)) => config.set(key.trim(), value.trim()),
+                None => Err(format!("expected key = value, found {}", line)),
+            };
+            if let Err(warning) = result {
+                warnings.push(format!("line {}: {}", i + 1, warning));
+            }
+        }
+        (config, warnings)
+    }
+
+    /// Set the setting `key` from its written `value`
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::config::config::Config;
+    /// let mut config = Config::default();
+    /// config.set("javascript", "off").unwrap();
+    /// assert_eq!(config.javascript, Some(false));
+    /// assert!(config.set("px-per-cell", "wide").is_err());
+    /// ```
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let invalid = || format!("invalid value {} for {}", value, key);
+        match key {
+            "theme" => {
+                self.theme = Some(match value {
+                    "dark" => Theme::dark(),
+                    "light" => Theme::light(),
+                    _ => return Err(invalid()),
+                })
+            }
+            "max-content-width" => {
+                self.max_content_width = Some(value.parse().map_err(|_| invalid())?)
+            }
+            "javascript" => {
+                self.javascript = Some(match value {
+                    "on" | "true" | "yes" => true,
+                    "off" | "false" | "no" => false,
+                    _ => return Err(invalid()),
+                })
+            }
+            "user-stylesheet" => self.user_stylesheet = Some(PathBuf::from(value)),
+            "px-per-cell" => {
+                let px_per_cell: f32 = value.parse().map_err(|_| invalid())?;
+                if !px_per_cell.is_finite() || px_per_cell <= 0.0 {
+                    return Err(invalid());
+                }
+                self.px_per_cell = Some(px_per_cell)
+            }
+            _ => match key.strip_prefix("bind.") {
+                Some(from) => {
+                    let from = parse_key(from).ok_or_else(|| format!("unknown key {}", from))?;
+                    let to = parse_key(value).ok_or_else(|| format!("unknown key {}", value))?;
+                    self.key_bindings.push((from, to));
+                }
+                None => return Err(format!("unknown key {}", key)),
+            },
+        }
+        Ok(())
+    }
+
+    /// Copy the settings that renderer options hold into `options`
+    pub fn apply(&self, options: &mut RendererOptions) {
+        if let Some(theme) = &self.theme {
+            options.theme = theme.clone();
+        }
+        if let Some(px_per_cell) = self.px_per_cell {
+            options.units.px_per_cell = px_per_cell;
+        }
+    }
+}
+
+/// Where the config file is read from when no other path is given:
+/// `$XDG_CONFIG_HOME/tiny-browserbook/config`, or `~/.config/tiny-browserbook/config`
+pub fn default_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("tiny-browserbook").join("config"))
+}
+
+#[cfg(test)]
+mod tests {
+    use cursive::event::Key;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let (config, warnings) = Config::parse(
+            "# defaults
+theme = light
+max-content-width = 100
+
+javascript = no
+user-stylesheet = /home/me/user.css
+px-per-cell = 10
+bind.t = T
+bind.Ctrl-r = F5
+",
+        );
+        assert!(warnings.is_empty());
+        assert_eq!(
+            config,
+            Config {
+                theme: Some(Theme::light()),
+                max_content_width: Some(100),
+                javascript: Some(false),
+                user_stylesheet: Some(PathBuf::from("/home/me/user.css")),
+                px_per_cell: Some(10.0),
+                key_bindings: vec![
+                    (Event::Char('t'), Event::Char('T')),
+                    (Event::CtrlChar('r'), Event::Key(Key::F5)),
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_config_warns_on_invalid_lines() {
+        let (config, warnings) = Config::parse(
+            "theme = solarized
+javascript
+px-per-cell = 0
+font = mono
+bind.Hyper-x = y
+theme = dark",
+        );
+        assert_eq!(
+            warnings,
+            vec![
+                "line 1: invalid value solarized for theme",
+                "line 2: expected key = value, found javascript",
+                "line 3: invalid value 0 for px-per-cell",
+                "line 4: unknown key font",
+                "line 5: unknown key Hyper-x",
+            ]
+        );
+        assert_eq!(
+            config,
+            Config {
+                theme: Some(Theme::dark()),
+                ..Config::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_apply_config() {
+        let (config, _) = Config::parse("theme = light\npx-per-cell = 4");
+        let mut options = RendererOptions::default();
+        config.apply(&mut options);
+        assert_eq!(options.theme, Theme::light());
+        assert_eq!(options.units.px_per_cell, 4.0);
+        assert_eq!(options.units.em_width, 2.0);
+    }
+}
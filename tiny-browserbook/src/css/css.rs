@@ -1,12 +1,23 @@
+use std::borrow::Cow;
+
 use combine::{
-    choice,
+    attempt, choice, eof,
     error::StreamError,
-    many, many1, optional,
-    parser::char::{self, letter, newline, space},
-    sep_by, sep_end_by, ParseError, Parser, Stream,
+    look_ahead, many, many1, none_of, one_of, optional,
+    parser::{
+        char::{self, alpha_num, digit, hex_digit, letter, newline, space},
+        repeat::skip_until,
+    },
+    sep_by, sep_by1, sep_end_by, skip_many, skip_many1,
+    stream::position,
+    EasyParser, ParseError, Parser, Stream,
 };
 
-use crate::html::dom::{Node, NodeType};
+use crate::{
+    error::error::{BrowserError, CssParseError},
+    html::dom::{Node, NodeType},
+    layout::units::Unit,
+};
 
 #[derive(Debug, PartialEq)]
 pub struct Stylesheet {
@@ -28,6 +39,56 @@ pub struct Declaration {
 #[derive(Debug, PartialEq, Clone)]
 pub enum CSSValue {
     Keyword(String),
+    /// A number and its unit, `Unit::Number` when it has none
+    Length(f32, Unit),
+    Color {
+        r: u8,
+        g: u8,
+        b: u8,
+    },
+    /// Components separated by spaces, as in `margin: 1 2`
+    List(Vec<CSSValue>),
+    /// Items separated by commas, as in `font-family: monospace, serif`, each of them
+    /// being a `List` when it has several components
+    CommaList(Vec<CSSValue>),
+}
+
+/// The basic color keywords and their RGB values
+const NAMED_COLORS: [(&str, (u8, u8, u8)); 16] = [
+    ("black", (0, 0, 0)),
+    ("silver", (192, 192, 192)),
+    ("gray", (128, 128, 128)),
+    ("white", (255, 255, 255)),
+    ("maroon", (128, 0, 0)),
+    ("red", (255, 0, 0)),
+    ("purple", (128, 0, 128)),
+    ("fuchsia", (255, 0, 255)),
+    ("green", (0, 128, 0)),
+    ("lime", (0, 255, 0)),
+    ("olive", (128, 128, 0)),
+    ("yellow", (255, 255, 0)),
+    ("navy", (0, 0, 128)),
+    ("blue", (0, 0, 255)),
+    ("teal", (0, 128, 128)),
+    ("aqua", (0, 255, 255)),
+];
+
+/// Get the color named `name`, ignoring case, if it is one of the basic colors
+/// # Example
+/// ```
+/// use tiny_browserbook::css::css::{named_color, CSSValue};
+/// assert_eq!(named_color("Teal"), Some(CSSValue::Color { r: 0, g: 128, b: 128 }));
+/// assert_eq!(named_color("block"), None);
+/// ```
+pub fn named_color(name: &str) -> Option<CSSValue> {
+    NAMED_COLORS
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, (r, g, b))| CSSValue::Color {
+            r: *r,
+            g: *g,
+            b: *b,
+        })
 }
 
 #[derive(Debug, PartialEq)]
@@ -37,11 +98,25 @@ pub struct Rule {
 }
 
 impl Rule {
-    pub fn matches(&self, n: &Box<Node>) -> bool {
-        self.selectors.iter().any(|s| s.matches(n))
+    pub fn matches(&self, n: &Node) -> bool {
+        self.selectors.iter().any(|s| n.matches(s))
+    }
+
+    /// Get the specificity of the most specific selector matching `n`, or `None` when
+    /// none does
+    pub fn specificity_for(&self, n: &Node) -> Option<Specificity> {
+        self.selectors
+            .iter()
+            .filter(|s| n.matches(s))
+            .map(|s| s.specificity())
+            .max()
     }
 }
 
+/// How many ids, classes and attributes, and types a selector has, compared in that
+/// order to pick the declaration that wins
+pub type Specificity = (usize, usize, usize);
+
 pub type Selector = SimpleSelector;
 
 #[derive(Debug, PartialEq)]
@@ -50,8 +125,10 @@ pub enum SimpleSelector {
     TypeSelector {
         tag_name: String,
     },
+    /// `tag[attribute]`, `tag[attribute=value]` or `tag[attribute~=value]`, with or
+    /// without the tag
     AttributeSelector {
-        tag_name: String,
+        tag_name: Option<String>,
         op: AttributeSelectorOp,
         attribute: String,
         value: String,
@@ -59,10 +136,34 @@ pub enum SimpleSelector {
     ClassSelector {
         class_name: String,
     },
+    IdSelector {
+        id: String,
+    },
 }
 
 impl SimpleSelector {
-    pub fn matches(&self, n: &Box<Node>) -> bool {
+    /// Get the specificity of the selector
+    ///
+    /// `tag#id` is read as `tag[id=id]`, so it counts as a type and an attribute.
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::css::css::parse_selector;
+    /// assert_eq!(parse_selector("#result").unwrap().specificity(), (1, 0, 0));
+    /// assert_eq!(parse_selector("div").unwrap().specificity(), (0, 0, 1));
+    /// ```
+    pub fn specificity(&self) -> Specificity {
+        match self {
+            SimpleSelector::UniversalSelector => (0, 0, 0),
+            SimpleSelector::TypeSelector { .. } => (0, 0, 1),
+            SimpleSelector::AttributeSelector { tag_name, .. } => {
+                (0, 1, usize::from(tag_name.is_some()))
+            }
+            SimpleSelector::ClassSelector { .. } => (0, 1, 0),
+            SimpleSelector::IdSelector { .. } => (1, 0, 0),
+        }
+    }
+
+    pub fn matches(&self, n: &Node) -> bool {
         match self {
             SimpleSelector::UniversalSelector => true,
             SimpleSelector::TypeSelector { tag_name } => match n.node_type {
@@ -74,40 +175,33 @@ impl SimpleSelector {
                 op,
                 attribute,
                 value,
-            } => match n.node_type {
-                NodeType::Element(ref e) => {
-                    e.tag_name.as_str() == tag_name
-                        && match op {
-                            AttributeSelectorOp::Eq => e.attributes.get(attribute) == Some(value),
-                            AttributeSelectorOp::Contain => e
-                                .attributes
-                                .get(attribute)
-                                .map(|value| {
-                                    value
-                                        .split_ascii_whitespace()
-                                        .find(|v| v == value)
-                                        .is_some()
-                                })
-                                .unwrap_or(false),
-                        }
-                }
-                _ => false,
-            },
-            SimpleSelector::ClassSelector { class_name } => match n.node_type {
-                NodeType::Element(ref e) => e.attributes.get("class") == Some(class_name),
-                _ => false,
-            },
+            } => {
+                matches!(n.node_type, NodeType::Element(ref e)
+                    if tag_name.as_ref().is_none_or(|tag_name| e.tag_name == *tag_name))
+                    && match op {
+                        AttributeSelectorOp::Exists => n.has_attribute(attribute),
+                        AttributeSelectorOp::Eq => n.get_attribute(attribute) == Some(value),
+                        AttributeSelectorOp::Contain => n
+                            .get_attribute(attribute)
+                            .is_some_and(|v| v.split_ascii_whitespace().any(|v| v == value)),
+                    }
+            }
+            SimpleSelector::ClassSelector { class_name } => n.has_class(class_name),
+            SimpleSelector::IdSelector { id } => n.get_attribute("id") == Some(id.as_str()),
         }
     }
 }
 
 #[derive(Debug, PartialEq)]
 pub enum AttributeSelectorOp {
+    /// The attribute is there, whatever its value
+    Exists,
     Eq,
     Contain,
 }
 
-/// Parse CSS stylesheet
+/// Parse CSS stylesheet, dropping the declarations whose value can't be parsed and
+/// the rules that can't be parsed, and skipping comments
 /// # Example
 /// ```
 /// use tiny_browserbook::css::css::parse;
@@ -124,29 +218,143 @@ pub enum AttributeSelectorOp {
 /// assert_eq!(result.rules.len(), 2);
 /// ```
 pub fn parse(raw: &str) -> Stylesheet {
-    rules()
-        .parse(raw)
+    let raw = close_comment(raw);
+    let (rules, _) = rules(true).parse(raw.as_ref()).unwrap();
+    Stylesheet::new(rules)
+}
+
+/// Parse CSS stylesheet, failing on malformed rules or declarations or unparsed
+/// trailing input
+/// # Example
+/// ```
+/// use tiny_browserbook::css::css::try_parse;
+/// assert_eq!(try_parse("p { display: block; }").unwrap().rules.len(), 1);
+/// assert!(try_parse("p { display: block; ").is_err());
+/// assert!(try_parse("p { color: #ff00; }").is_err());
+/// assert!(try_parse("p { display: block; } /* unterminated").is_err());
+/// ```
+pub fn try_parse(raw: &str) -> Result<Stylesheet, BrowserError> {
+    rules(false)
+        .skip(eof())
+        .easy_parse(position::Stream::new(raw))
         .map(|(rules, _)| Stylesheet::new(rules))
-        .unwrap()
+        .map_err(|e| {
+            CssParseError {
+                message: e.to_string(),
+            }
+            .into()
+        })
+}
+
+/// Parse a comma-separated list of selectors, as written before a rule's declarations
+/// # Example
+/// ```
+/// use tiny_browserbook::css::css::{try_parse_selectors, SimpleSelector};
+/// let selectors = try_parse_selectors("p, .note").unwrap();
+/// assert_eq!(selectors[0], SimpleSelector::TypeSelector { tag_name: "p".into() });
+/// assert!(try_parse_selectors("p {").is_err());
+/// ```
+pub fn try_parse_selectors(raw: &str) -> Result<Vec<Selector>, BrowserError> {
+    (whitespaces(), selectors())
+        .map(|(_, selectors)| selectors)
+        .skip(eof())
+        .easy_parse(position::Stream::new(raw))
+        .map(|(selectors, _)| selectors)
+        .map_err(|e| {
+            CssParseError {
+                message: e.to_string(),
+            }
+            .into()
+        })
 }
 
-fn whitespaces<Input>() -> impl Parser<Input, Output = String>
+/// Parse a single selector
+/// # Example
+/// ```
+/// use tiny_browserbook::css::css::{parse_selector, SimpleSelector};
+/// assert_eq!(parse_selector(" * "), Ok(SimpleSelector::UniversalSelector));
+/// assert!(parse_selector("p, .note").is_err());
+/// ```
+pub fn parse_selector(raw: &str) -> Result<Selector, BrowserError> {
+    (whitespaces(), simple_selector().skip(whitespaces()))
+        .map(|(_, selector)| selector)
+        .skip(eof())
+        .easy_parse(position::Stream::new(raw))
+        .map(|(selector, _)| selector)
+        .map_err(|e| {
+            CssParseError {
+                message: e.to_string(),
+            }
+            .into()
+        })
+}
+
+/// Skip whitespace and `/* ... */` comments
+fn whitespaces<Input>() -> impl Parser<Input, Output = ()>
 where
     Input: Stream<Token = char>,
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
 {
-    many::<String, _, _>(space().or(newline()))
+    skip_many(choice((space().or(newline()).map(|_| ()), comment())))
 }
 
-fn rules<Input>() -> impl Parser<Input, Output = Vec<Rule>>
+/// A comment ends at the first `*/`, so comments don't nest, and one left
+/// unterminated is an error
+fn comment<Input>() -> impl Parser<Input, Output = ()>
 where
     Input: Stream<Token = char>,
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
 {
-    (whitespaces(), many(rule().skip(whitespaces()))).map(|(_, rules)| rules)
+    (
+        attempt(char::string("/*")),
+        skip_until(attempt(char::string("*/"))),
+        char::string("*/"),
+    )
+        .map(|_| ())
+}
+
+/// Close a comment left open at the end of `raw`, as the end of a stylesheet
+/// ends its last comment
+fn close_comment(raw: &str) -> Cow<'_, str> {
+    let mut rest = raw;
+    while let Some(start) = rest.find("/*") {
+        match rest[start + 2..].find("*/") {
+            Some(end) => rest = &rest[start + 2 + end + 2..],
+            None => return Cow::Owned(format!("{}*/", raw)),
+        }
+    }
+    Cow::Borrowed(raw)
 }
 
-fn rule<Input>() -> impl Parser<Input, Output = Rule>
+/// With `recover`, declarations that can't be parsed are skipped up to the next `;`
+/// or `}` and dropped, and rules that can't be parsed up to the next `}`, instead of
+/// failing the whole stylesheet
+fn rules<Input>(recover: bool) -> impl Parser<Input, Output = Vec<Rule>>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let item = if recover {
+        choice((
+            attempt(rule(true)).map(Some),
+            choice((
+                skip_many1(none_of("}".chars())).skip(optional(char::char('}'))),
+                char::char('}').map(|_| ()),
+            ))
+            .map(|_| None),
+        ))
+        .left()
+    } else {
+        rule(false).map(Some).right()
+    };
+    (
+        whitespaces(),
+        many::<Vec<_>, _, _>(item.skip(whitespaces())),
+    )
+        .map(|(_, rules)| rules.into_iter().flatten().collect())
+}
+
+fn rule<Input>(recover: bool) -> impl Parser<Input, Output = Rule>
 where
     Input: Stream<Token = char>,
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
@@ -154,7 +362,7 @@ where
     (
         selectors().skip(whitespaces()),
         char::char('{').skip(whitespaces()),
-        declarations().skip(whitespaces()),
+        declarations(recover).skip(whitespaces()),
         char::char('}'),
     )
         .map(|(selectors, _, declarations, _)| Rule {
@@ -174,64 +382,126 @@ where
     )
 }
 
+/// A name starting with a letter, then letters, digits and hyphens
+fn identifier<Input>() -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        letter(),
+        many::<String, _, _>(alpha_num().or(char::char('-'))),
+    )
+        .map(|(first, rest)| format!("{}{}", first, rest))
+}
+
+/// `[attribute]` and the rest of the bracket of an attribute selector, from just
+/// after the tag name if there is one
+fn attribute<Input>() -> impl Parser<Input, Output = (String, AttributeSelectorOp, String)>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        char::char('[').skip(whitespaces()),
+        many1(letter()),
+        optional((
+            choice((char::string("="), char::string("~="))),
+            many1(letter()),
+        )),
+        char::char(']'),
+    )
+        .and_then(|(_, attribute, op_value, _)| {
+            let (op, value) = match op_value {
+                None => (AttributeSelectorOp::Exists, String::new()),
+                Some(("=", value)) => (AttributeSelectorOp::Eq, value),
+                Some(("~=", value)) => (AttributeSelectorOp::Contain, value),
+                Some(_) => {
+                    return Err(<Input::Error as combine::error::ParseError<
+                        char,
+                        Input::Range,
+                        Input::Position,
+                    >>::StreamError::message_static_message(
+                        "invalid attribute selector op",
+                    ))
+                }
+            };
+            Ok((attribute, op, value))
+        })
+}
+
+/// `tag#id` is read as the selector `tag[id=id]`, and `tag.class` as `tag[class~=class]`
 fn simple_selector<Input>() -> impl Parser<Input, Output = SimpleSelector>
 where
     Input: Stream<Token = char>,
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
 {
     let universal_selector = char::char('*').map(|_| SimpleSelector::UniversalSelector);
-    let class_selector = (char::char('.'), many1(letter()))
+    let class_selector = (char::char('.'), identifier())
         .map(|(_, class_name)| SimpleSelector::ClassSelector { class_name });
+    let id_selector =
+        (char::char('#'), identifier()).map(|(_, id)| SimpleSelector::IdSelector { id });
+    let tagless_attribute_selector =
+        attribute().map(|(attribute, op, value)| SimpleSelector::AttributeSelector {
+            tag_name: None,
+            op,
+            attribute,
+            value,
+        });
     let type_or_attribute_selector = (
-        many1(letter()).skip(whitespaces()),
-        optional((
-            char::char('[').skip(whitespaces()),
-            many1(letter()),
-            choice((char::string("="), char::string("~="))),
-            many1(letter()),
-            char::char(']'),
+        many1(letter()),
+        choice((
+            (char::char('#'), identifier())
+                .map(|(_, id)| Some(("id".to_string(), AttributeSelectorOp::Eq, id))),
+            (char::char('.'), identifier()).map(|(_, class_name)| {
+                Some((
+                    "class".to_string(),
+                    AttributeSelectorOp::Contain,
+                    class_name,
+                ))
+            }),
+            whitespaces().with(optional(attribute())),
         )),
     )
-        .and_then(|(tag_name, opts)| match opts {
-            Some((_, attribute, op, value, _)) => {
-                let op = match op {
-                    "=" => AttributeSelectorOp::Eq,
-                    "~=" => AttributeSelectorOp::Contain,
-                    _ => {
-                        return Err(<Input::Error as combine::error::ParseError<
-                            char,
-                            Input::Range,
-                            Input::Position,
-                        >>::StreamError::message_static_message(
-                            "invalid attribute selector op",
-                        ))
-                    }
-                };
-                Ok(SimpleSelector::AttributeSelector {
-                    tag_name,
-                    op,
-                    attribute,
-                    value,
-                })
-            }
-            None => Ok(SimpleSelector::TypeSelector { tag_name: tag_name }),
+        .map(|(tag_name, attribute)| match attribute {
+            Some((attribute, op, value)) => SimpleSelector::AttributeSelector {
+                tag_name: Some(tag_name),
+                op,
+                attribute,
+                value,
+            },
+            None => SimpleSelector::TypeSelector { tag_name },
         });
     choice((
         universal_selector,
         class_selector,
+        id_selector,
+        tagless_attribute_selector,
         type_or_attribute_selector,
     ))
 }
 
-fn declarations<Input>() -> impl Parser<Input, Output = Vec<Declaration>>
+fn declarations<Input>(recover: bool) -> impl Parser<Input, Output = Vec<Declaration>>
 where
     Input: Stream<Token = char>,
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
 {
-    sep_end_by(
-        declaration().skip(whitespaces()),
-        char::char(';').skip(whitespaces()),
-    )
+    let item = if recover {
+        choice((
+            attempt(
+                declaration()
+                    .skip(whitespaces())
+                    .skip(look_ahead(one_of(";}".chars()))),
+            )
+            .map(Some),
+            skip_many1(none_of(";}".chars())).map(|_| None),
+        ))
+        .left()
+    } else {
+        declaration().skip(whitespaces()).map(Some).right()
+    };
+    sep_end_by::<Vec<_>, _, _, _>(item, char::char(';').skip(whitespaces()))
+        .map(|declarations| declarations.into_iter().flatten().collect())
 }
 
 fn declaration<Input>() -> impl Parser<Input, Output = Declaration>
@@ -240,37 +510,201 @@ where
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
 {
     (
-        many1(letter()).skip(whitespaces()),
+        (
+            letter().or(char::char('-')),
+            many::<String, _, _>(alpha_num().or(char::char('-'))),
+        )
+            .map(|(first, rest)| format!("{}{}", first, rest))
+            .skip(whitespaces()),
         char::char(':').skip(whitespaces()),
-        css_value(),
+        declaration_value(),
     )
         .map(|(k, _, v)| Declaration { name: k, value: v })
 }
 
+/// The value of a declaration, unwrapped when it is a single component
+fn declaration_value<Input>() -> impl Parser<Input, Output = CSSValue>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let components = many1::<Vec<_>, _, _>(css_value().skip(whitespaces())).map(unwrap_single);
+    sep_by1::<Vec<_>, _, _, _>(
+        components.map(|components| match components {
+            Ok(component) => component,
+            Err(components) => CSSValue::List(components),
+        }),
+        char::char(',').skip(whitespaces()),
+    )
+    .map(|items| match unwrap_single(items) {
+        Ok(item) => item,
+        Err(items) => CSSValue::CommaList(items),
+    })
+}
+
+/// The only value of `values`, or all of them when there are several
+fn unwrap_single(mut values: Vec<CSSValue>) -> Result<CSSValue, Vec<CSSValue>> {
+    if values.len() == 1 {
+        Ok(values.pop().unwrap())
+    } else {
+        Err(values)
+    }
+}
+
 fn css_value<Input>() -> impl Parser<Input, Output = CSSValue>
 where
     Input: Stream<Token = char>,
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
 {
-    many1(letter()).map(|s| CSSValue::Keyword(s))
+    choice((
+        attempt(length()).map(|(value, unit)| CSSValue::Length(value, unit)),
+        hex_color(),
+        rgb_color(),
+        identifier().and_then(|keyword| {
+            if unit(&keyword).is_some() {
+                return Err(<Input::Error as combine::error::ParseError<
+                    char,
+                    Input::Range,
+                    Input::Position,
+                >>::StreamError::message_static_message(
+                    "a unit needs a number before it",
+                ));
+            }
+            Ok(named_color(&keyword).unwrap_or(CSSValue::Keyword(keyword)))
+        }),
+    ))
+}
+
+/// `#rgb` or `#rrggbb`
+fn hex_color<Input>() -> impl Parser<Input, Output = CSSValue>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (char::char('#'), many1::<String, _, _>(hex_digit())).and_then(|(_, hex)| {
+        let channel = |digits: &str| u8::from_str_radix(digits, 16).unwrap();
+        let (r, g, b) = match hex.len() {
+            3 => {
+                let double = |i: usize| channel(&hex[i..i + 1].repeat(2));
+                (double(0), double(1), double(2))
+            }
+            6 => (
+                channel(&hex[0..2]),
+                channel(&hex[2..4]),
+                channel(&hex[4..6]),
+            ),
+            _ => {
+                return Err(<Input::Error as combine::error::ParseError<
+                    char,
+                    Input::Range,
+                    Input::Position,
+                >>::StreamError::message_static_message(
+                    "hex colors have 3 or 6 digits",
+                ))
+            }
+        };
+        Ok(CSSValue::Color { r, g, b })
+    })
+}
+
+/// `rgb(r, g, b)` with each channel from 0 to 255, larger ones being clamped
+fn rgb_color<Input>() -> impl Parser<Input, Output = CSSValue>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let channel = || {
+        many1::<String, _, _>(digit())
+            .map(|digits| digits.parse::<u32>().map_or(255, |n| n.min(255)) as u8)
+            .skip(whitespaces())
+    };
+    (
+        attempt(char::string("rgb(")).skip(whitespaces()),
+        channel().skip(char::char(',').skip(whitespaces())),
+        channel().skip(char::char(',').skip(whitespaces())),
+        channel().skip(char::char(')')),
+    )
+        .map(|(_, r, g, b)| CSSValue::Color { r, g, b })
+}
+
+/// A number with an optional sign and fraction, like `-1`, `0.5` or `.5`
+fn number<Input>() -> impl Parser<Input, Output = f32>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        optional(char::char('-').or(char::char('+'))),
+        many::<String, _, _>(digit()),
+        optional((char::char('.'), many1::<String, _, _>(digit()))),
+    )
+        .and_then(|(sign, integer, fraction)| {
+            if integer.is_empty() && fraction.is_none() {
+                return Err(<Input::Error as combine::error::ParseError<
+                    char,
+                    Input::Range,
+                    Input::Position,
+                >>::StreamError::message_static_message(
+                    "expected a number"
+                ));
+            }
+            let fraction = fraction.map(|(_, digits)| digits).unwrap_or_default();
+            let value: f32 = format!("0{}.{}0", integer, fraction).parse().unwrap();
+            Ok(if sign == Some('-') { -value } else { value })
+        })
+}
+
+/// The unit named `name`, ignoring case, other than `%`
+fn unit(name: &str) -> Option<Unit> {
+    match name.to_ascii_lowercase().as_str() {
+        "px" => Some(Unit::Px),
+        "em" => Some(Unit::Em),
+        "ch" => Some(Unit::Ch),
+        _ => None,
+    }
+}
+
+/// A number followed by its unit, if any
+fn length<Input>() -> impl Parser<Input, Output = (f32, Unit)>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let unit = choice((
+        char::char('%').map(|_| Some(Unit::Percent)),
+        many1::<String, _, _>(letter()).map(|name| unit(&name)),
+    ));
+    (number(), optional(unit)).and_then(|(value, unit)| match unit {
+        None => Ok((value, Unit::Number)),
+        Some(Some(unit)) => Ok((value, unit)),
+        Some(None) => Err(<Input::Error as combine::error::ParseError<
+            char,
+            Input::Range,
+            Input::Position,
+        >>::StreamError::message_static_message(
+            "unknown unit"
+        )),
+    })
 }
 
 #[cfg(test)]
 mod tests {
 
-    use crate::html::dom::Element;
+    use rstest::rstest;
+
+    use crate::html::dom::{AttrMap, Element};
 
     use super::*;
 
     #[test]
     fn test_rules() {
         assert_eq!(
-            rules().parse("test [foo=bar] { aa: bb; cc: dd; } rule { ee: dd; }"),
+            rules(false).parse("test [foo=bar] { aa: bb; cc: dd; } rule { ee: dd; }"),
             Ok((
                 vec![
                     Rule {
                         selectors: vec![SimpleSelector::AttributeSelector {
-                            tag_name: "test".to_string(),
+                            tag_name: Some("test".to_string()),
                             op: AttributeSelectorOp::Eq,
                             attribute: "foo".to_string(),
                             value: "bar".to_string()
@@ -304,11 +738,11 @@ mod tests {
     #[test]
     fn test_rule() {
         assert_eq!(
-            rule().parse("test [foo=bar] {}"),
+            rule(false).parse("test [foo=bar] {}"),
             Ok((
                 Rule {
                     selectors: vec![SimpleSelector::AttributeSelector {
-                        tag_name: "test".to_string(),
+                        tag_name: Some("test".to_string()),
                         attribute: "foo".to_string(),
                         op: AttributeSelectorOp::Eq,
                         value: "bar".to_string()
@@ -323,18 +757,18 @@ mod tests {
     #[test]
     fn test_rule_multiple() {
         assert_eq!(
-            rule().parse("test [foo=bar], testtest[piyo~=guoo] {}"),
+            rule(false).parse("test [foo=bar], testtest[piyo~=guoo] {}"),
             Ok((
                 Rule {
                     selectors: vec![
                         SimpleSelector::AttributeSelector {
-                            tag_name: "test".to_string(),
+                            tag_name: Some("test".to_string()),
                             attribute: "foo".to_string(),
                             op: AttributeSelectorOp::Eq,
                             value: "bar".to_string()
                         },
                         SimpleSelector::AttributeSelector {
-                            tag_name: "testtest".to_string(),
+                            tag_name: Some("testtest".to_string()),
                             attribute: "piyo".to_string(),
                             op: AttributeSelectorOp::Contain,
                             value: "guoo".to_string()
@@ -350,11 +784,11 @@ mod tests {
     #[test]
     fn test_rule_value() {
         assert_eq!(
-            rule().parse("test [foo=bar] { aa: bb; cc: dd; }"),
+            rule(false).parse("test [foo=bar] { aa: bb; cc: dd; }"),
             Ok((
                 Rule {
                     selectors: vec![SimpleSelector::AttributeSelector {
-                        tag_name: "test".to_string(),
+                        tag_name: Some("test".to_string()),
                         attribute: "foo".to_string(),
                         op: AttributeSelectorOp::Eq,
                         value: "bar".to_string()
@@ -382,7 +816,7 @@ mod tests {
             Ok((
                 vec![
                     SimpleSelector::AttributeSelector {
-                        tag_name: "test".to_string(),
+                        tag_name: Some("test".to_string()),
                         attribute: "foo".to_string(),
                         op: AttributeSelectorOp::Eq,
                         value: "bar".to_string()
@@ -423,7 +857,7 @@ mod tests {
             simple_selector().parse("test [foo=bar]"),
             Ok((
                 SimpleSelector::AttributeSelector {
-                    tag_name: "test".to_string(),
+                    tag_name: Some("test".to_string()),
                     attribute: "foo".to_string(),
                     op: AttributeSelectorOp::Eq,
                     value: "bar".to_string()
@@ -446,10 +880,103 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_simple_selector_type_with_class() {
+        assert_eq!(
+            simple_selector().parse("p.inline"),
+            Ok((
+                SimpleSelector::AttributeSelector {
+                    tag_name: Some("p".to_string()),
+                    attribute: "class".to_string(),
+                    op: AttributeSelectorOp::Contain,
+                    value: "inline".to_string()
+                },
+                ""
+            ))
+        );
+        assert_eq!(
+            simple_selector().parse(".col-2"),
+            Ok((
+                SimpleSelector::ClassSelector {
+                    class_name: "col-2".to_string(),
+                },
+                ""
+            ))
+        );
+    }
+
+    #[rstest]
+    #[case("[foo]", None, AttributeSelectorOp::Exists, "")]
+    #[case("[foo=bar]", None, AttributeSelectorOp::Eq, "bar")]
+    #[case("[foo~=bar]", None, AttributeSelectorOp::Contain, "bar")]
+    #[case("a[foo]", Some("a"), AttributeSelectorOp::Exists, "")]
+    #[case("a [foo]", Some("a"), AttributeSelectorOp::Exists, "")]
+    fn test_simple_selector_attribute_forms(
+        #[case] raw: &str,
+        #[case] tag_name: Option<&str>,
+        #[case] op: AttributeSelectorOp,
+        #[case] value: &str,
+    ) {
+        assert_eq!(
+            simple_selector().parse(raw),
+            Ok((
+                SimpleSelector::AttributeSelector {
+                    tag_name: tag_name.map(str::to_string),
+                    attribute: "foo".to_string(),
+                    op,
+                    value: value.to_string()
+                },
+                ""
+            ))
+        );
+    }
+
+    #[rstest]
+    #[case("#result", "result")]
+    #[case("#main-2", "main-2")]
+    #[case("#a1-b", "a1-b")]
+    fn test_simple_selector_id(#[case] raw: &str, #[case] id: &str) {
+        assert_eq!(
+            simple_selector().parse(raw),
+            Ok((SimpleSelector::IdSelector { id: id.to_string() }, ""))
+        );
+    }
+
+    #[test]
+    fn test_simple_selector_id_starts_with_letter() {
+        assert!(simple_selector().parse("#2a").is_err());
+        assert!(simple_selector().parse("#-a").is_err());
+    }
+
+    #[test]
+    fn test_simple_selector_type_with_id() {
+        assert_eq!(
+            simple_selector().parse("div#result"),
+            Ok((
+                SimpleSelector::AttributeSelector {
+                    tag_name: Some("div".to_string()),
+                    attribute: "id".to_string(),
+                    op: AttributeSelectorOp::Eq,
+                    value: "result".to_string()
+                },
+                ""
+            ))
+        );
+        assert_eq!(
+            selectors().parse("div #result"),
+            Ok((
+                vec![SimpleSelector::TypeSelector {
+                    tag_name: "div".to_string()
+                }],
+                "#result"
+            ))
+        );
+    }
+
     #[test]
     fn test_declarations() {
         assert_eq!(
-            declarations().parse("foo: bar; piyo: piyopiyo;"),
+            declarations(false).parse("foo: bar; piyo: piyopiyo;"),
             Ok((
                 vec![
                     Declaration {
@@ -466,9 +993,215 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_hyphenated_declaration_name() {
+        assert_eq!(
+            declaration().parse("white-space: pre"),
+            Ok((
+                Declaration {
+                    name: "white-space".to_string(),
+                    value: CSSValue::Keyword("pre".to_string())
+                },
+                ""
+            ))
+        );
+    }
+
+    #[rstest]
+    #[case("font-weight: bold", "font-weight", "bold")]
+    #[case("display: inline-block", "display", "inline-block")]
+    #[case("z-index: auto", "z-index", "auto")]
+    #[case("-webkit-box-flex: x1", "-webkit-box-flex", "x1")]
+    #[case("grid-area: h2-title", "grid-area", "h2-title")]
+    fn test_hyphenated_declaration(#[case] raw: &str, #[case] name: &str, #[case] value: &str) {
+        assert_eq!(
+            declaration().parse(raw),
+            Ok((
+                Declaration {
+                    name: name.to_string(),
+                    value: CSSValue::Keyword(value.to_string())
+                },
+                ""
+            ))
+        );
+    }
+
+    #[rstest]
+    #[case("50px", CSSValue::Length(50.0, Unit::Px))]
+    #[case("0", CSSValue::Length(0.0, Unit::Number))]
+    #[case("1.5", CSSValue::Length(1.5, Unit::Number))]
+    #[case("50%", CSSValue::Length(50.0, Unit::Percent))]
+    #[case("2em", CSSValue::Length(2.0, Unit::Em))]
+    #[case("10CH", CSSValue::Length(10.0, Unit::Ch))]
+    #[case("-.5em", CSSValue::Length(-0.5, Unit::Em))]
+    #[case("+3", CSSValue::Length(3.0, Unit::Number))]
+    fn test_css_value(#[case] raw: &str, #[case] expected: CSSValue) {
+        assert_eq!(css_value().parse(raw), Ok((expected, "")));
+    }
+
+    #[rstest]
+    #[case("red", 255, 0, 0)]
+    #[case("Navy", 0, 0, 128)]
+    #[case("#ff0000", 255, 0, 0)]
+    #[case("#1E90fF", 30, 144, 255)]
+    #[case("#f80", 255, 136, 0)]
+    #[case("rgb(255, 0, 0)", 255, 0, 0)]
+    #[case("rgb(12,34,56)", 12, 34, 56)]
+    #[case("rgb( 300 , 0 , 0 )", 255, 0, 0)]
+    fn test_css_value_color(#[case] raw: &str, #[case] r: u8, #[case] g: u8, #[case] b: u8) {
+        assert_eq!(
+            css_value().parse(raw),
+            Ok((CSSValue::Color { r, g, b }, ""))
+        );
+    }
+
+    #[rstest]
+    #[case("rebeccapurple")]
+    #[case("transparent")]
+    #[case("rgba")]
+    fn test_css_value_unknown_color_name(#[case] raw: &str) {
+        assert_eq!(
+            css_value().parse(raw),
+            Ok((CSSValue::Keyword(raw.to_string()), ""))
+        );
+    }
+
+    #[rstest]
+    #[case("color: #ff00")]
+    #[case("color: #fffffff")]
+    #[case("color: #")]
+    #[case("color: rgb(1, 2)")]
+    #[case("color: rgb(1, 2, x)")]
+    fn test_invalid_color(#[case] raw: &str) {
+        assert!(declaration().parse(raw).is_err());
+        assert!(try_parse(&format!("p {{ {}; }}", raw)).is_err());
+        assert_eq!(
+            parse(&format!("p {{ display: block; {}; width: 1 }}", raw)),
+            Stylesheet::new(vec![Rule {
+                selectors: vec![SimpleSelector::TypeSelector {
+                    tag_name: "p".to_string()
+                }],
+                declarations: vec![
+                    Declaration {
+                        name: "display".to_string(),
+                        value: CSSValue::Keyword("block".to_string())
+                    },
+                    Declaration {
+                        name: "width".to_string(),
+                        value: CSSValue::Length(1.0, Unit::Number)
+                    }
+                ]
+            }])
+        );
+    }
+
+    fn number(value: f32) -> CSSValue {
+        CSSValue::Length(value, Unit::Number)
+    }
+
+    fn keyword(value: &str) -> CSSValue {
+        CSSValue::Keyword(value.to_string())
+    }
+
+    #[rstest]
+    #[case("margin: 1", number(1.0))]
+    #[case("margin: 1 2", CSSValue::List(vec![number(1.0), number(2.0)]))]
+    #[case(
+        "margin: 1 2 1  2",
+        CSSValue::List(vec![number(1.0), number(2.0), number(1.0), number(2.0)])
+    )]
+    #[case(
+        "font: bold 12px monospace",
+        CSSValue::List(vec![
+            keyword("bold"),
+            CSSValue::Length(12.0, Unit::Px),
+            keyword("monospace")
+        ])
+    )]
+    #[case(
+        "font-family: monospace, serif",
+        CSSValue::CommaList(vec![keyword("monospace"), keyword("serif")])
+    )]
+    #[case(
+        "font-family: Noto Sans Mono ,monospace",
+        CSSValue::CommaList(vec![
+            CSSValue::List(vec![keyword("Noto"), keyword("Sans"), keyword("Mono")]),
+            keyword("monospace")
+        ])
+    )]
+    fn test_multi_component_value(#[case] raw: &str, #[case] value: CSSValue) {
+        let (declaration, _) = declaration().parse(raw).unwrap();
+        assert_eq!(declaration.value, value);
+    }
+
+    #[test]
+    fn test_multi_component_rule() {
+        assert_eq!(
+            try_parse("p { margin: 1 2 ; font-family: monospace, serif; display: block }")
+                .unwrap()
+                .rules[0]
+                .declarations
+                .iter()
+                .map(|declaration| declaration.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["margin", "font-family", "display"]
+        );
+        assert!(try_parse("p { font-family: monospace, ; }").is_err());
+    }
+
+    #[rstest]
+    #[case("width: 50qq")]
+    #[case("width: -")]
+    #[case("width: 5.")]
+    #[case("width: px")]
+    #[case("width: EM")]
+    #[case("width: .px")]
+    fn test_css_value_invalid(#[case] declaration: &str) {
+        assert!(matches!(
+            try_parse(&format!("p {{ {}; }}", declaration)),
+            Err(BrowserError::Css(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_drops_bare_unit() {
+        assert!(css_value().parse("px").is_err());
+        assert_eq!(
+            parse("p { width: px; display: block; }").rules[0].declarations,
+            vec![Declaration {
+                name: "display".to_string(),
+                value: CSSValue::Keyword("block".to_string())
+            }]
+        );
+    }
+
+    #[test]
+    fn test_try_parse_hyphenated_declarations() {
+        let stylesheet = try_parse(
+            "p { font-weight: bold; color: red; } div { background-color: red; display: inline-block; }",
+        )
+        .unwrap();
+        let declarations: Vec<_> = stylesheet
+            .rules
+            .iter()
+            .flat_map(|rule| rule.declarations.iter())
+            .map(|declaration| (declaration.name.as_str(), declaration.value.clone()))
+            .collect();
+        let red = CSSValue::Color { r: 255, g: 0, b: 0 };
+        assert_eq!(
+            declarations,
+            vec![
+                ("font-weight", keyword("bold")),
+                ("color", red.clone()),
+                ("background-color", red),
+                ("display", keyword("inline-block"))
+            ]
+        );
+    }
+
     #[test]
     fn test_universal_selector_behaviour() {
-        let e = &Element::new(
+        let e: &Node = &Element::new(
             "p".to_string(),
             [
                 ("id".to_string(), "test".to_string()),
@@ -484,7 +1217,7 @@ mod tests {
 
     #[test]
     fn test_universal_selector_behaviour_with_tag() {
-        let e = &Element::new(
+        let e: &Node = &Element::new(
             "p".to_string(),
             [
                 ("id".to_string(), "test".to_string()),
@@ -513,7 +1246,7 @@ mod tests {
 
     #[test]
     fn test_attribute_selector_behaviour() {
-        let e = &Element::new(
+        let e: &Node = &Element::new(
             "p".to_string(),
             [
                 ("id".to_string(), "test".to_string()),
@@ -527,7 +1260,7 @@ mod tests {
 
         assert_eq!(
             (SimpleSelector::AttributeSelector {
-                tag_name: "p".into(),
+                tag_name: Some("p".into()),
                 attribute: "id".into(),
                 value: "test".into(),
                 op: AttributeSelectorOp::Eq,
@@ -538,7 +1271,7 @@ mod tests {
 
         assert_eq!(
             (SimpleSelector::AttributeSelector {
-                tag_name: "p".into(),
+                tag_name: Some("p".into()),
                 attribute: "id".into(),
                 value: "invalid".into(),
                 op: AttributeSelectorOp::Eq,
@@ -549,7 +1282,7 @@ mod tests {
 
         assert_eq!(
             (SimpleSelector::AttributeSelector {
-                tag_name: "p".into(),
+                tag_name: Some("p".into()),
                 attribute: "invalid".into(),
                 value: "test".into(),
                 op: AttributeSelectorOp::Eq,
@@ -560,7 +1293,7 @@ mod tests {
 
         assert_eq!(
             (SimpleSelector::AttributeSelector {
-                tag_name: "invalid".into(),
+                tag_name: Some("invalid".into()),
                 attribute: "id".into(),
                 value: "test".into(),
                 op: AttributeSelectorOp::Eq,
@@ -570,9 +1303,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_attribute_selector_contain_behaviour() {
+        let e: &Node = &Element::new(
+            "p".to_string(),
+            [("class".to_string(), "a  b".to_string())]
+                .iter()
+                .cloned()
+                .collect(),
+            vec![],
+        );
+        let contain = |value: &str| SimpleSelector::AttributeSelector {
+            tag_name: Some("p".into()),
+            attribute: "class".into(),
+            value: value.into(),
+            op: AttributeSelectorOp::Contain,
+        };
+        assert!(contain("b").matches(e));
+        assert!(!contain("c").matches(e));
+        assert!(!contain("a  b").matches(e));
+    }
+
     #[test]
     fn test_class_selector_behaviour() {
-        let e = &Element::new(
+        let e: &Node = &Element::new(
             "p".to_string(),
             [
                 ("id".to_string(), "test".to_string()),
@@ -599,5 +1353,264 @@ mod tests {
             .matches(e),
             false
         );
+        let mut e = Element::new("p".to_string(), AttrMap::new(), vec![]);
+        e.set_attribute("class", " note  testclass ");
+        assert!(e.matches(&SimpleSelector::ClassSelector {
+            class_name: "testclass".into()
+        }));
+    }
+
+    #[test]
+    fn test_id_selector_behaviour() {
+        let node = crate::html::html::parse(r#"<div id="result"></div><p id="result-2"></p>"#);
+        let body = node.body().unwrap();
+        let id = |id: &str| SimpleSelector::IdSelector { id: id.into() };
+
+        assert!(id("result").matches(&body.children[0]));
+        assert!(!id("result").matches(&body.children[1]));
+        assert!(id("result-2").matches(&body.children[1]));
+        assert!(!id("Result").matches(&body.children[0]));
+        assert!(!id("result").matches(body));
+        assert!(!parse_selector("p#result")
+            .unwrap()
+            .matches(&body.children[0]));
+        assert!(parse_selector("div#result")
+            .unwrap()
+            .matches(&body.children[0]));
+    }
+
+    #[rstest]
+    #[case(AttributeSelectorOp::Eq, "id", "test", true)]
+    #[case(AttributeSelectorOp::Eq, "id", "tes", false)]
+    #[case(AttributeSelectorOp::Eq, "id", "TEST", false)]
+    #[case(AttributeSelectorOp::Eq, "class", "a", false)]
+    #[case(AttributeSelectorOp::Eq, "class", "a b", true)]
+    #[case(AttributeSelectorOp::Eq, "title", "", true)]
+    #[case(AttributeSelectorOp::Eq, "lang", "", false)]
+    #[case(AttributeSelectorOp::Eq, "lang", "test", false)]
+    #[case(AttributeSelectorOp::Contain, "class", "a", true)]
+    #[case(AttributeSelectorOp::Contain, "class", "b", true)]
+    #[case(AttributeSelectorOp::Contain, "class", "a b", false)]
+    #[case(AttributeSelectorOp::Contain, "class", "", false)]
+    #[case(AttributeSelectorOp::Contain, "id", "test", true)]
+    #[case(AttributeSelectorOp::Contain, "title", "", false)]
+    #[case(AttributeSelectorOp::Contain, "lang", "a", false)]
+    fn test_attribute_selector_ops(
+        #[case] op: AttributeSelectorOp,
+        #[case] attribute: &str,
+        #[case] value: &str,
+        #[case] expected: bool,
+    ) {
+        let p = &crate::html::html::parse_fragment(r#"<p id="test" class="a b" title="">x</p>"#)[0];
+        let selector = SimpleSelector::AttributeSelector {
+            tag_name: Some("p".into()),
+            op,
+            attribute: attribute.into(),
+            value: value.into(),
+        };
+        assert_eq!(selector.matches(p), expected);
+        assert!(!selector.matches(&p.children[0]));
+    }
+
+    #[rstest]
+    #[case("[foo]", r#"<div foo>x</div>"#, true)]
+    #[case("[foo]", r#"<span foo="bar">x</span>"#, true)]
+    #[case("[foo]", r#"<div bar="foo">x</div>"#, false)]
+    #[case("[foo]", r#"<div>x</div>"#, false)]
+    #[case("[foo=bar]", r#"<div foo="bar">x</div>"#, true)]
+    #[case("[foo=bar]", r#"<span foo="bar">x</span>"#, true)]
+    #[case("[foo=bar]", r#"<div foo="baz">x</div>"#, false)]
+    #[case("[foo=bar]", r#"<div foo>x</div>"#, false)]
+    #[case("[foo=bar]", r#"<div>x</div>"#, false)]
+    #[case("[disabled]", r#"<input disabled>"#, true)]
+    #[case("[disabled]", r#"<input disabled="">"#, true)]
+    #[case("[disabled]", r#"<button disabled>x</button>"#, true)]
+    #[case("[disabled]", r#"<input>"#, false)]
+    #[case("[type=checkbox]", r#"<input type="checkbox">"#, true)]
+    #[case("[type=checkbox]", r#"<input type="radio">"#, false)]
+    #[case("[type=checkbox]", r#"<input>"#, false)]
+    #[case("a[href]", r#"<a href="/">x</a>"#, true)]
+    #[case("a[href]", r#"<a href="">x</a>"#, true)]
+    #[case("a[href]", r#"<a name="top">x</a>"#, false)]
+    #[case("a[href]", r#"<link href="/">"#, false)]
+    fn test_attribute_selector_forms_behaviour(
+        #[case] selector: &str,
+        #[case] html: &str,
+        #[case] expected: bool,
+    ) {
+        let selector = parse_selector(selector).unwrap();
+        let e = &crate::html::html::parse_fragment(html)[0];
+        assert_eq!(selector.matches(e), expected);
+        if let Some(text) = e.children.first() {
+            assert!(!selector.matches(text));
+        }
+    }
+
+    #[rstest]
+    #[case(r#"<p class="inline">a</p>"#, true)]
+    #[case(r#"<p class="a inline b">a</p>"#, true)]
+    #[case("<p class=\"\tinline\n\">a</p>", true)]
+    #[case(r#"<p class="inline-block">a</p>"#, false)]
+    #[case(r#"<p class="a b">a</p>"#, false)]
+    #[case(r#"<p>a</p>"#, false)]
+    fn test_class_selector_tokens(#[case] html: &str, #[case] expected: bool) {
+        let p = &crate::html::html::parse_fragment(html)[0];
+        let inline = SimpleSelector::ClassSelector {
+            class_name: "inline".into(),
+        };
+        assert_eq!(inline.matches(p), expected);
+        assert_eq!(parse_selector("p.inline").unwrap().matches(p), expected);
+        assert!(!parse_selector("div.inline").unwrap().matches(p));
+        assert!(!inline.matches(&p.children[0]));
+    }
+
+    #[test]
+    fn test_attribute_selector_single_quoted_value() {
+        let node = crate::html::html::parse("<p id='test' class='a \"b\"'></p>");
+        let e = &node.body().unwrap().children[0];
+
+        assert!((SimpleSelector::AttributeSelector {
+            tag_name: Some("p".into()),
+            attribute: "id".into(),
+            value: "test".into(),
+            op: AttributeSelectorOp::Eq,
+        })
+        .matches(e));
+        assert!((SimpleSelector::AttributeSelector {
+            tag_name: Some("p".into()),
+            attribute: "class".into(),
+            value: "a \"b\"".into(),
+            op: AttributeSelectorOp::Eq,
+        })
+        .matches(e));
+    }
+
+    #[test]
+    fn test_try_parse() {
+        assert_eq!(
+            try_parse("p { display: block; }"),
+            Ok(Stylesheet::new(vec![Rule {
+                selectors: vec![SimpleSelector::TypeSelector {
+                    tag_name: "p".into()
+                }],
+                declarations: vec![Declaration {
+                    name: "display".into(),
+                    value: CSSValue::Keyword("block".into())
+                }],
+            }]))
+        );
+    }
+
+    #[test]
+    fn test_try_parse_invalid() {
+        assert!(matches!(
+            try_parse("p { display: block; } }"),
+            Err(BrowserError::Css(_))
+        ));
+    }
+
+    #[rstest]
+    #[case("/* head */ p { display: block; } /* tail */", "p { display: block; }")]
+    #[case(
+        "p { display: block; } /* between */ div { display: none; }",
+        "p { display: block; } div { display: none; }"
+    )]
+    #[case(
+        "p /* c */, /* c */ div { display: block; }",
+        "p, div { display: block; }"
+    )]
+    #[case("p { /* c */ display: block; /* c */ }", "p { display: block; }")]
+    #[case(
+        "p { display: block; /* c */ margin: 1 2; }",
+        "p { display: block; margin: 1 2; }"
+    )]
+    #[case(
+        "p { display /* c */ : /* c */ block /* c */ ; }",
+        "p { display: block; }"
+    )]
+    #[case("p { margin: 1 /* c */ 2, /* c */ 3; }", "p { margin: 1 2, 3; }")]
+    #[case("p { display: block; /* ; } div { */ }", "p { display: block; }")]
+    #[case("p { display: block; } /**/ /***/ /* * / */", "p { display: block; }")]
+    fn test_comments(#[case] css: &str, #[case] without_comments: &str) {
+        let expected = try_parse(without_comments).unwrap();
+        assert_eq!(try_parse(css), Ok(expected));
+        assert_eq!(parse(css), try_parse(without_comments).unwrap());
+    }
+
+    #[test]
+    fn test_comments_in_declaration_block() {
+        assert_eq!(
+            try_parse("p {\n  /* the display */\n  display: block; /* done */\n}"),
+            try_parse("p { display: block; }")
+        );
+        assert_eq!(
+            try_parse("p /* c */ { margin: 1 /* c */ 2; }"),
+            try_parse("p { margin: 1 2; }")
+        );
+    }
+
+    #[test]
+    fn test_nested_comment() {
+        let css = "p { display: block; } /* a /* b */ c */ div { display: none; }";
+        assert!(matches!(try_parse(css), Err(BrowserError::Css(_))));
+        assert_eq!(
+            parse(css),
+            Stylesheet::new(vec![Rule {
+                selectors: vec![SimpleSelector::TypeSelector {
+                    tag_name: "p".into()
+                }],
+                declarations: vec![Declaration {
+                    name: "display".into(),
+                    value: CSSValue::Keyword("block".into())
+                }],
+            }])
+        );
+    }
+
+    #[rstest]
+    #[case("p { display: block; } /* unterminated")]
+    #[case("p { display: block; } /* unterminated *")]
+    fn test_unterminated_comment(#[case] css: &str) {
+        assert!(matches!(try_parse(css), Err(BrowserError::Css(_))));
+        assert_eq!(parse(css), try_parse("p { display: block; }").unwrap());
+    }
+
+    #[rstest]
+    #[case("*", (0, 0, 0))]
+    #[case("p", (0, 0, 1))]
+    #[case(".a", (0, 1, 0))]
+    #[case("[a]", (0, 1, 0))]
+    #[case("p[a=b]", (0, 1, 1))]
+    #[case("p.a", (0, 1, 1))]
+    #[case("#a", (1, 0, 0))]
+    fn test_specificity(#[case] selector: &str, #[case] expected: Specificity) {
+        assert_eq!(parse_selector(selector).unwrap().specificity(), expected);
+    }
+
+    #[test]
+    fn test_rule_specificity_for() {
+        let stylesheet = parse("p, .a, #b { display: block; }");
+        let rule = &stylesheet.rules[0];
+        let nodes = crate::html::html::parse_fragment(
+            r#"<p class="a" id="b"></p><p class="a"></p><div></div>"#,
+        );
+        assert_eq!(rule.specificity_for(&nodes[0]), Some((1, 0, 0)));
+        assert_eq!(rule.specificity_for(&nodes[1]), Some((0, 1, 0)));
+        assert_eq!(rule.specificity_for(&nodes[2]), None);
+    }
+
+    #[test]
+    fn test_parse_selector() {
+        assert_eq!(
+            parse_selector("p[id=main]"),
+            Ok(SimpleSelector::AttributeSelector {
+                tag_name: Some("p".into()),
+                op: AttributeSelectorOp::Eq,
+                attribute: "id".into(),
+                value: "main".into(),
+            })
+        );
+        assert!(matches!(parse_selector(""), Err(BrowserError::Css(_))));
+        assert!(matches!(parse_selector("p {"), Err(BrowserError::Css(_))));
     }
 }
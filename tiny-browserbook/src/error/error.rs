@@ -0,0 +1,247 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HtmlParseError {
+    pub message: String,
+    /// Byte offset in the input
+    pub position: usize,
+    /// 1-based line and column, counted in characters
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CssParseError {
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsError {
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderError {
+    pub message: String,
+}
+
+/// Why a change to the tree of a [`Document`](crate::html::dom::Document) was refused
+#[derive(Debug, Clone, PartialEq)]
+pub enum DomError {
+    /// The parent is a text node, which can't have children
+    TextParent,
+    /// The node would be inserted into itself or one of its descendants
+    Cycle,
+    /// The node, or the reference node, is not a child of the parent
+    NotAChild,
+}
+
+impl fmt::Display for DomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DomError::TextParent => write!(f, "text nodes can't have children"),
+            DomError::Cycle => write!(f, "a node can't be inserted into itself"),
+            DomError::NotAChild => write!(f, "the node is not a child of the parent"),
+        }
+    }
+}
+
+/// Why a resource could not be loaded
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadError {
+    /// The request failed, for the reason given
+    Failed(String),
+    /// The resource is `size` bytes, by its declared length or as far as it was read
+    /// before giving up, over the `limit` allowed for it
+    TooLarge { size: usize, limit: usize },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Failed(message) => write!(f, "{}", message),
+            LoadError::TooLarge { size, limit } => write!(
+                f,
+                "resource of {} bytes is over the limit of {} bytes",
+                size, limit
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<String> for LoadError {
+    fn from(message: String) -> Self {
+        LoadError::Failed(message)
+    }
+}
+
+/// Failures of the public fallible APIs
+/// # Example
+/// ```
+/// use tiny_browserbook::css::css::try_parse;
+/// use tiny_browserbook::error::error::BrowserError;
+/// match try_parse("p { display: block; ") {
+///     Err(BrowserError::Css(e)) => assert!(!e.message.is_empty()),
+///     _ => unreachable!(),
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum BrowserError {
+    Html(HtmlParseError),
+    Css(CssParseError),
+    Js(JsError),
+    Render(RenderError),
+    Dom(DomError),
+    Load(LoadError),
+}
+
+impl fmt::Display for BrowserError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BrowserError::Html(e) => write!(
+                f,
+                "html parse error at line {}, column {}: {}",
+                e.line, e.column, e.message
+            ),
+            BrowserError::Css(e) => write!(f, "css parse error: {}", e.message),
+            BrowserError::Js(e) => write!(f, "javascript error: {}", e.message),
+            BrowserError::Render(e) => write!(f, "render error: {}", e.message),
+            BrowserError::Dom(e) => write!(f, "dom error: {}", e),
+            BrowserError::Load(e) => write!(f, "load error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for BrowserError {}
+
+impl From<HtmlParseError> for BrowserError {
+    fn from(e: HtmlParseError) -> Self {
+        BrowserError::Html(e)
+    }
+}
+
+impl From<CssParseError> for BrowserError {
+    fn from(e: CssParseError) -> Self {
+        BrowserError::Css(e)
+    }
+}
+
+impl From<JsError> for BrowserError {
+    fn from(e: JsError) -> Self {
+        BrowserError::Js(e)
+    }
+}
+
+impl From<RenderError> for BrowserError {
+    fn from(e: RenderError) -> Self {
+        BrowserError::Render(e)
+    }
+}
+
+impl From<DomError> for BrowserError {
+    fn from(e: DomError) -> Self {
+        BrowserError::Dom(e)
+    }
+}
+
+impl From<LoadError> for BrowserError {
+    fn from(e: LoadError) -> Self {
+        BrowserError::Load(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case(
+        HtmlParseError {
+            message: "unexpected `<`".into(),
+            position: 12,
+            line: 2,
+            column: 3,
+        }
+        .into(),
+        "html parse error at line 2, column 3: unexpected `<`"
+    )]
+    #[case(
+        CssParseError { message: "expected `}`".into() }.into(),
+        "css parse error: expected `}`"
+    )]
+    #[case(
+        JsError { message: "ReferenceError: x is not defined".into() }.into(),
+        "javascript error: ReferenceError: x is not defined"
+    )]
+    #[case(
+        RenderError { message: "nothing to display".into() }.into(),
+        "render error: nothing to display"
+    )]
+    #[case(DomError::Cycle.into(), "dom error: a node can't be inserted into itself")]
+    #[case(
+        LoadError::TooLarge { size: 11, limit: 10 }.into(),
+        "load error: resource of 11 bytes is over the limit of 10 bytes"
+    )]
+    #[case(
+        LoadError::Failed("connection refused".into()).into(),
+        "load error: connection refused"
+    )]
+    fn test_display(#[case] error: BrowserError, #[case] expected: &str) {
+        assert_eq!(error.to_string(), expected);
+    }
+
+    #[test]
+    fn test_from() {
+        let message = "m".to_string();
+        assert_eq!(
+            BrowserError::from(HtmlParseError {
+                message: message.clone(),
+                position: 0,
+                line: 1,
+                column: 1,
+            }),
+            BrowserError::Html(HtmlParseError {
+                message: message.clone(),
+                position: 0,
+                line: 1,
+                column: 1,
+            })
+        );
+        assert_eq!(
+            BrowserError::from(CssParseError {
+                message: message.clone()
+            }),
+            BrowserError::Css(CssParseError {
+                message: message.clone()
+            })
+        );
+        assert_eq!(
+            BrowserError::from(JsError {
+                message: message.clone()
+            }),
+            BrowserError::Js(JsError {
+                message: message.clone()
+            })
+        );
+        assert_eq!(
+            BrowserError::from(RenderError {
+                message: message.clone()
+            }),
+            BrowserError::Render(RenderError {
+                message: message.clone()
+            })
+        );
+        assert_eq!(
+            BrowserError::from(DomError::NotAChild),
+            BrowserError::Dom(DomError::NotAChild)
+        );
+        assert_eq!(
+            BrowserError::from(LoadError::Failed(message.clone())),
+            BrowserError::Load(LoadError::Failed(message))
+        );
+    }
+}
@@ -1,2 +1,4 @@
 pub mod dom;
 pub mod html;
+pub mod lint;
+pub mod parser;
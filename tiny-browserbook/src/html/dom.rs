@@ -1,14 +1,182 @@
 use std::collections::HashMap;
+use std::fmt;
+use std::ops::{Index, Range};
 
-pub type AttrMap = HashMap<String, String>;
+use crate::css::css::{try_parse_selectors, Selector};
+use crate::error::error::{BrowserError, DomError, HtmlParseError};
+use crate::html::html::{try_parse_fragment, VOID_ELEMENTS};
 
-#[derive(Debug, PartialEq)]
+/// Attributes of an element, iterated in the order they were first inserted
+///
+/// Two maps are equal when they hold the same attributes, whatever their order.
+/// # Example
+/// ```
+/// use tiny_browserbook::html::dom::AttrMap;
+/// let mut attributes = AttrMap::new();
+/// attributes.insert("id".to_string(), "a".to_string());
+/// attributes.insert("class".to_string(), "x".to_string());
+/// attributes.insert("id".to_string(), "b".to_string());
+/// let names: Vec<_> = attributes.iter().map(|(name, _)| name.as_str()).collect();
+/// assert_eq!(names, vec!["id", "class"]);
+/// assert_eq!(attributes["id"], "b");
+/// ```
+#[derive(Clone, Default)]
+pub struct AttrMap {
+    entries: Vec<(String, String)>,
+    /// Position of each name in `entries`
+    index: HashMap<String, usize>,
+}
+
+impl AttrMap {
+    pub fn new() -> Self {
+        AttrMap::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&String> {
+        self.index.get(name).map(|&i| &self.entries[i].1)
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.index.contains_key(name)
+    }
+
+    /// Set `name` to `value`, returning the value it replaced
+    ///
+    /// A replaced attribute keeps its place, and a new one goes last.
+    pub fn insert(&mut self, name: String, value: String) -> Option<String> {
+        match self.index.get(&name) {
+            Some(&i) => Some(std::mem::replace(&mut self.entries[i].1, value)),
+            None => {
+                self.index.insert(name.clone(), self.entries.len());
+                self.entries.push((name, value));
+                None
+            }
+        }
+    }
+
+    /// Take out `name`, returning its value
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let i = self.index.remove(name)?;
+        let (_, value) = self.entries.remove(i);
+        for (name, _) in self.entries[i..].iter() {
+            *self.index.get_mut(name).unwrap() -= 1;
+        }
+        Some(value)
+    }
+
+    /// Iterate over the names and values in insertion order
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.entries.iter().map(|(name, value)| (name, value))
+    }
+}
+
+impl PartialEq for AttrMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(name, value)| other.get(name) == Some(value))
+    }
+}
+
+impl fmt::Debug for AttrMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl Index<&str> for AttrMap {
+    type Output = String;
+
+    fn index(&self, name: &str) -> &String {
+        self.get(name)
+            .unwrap_or_else(|| panic!("no attribute named {}", name))
+    }
+}
+
+/// Later values of a repeated name replace earlier ones, as with `insert`
+impl FromIterator<(String, String)> for AttrMap {
+    fn from_iter<I: IntoIterator<Item = (String, String)>>(iter: I) -> Self {
+        let mut attributes = AttrMap::new();
+        for (name, value) in iter {
+            attributes.insert(name, value);
+        }
+        attributes
+    }
+}
+
+impl IntoIterator for AttrMap {
+    type Item = (String, String);
+    type IntoIter = std::vec::IntoIter<(String, String)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Node {
     pub node_type: NodeType,
     pub children: Vec<Box<Node>>,
+    /// Byte range of the open tag, or of the text, in the parsed source
+    pub span: Option<Range<usize>>,
+}
+
+/// Nodes are compared by content, wherever in the source they came from
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.node_type == other.node_type && self.children == other.children
+    }
 }
 
 impl Node {
+    /// Get the byte range of the node in the source it was parsed from
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse;
+    /// let raw = "<div>\n  <p class=\"a\">hello</p>\n</div>";
+    /// let node = parse(raw);
+    /// let span = node.body().unwrap().children[0].children[0].source_span().unwrap();
+    /// assert_eq!(&raw[span], "<p class=\"a\">");
+    /// ```
+    pub fn source_span(&self) -> Option<Range<usize>> {
+        self.span.clone()
+    }
+
+    /// Get the `<head>` of a document returned by `parse`
+    pub fn head(&self) -> Option<&Node> {
+        self.child_element("head")
+    }
+
+    /// Get the `<body>` of a document returned by `parse`
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse;
+    /// let node = parse("<p>hello</p>");
+    /// assert_eq!(node.body().unwrap().children.len(), 1);
+    /// ```
+    pub fn body(&self) -> Option<&Node> {
+        self.child_element("body")
+    }
+
+    fn child_element(&self, tag_name: &str) -> Option<&Node> {
+        self.children
+            .iter()
+            .map(|child| child.as_ref())
+            .find(|child| match &child.node_type {
+                NodeType::Element(e) => e.tag_name == tag_name,
+                NodeType::Text(_) => false,
+            })
+    }
+
     /// Get the inner text of the node
     /// # Example
     /// ```
@@ -19,30 +187,976 @@ impl Node {
     ///        attributes: AttrMap::new(),
     ///    }),
     ///    children: vec![Text::new("hello world".to_string())],
+    ///    span: None,
     /// };
     /// assert_eq!(node.inner_text(), "hello world");
     /// ```
     pub fn inner_text(&self) -> String {
+        let mut out = String::new();
+        self.collect_text(&mut out);
+        out
+    }
+
+    /// Append the inner text of the node to `out`
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse;
+    /// let mut out = "text: ".to_string();
+    /// parse("<p>hello <b>world</b></p>").inner_text_into(&mut out);
+    /// assert_eq!(out, "text: hello world");
+    /// ```
+    pub fn inner_text_into(&self, out: &mut String) {
+        self.collect_text(out);
+    }
+
+    /// Replace the children with a single text node holding `text`, or with nothing
+    /// when `text` is empty
+    ///
+    /// Text nodes have no children, so nothing changes on them.
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse;
+    /// let mut node = parse(r#"<div id="a"><p>old</p></div>"#);
+    /// let div = node.get_element_by_id_mut("a").unwrap();
+    /// div.set_inner_text("<b>new</b>");
+    /// assert_eq!(div.inner_html(), "&lt;b&gt;new&lt;/b&gt;");
+    /// ```
+    pub fn set_inner_text(&mut self, text: &str) {
+        if let NodeType::Text(_) = self.node_type {
+            return;
+        }
+        self.children = if text.is_empty() {
+            vec![]
+        } else {
+            vec![Text::new(text.to_string())]
+        };
+    }
+
+    /// Replace the children with the nodes parsed from `html`
+    ///
+    /// The children are kept as they were if `html` is malformed. Text nodes have no
+    /// children, so nothing changes on them.
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse;
+    /// let mut node = parse(r#"<div id="a"><p>old</p></div>"#);
+    /// let div = node.get_element_by_id_mut("a").unwrap();
+    /// div.set_inner_html("<p>new <b>text</b></p>").unwrap();
+    /// assert_eq!(div.inner_text(), "new text");
+    /// assert!(div.set_inner_html("<p>a</p></div>").is_err());
+    /// assert_eq!(div.inner_html(), "<p>new <b>text</b></p>");
+    /// ```
+    pub fn set_inner_html(&mut self, html: &str) -> Result<(), HtmlParseError> {
+        let children = try_parse_fragment(html)?;
+        if let NodeType::Element(_) = self.node_type {
+            self.children = children;
+        }
+        Ok(())
+    }
+
+    fn collect_text(&self, out: &mut String) {
+        for node in self.children.iter() {
+            match &node.node_type {
+                NodeType::Text(t) => out.push_str(&t.data),
+                _ => node.collect_text(out),
+            }
+        }
+    }
+
+    /// Serialize the node and its descendants back to markup
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse_fragment;
+    /// let nodes = parse_fragment(r#"<p class="a">x &amp; y<br></p>"#);
+    /// assert_eq!(nodes[0].outer_html(), r#"<p class="a">x &amp; y<br></p>"#);
+    /// ```
+    pub fn outer_html(&self) -> String {
+        self.to_html_with(&SerializeOptions::default())
+    }
+
+    /// Serialize the children of the node back to markup, always escaped as
+    /// `SerializeOptions::default()` escapes them
+    pub fn inner_html(&self) -> String {
+        let mut out = String::new();
+        self.write_children(&mut out, &SerializeOptions::default(), false);
+        out
+    }
+
+    /// Serialize the node and its descendants back to markup as `options` asks
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::dom::{QuoteStyle, SerializeOptions};
+    /// use tiny_browserbook::html::html::parse_fragment;
+    /// let nodes = parse_fragment(r#"<ul class="a"><li>x &amp; y</li></ul>"#);
+    /// let options = SerializeOptions {
+    ///     escape_text: false,
+    ///     quote_attrs: QuoteStyle::Single,
+    ///     pretty: true,
+    ///     indent: 2,
+    /// };
+    /// assert_eq!(nodes[0].to_html_with(&options), "<ul class='a'>\n  <li>x & y</li>\n</ul>");
+    /// ```
+    pub fn to_html_with(&self, options: &SerializeOptions) -> String {
+        let mut out = String::new();
+        if options.pretty {
+            self.write_pretty(&mut out, options, 0);
+            out.pop();
+        } else {
+            self.write_html(&mut out, options, false);
+        }
+        out
+    }
+
+    fn write_html(&self, out: &mut String, options: &SerializeOptions, raw: bool) {
+        match &self.node_type {
+            NodeType::Text(t) if raw || !options.escape_text => out.push_str(&t.data),
+            NodeType::Text(t) => out.push_str(&escape(&t.data, None)),
+            NodeType::Element(e) => {
+                e.write_open_tag(out, options.quote_attrs);
+                if VOID_ELEMENTS.contains(&e.tag_name.as_str()) {
+                    return;
+                }
+                self.write_children(out, options, e.has_raw_text());
+                out.push_str(&format!("</{}>", e.tag_name));
+            }
+        }
+    }
+
+    fn write_children(&self, out: &mut String, options: &SerializeOptions, raw: bool) {
+        for child in self.children.iter() {
+            child.write_html(out, options, raw);
+        }
+    }
+
+    /// Write the node on lines of its own, indented by `depth` levels
+    ///
+    /// Elements holding only text, and elements whose whitespace matters, stay on
+    /// one line as they would be written compactly.
+    fn write_pretty(&self, out: &mut String, options: &SerializeOptions, depth: usize) {
+        let indent = " ".repeat(options.indent * depth);
+        match &self.node_type {
+            NodeType::Text(t) => {
+                let text = t.data.trim();
+                if !text.is_empty() {
+                    out.push_str(&indent);
+                    out.push_str(&if options.escape_text {
+                        escape(text, None)
+                    } else {
+                        text.to_string()
+                    });
+                    out.push('\n');
+                }
+            }
+            NodeType::Element(e)
+                if e.has_raw_text()
+                    || matches!(e.tag_name.as_str(), "pre" | "textarea")
+                    || self
+                        .children
+                        .iter()
+                        .all(|child| matches!(child.node_type, NodeType::Text(_))) =>
+            {
+                out.push_str(&indent);
+                self.write_html(out, options, false);
+                out.push('\n');
+            }
+            NodeType::Element(e) => {
+                out.push_str(&indent);
+                e.write_open_tag(out, options.quote_attrs);
+                out.push('\n');
+                for child in self.children.iter() {
+                    child.write_pretty(out, options, depth + 1);
+                }
+                out.push_str(&format!("{}</{}>\n", indent, e.tag_name));
+            }
+        }
+    }
+
+    /// Outline the node and its descendants, one per line and indented by depth
+    ///
+    /// Elements are shown with their id and classes as in a selector, and text as
+    /// `#text` followed by its quoted data, cut short past `TREE_TEXT_LIMIT` characters.
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse_fragment;
+    /// let nodes = parse_fragment(r#"<div id="a"><p class="x y">hello</p></div>"#);
+    /// assert_eq!(nodes[0].to_tree_string(), "div#a\n  p.x.y\n    #text \"hello\"");
+    /// ```
+    pub fn to_tree_string(&self) -> String {
+        let mut out = String::new();
+        self.write_tree(&mut out, 0);
+        out.pop();
+        out
+    }
+
+    fn write_tree(&self, out: &mut String, depth: usize) {
+        out.push_str(&"  ".repeat(depth));
+        match &self.node_type {
+            NodeType::Text(t) => {
+                let mut text: String = t.data.chars().take(TREE_TEXT_LIMIT).collect();
+                if t.data.chars().nth(TREE_TEXT_LIMIT).is_some() {
+                    text.push('…');
+                }
+                out.push_str(&format!("#text {:?}", text));
+            }
+            NodeType::Element(e) => {
+                out.push_str(&e.tag_name);
+                if let Some(id) = self.get_attribute("id") {
+                    out.push_str(&format!("#{}", id));
+                }
+                for class in self.class_list() {
+                    out.push_str(&format!(".{}", class));
+                }
+            }
+        }
+        out.push('\n');
+        for child in self.children.iter() {
+            child.write_tree(out, depth + 1);
+        }
+    }
+
+    /// Get the path from this node to `target`, like `/html/body[1]/div[2]/p[1]`
+    ///
+    /// Every step after the root carries its 1-based position among the siblings
+    /// with the same name. Text nodes are addressed as `text()`.
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse;
+    /// let node = parse("<body><p>a</p><p>b</p></body>");
+    /// let target = &node.body().unwrap().children[1];
+    /// assert_eq!(node.node_path(target), Some("/html/body[1]/p[2]".to_string()));
+    /// ```
+    pub fn node_path(&self, target: &Node) -> Option<String> {
+        self.path_steps(target).map(|steps| {
+            std::iter::once(self.step_name())
+                .chain(steps)
+                .map(|step| format!("/{}", step))
+                .collect()
+        })
+    }
+
+    /// Find the node addressed by a path produced by `node_path`
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse;
+    /// let node = parse("<body><p>a</p><p>b</p></body>");
+    /// assert_eq!(node.resolve_path("/html/body[1]/p[2]").unwrap().inner_text(), "b");
+    /// assert!(node.resolve_path("/html/body[1]/p[3]").is_none());
+    /// ```
+    pub fn resolve_path(&self, path: &str) -> Option<&Node> {
+        let mut steps = path.strip_prefix('/')?.split('/');
+        let (name, position) = parse_step(steps.next()?)?;
+        if name != self.step_name() || position != 1 {
+            return None;
+        }
+        steps.try_fold(self, |node, step| {
+            let (name, position) = parse_step(step)?;
+            node.children
+                .iter()
+                .filter(|child| child.step_name() == name)
+                .nth(position.checked_sub(1)?)
+                .map(|child| child.as_ref())
+        })
+    }
+
+    /// Get the nodes from this node down to `target`, both included
+    pub fn ancestors<'a>(&'a self, target: &Node) -> Option<Vec<&'a Node>> {
+        if std::ptr::eq(self, target) {
+            return Some(vec![self]);
+        }
+        self.children.iter().find_map(|child| {
+            child.ancestors(target).map(|mut nodes| {
+                nodes.insert(0, self);
+                nodes
+            })
+        })
+    }
+
+    /// Get the value of attribute `name` on `target` or its nearest ancestor having it
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse;
+    /// let node = parse(r#"<div title="note"><p>a</p></div>"#);
+    /// let target = &node.body().unwrap().children[0].children[0];
+    /// assert_eq!(node.closest_attribute(target, "title"), Some("note"));
+    /// ```
+    pub fn closest_attribute<'a>(&'a self, target: &Node, name: &str) -> Option<&'a str> {
+        self.ancestors(target)?
+            .into_iter()
+            .rev()
+            .find_map(|node| node.get_attribute(name))
+    }
+
+    /// Get `target` or its nearest ancestor matching `selector`
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::css::css::SimpleSelector;
+    /// use tiny_browserbook::html::html::parse;
+    /// let node = parse("<ul><li><span>a</span></li></ul>");
+    /// let ul = &node.body().unwrap().children[0];
+    /// let span = &ul.children[0].children[0];
+    /// let li = node.closest(span, &SimpleSelector::TypeSelector { tag_name: "li".into() });
+    /// assert!(std::ptr::eq(li.unwrap(), ul.children[0].as_ref()));
+    /// ```
+    pub fn closest<'a>(&'a self, target: &Node, selector: &Selector) -> Option<&'a Node> {
+        self.ancestors(target)?
+            .into_iter()
+            .rev()
+            .find(|node| node.matches(selector))
+    }
+
+    /// Whether the node matches `selector`
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::css::css::parse_selector;
+    /// use tiny_browserbook::html::html::parse_fragment;
+    /// let nodes = parse_fragment(r#"<p class="note">a</p>"#);
+    /// assert!(nodes[0].matches(&parse_selector("p").unwrap()));
+    /// assert!(nodes[0].matches(&parse_selector(".note").unwrap()));
+    /// assert!(!nodes[0].children[0].matches(&parse_selector("p").unwrap()));
+    /// ```
+    pub fn matches(&self, selector: &Selector) -> bool {
+        selector.matches(self)
+    }
+
+    /// Get the text of the `<label>` naming `target`: the one whose `for` is the id of
+    /// `target`, or else the nearest one around it
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse;
+    /// let node = parse(r#"<label for="q">Search</label><label>Name <input id="n"></label><input id="q">"#);
+    /// let q = node.get_element_by_id("q").unwrap();
+    /// let n = node.get_element_by_id("n").unwrap();
+    /// assert_eq!(node.label_text(q), Some("Search".to_string()));
+    /// assert_eq!(node.label_text(n), Some("Name".to_string()));
+    /// ```
+    pub fn label_text(&self, target: &Node) -> Option<String> {
+        let for_id = target.get_attribute("id").and_then(|id| {
+            self.get_elements_by_tag_name("label")
+                .into_iter()
+                .find(|label| label.get_attribute("for") == Some(id))
+        });
+        let label = for_id.or_else(|| {
+            self.ancestors(target)?.into_iter().rev().find(
+                |node| matches!(&node.node_type, NodeType::Element(e) if e.tag_name == "label"),
+            )
+        })?;
+        let text = label.inner_text();
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        (!text.is_empty()).then_some(text)
+    }
+
+    /// Get the accessible name of `target`, as [`Element::accessible_name`] finds it
+    /// with the text of its label
+    pub fn accessible_name(&self, target: &Node) -> Option<String> {
+        match &target.node_type {
+            NodeType::Element(e) => e.accessible_name(self.label_text(target).as_deref()),
+            NodeType::Text(_) => None,
+        }
+    }
+
+    /// Iterate over the descendants of the node, this node left out, in document order
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse_fragment;
+    /// let nodes = parse_fragment("<div><p>a</p><p>b</p></div>");
+    /// let texts: Vec<_> = nodes[0].descendants().map(|node| node.outer_html()).collect();
+    /// assert_eq!(texts, vec!["<p>a</p>", "a", "<p>b</p>", "b"]);
+    /// ```
+    pub fn descendants(&self) -> Descendants<'_> {
+        Descendants {
+            stack: self
+                .children
+                .iter()
+                .rev()
+                .map(|child| child.as_ref())
+                .collect(),
+        }
+    }
+
+    /// Iterate over this node and its descendants, in document order
+    fn inclusive_descendants(&self) -> impl Iterator<Item = &Node> {
+        std::iter::once(self).chain(self.descendants())
+    }
+
+    /// Call `f` on each descendant of the node, this node left out, in document order
+    ///
+    /// The children of a node are visited after `f` returns for it, so changes it makes
+    /// to them are seen.
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse_fragment;
+    /// let mut nodes = parse_fragment("<ul><li>a</li><li>b</li></ul>");
+    /// nodes[0].for_each_descendant_mut(&mut |node| node.add_class("item"));
+    /// assert_eq!(nodes[0].get_elements_by_class_name("item").len(), 2);
+    /// ```
+    pub fn for_each_descendant_mut(&mut self, f: &mut dyn FnMut(&mut Node)) {
+        for child in self.children.iter_mut() {
+            f(child);
+            child.for_each_descendant_mut(f);
+        }
+    }
+
+    /// Iterate over the children of the node that are elements
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse_fragment;
+    /// let nodes = parse_fragment("<p>a <b>b</b> c <i>d</i></p>");
+    /// assert_eq!(nodes[0].children_elements().count(), 2);
+    /// ```
+    pub fn children_elements(&self) -> impl Iterator<Item = &Node> {
         self.children
             .iter()
-            .clone()
+            .filter(|child| matches!(child.node_type, NodeType::Element(_)))
+            .map(|child| child.as_ref())
+    }
+
+    /// Get the elements among this node and its descendants matching any of `selectors`,
+    /// in document order
+    pub fn select_all<'a>(&'a self, selectors: &[Selector]) -> Vec<&'a Node> {
+        self.inclusive_descendants()
+            .filter(|node| node.is_selected(selectors))
+            .collect()
+    }
+
+    /// Whether the node is an element matching any of `selectors`, as the elements
+    /// are the only nodes the `query_selector` functions return
+    fn is_selected(&self, selectors: &[Selector]) -> bool {
+        matches!(self.node_type, NodeType::Element(_))
+            && selectors.iter().any(|selector| self.matches(selector))
+    }
+
+    /// Get the first element in document order, this node included, whose `id` is `id`
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse;
+    /// let node = parse(r#"<div><p id="result">not loaded</p></div>"#);
+    /// assert_eq!(node.get_element_by_id("result").unwrap().inner_text(), "not loaded");
+    /// assert!(node.get_element_by_id("missing").is_none());
+    /// ```
+    pub fn get_element_by_id(&self, id: &str) -> Option<&Node> {
+        self.inclusive_descendants().find(|node| node.has_id(id))
+    }
+
+    /// Get the first element in document order, this node included, whose `id` is `id`,
+    /// for changing it
+    pub fn get_element_by_id_mut(&mut self, id: &str) -> Option<&mut Node> {
+        if self.has_id(id) {
+            return Some(self);
+        }
+        self.children
+            .iter_mut()
+            .find_map(|child| child.get_element_by_id_mut(id))
+    }
+
+    /// Get this node and its descendants that are `name` elements, in document order
+    ///
+    /// Tag names are compared ignoring case, and `*` matches every element.
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse;
+    /// let node = parse("<div><p>a</p><section><p>b</p></section></div>");
+    /// let texts: Vec<_> = node.get_elements_by_tag_name("P").iter().map(|p| p.inner_text()).collect();
+    /// assert_eq!(texts, vec!["a", "b"]);
+    /// ```
+    pub fn get_elements_by_tag_name(&self, name: &str) -> Vec<&Node> {
+        self.filter_elements(&|e: &Element| name == "*" || e.tag_name.eq_ignore_ascii_case(name))
+    }
+
+    /// Get this node and its descendants having `class` among their classes, in document order
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse;
+    /// let node = parse(r#"<p class="note warning">a</p><p class="notes">b</p>"#);
+    /// assert_eq!(node.get_elements_by_class_name("note").len(), 1);
+    /// ```
+    pub fn get_elements_by_class_name(&self, class: &str) -> Vec<&Node> {
+        self.filter_elements(&|e: &Element| {
+            e.attributes
+                .get("class")
+                .is_some_and(|classes| class_tokens(classes).contains(&class))
+        })
+    }
+
+    fn filter_elements<'a>(&'a self, predicate: &dyn Fn(&Element) -> bool) -> Vec<&'a Node> {
+        self.inclusive_descendants()
+            .filter(|node| matches!(&node.node_type, NodeType::Element(e) if predicate(e)))
+            .collect()
+    }
+
+    fn has_id(&self, id: &str) -> bool {
+        self.get_attribute("id") == Some(id)
+    }
+
+    /// Get the first element of this node and its descendants, in document order,
+    /// matching any of the comma-separated `selector`s
+    ///
+    /// An invalid selector matches nothing.
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse;
+    /// let node = parse(r#"<p>a</p><p class="note">b</p><p class="note">c</p>"#);
+    /// assert_eq!(node.query_selector(".note").unwrap().inner_text(), "b");
+    /// assert!(node.query_selector("table").is_none());
+    /// ```
+    pub fn query_selector(&self, selector: &str) -> Option<&Node> {
+        let selectors = try_parse_selectors(selector).ok()?;
+        self.find_selected(&selectors)
+    }
+
+    /// Get the elements among this node and its descendants matching any of the
+    /// comma-separated `selector`s, in document order
+    ///
+    /// An invalid selector matches nothing.
+    pub fn query_selector_all(&self, selector: &str) -> Vec<&Node> {
+        match try_parse_selectors(selector) {
+            Ok(selectors) => self.select_all(&selectors),
+            Err(_) => vec![],
+        }
+    }
+
+    fn find_selected(&self, selectors: &[Selector]) -> Option<&Node> {
+        self.inclusive_descendants()
+            .find(|node| node.is_selected(selectors))
+    }
+
+    /// Gather `what` from this node and its descendants matching any of the
+    /// comma-separated `selector`s, in document order
+    ///
+    /// An invalid selector matches nothing, and elements without the attribute are
+    /// skipped when collecting `Collect::Attr`.
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::dom::Collect;
+    /// use tiny_browserbook::html::html::parse;
+    /// let node = parse(r#"<a href="/a">a</a><p>b</p><a href="/c">c</a>"#);
+    /// assert_eq!(node.collect("a", Collect::Attr("href".to_string())), vec!["/a", "/c"]);
+    /// assert_eq!(node.collect("p, a", Collect::InnerText), vec!["a", "b", "c"]);
+    /// ```
+    pub fn collect(&self, selector: &str, what: Collect) -> Vec<String> {
+        let Ok(selectors) = try_parse_selectors(selector) else {
+            return vec![];
+        };
+        self.select_all(&selectors)
             .into_iter()
-            .map(|node| match &node.node_type {
-                NodeType::Text(t) => t.data.clone(),
-                _ => node.inner_text(),
+            .filter_map(|node| match &what {
+                Collect::InnerText => Some(node.inner_text()),
+                Collect::InnerHtml => Some(node.inner_html()),
+                Collect::Attr(name) => node.get_attribute(name).map(str::to_string),
             })
-            .collect::<Vec<_>>()
-            .join("")
+            .collect()
+    }
+
+    /// Get the value of attribute `name`, or `None` on a text node
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::dom::{AttrMap, Element, Text};
+    /// let mut attributes = AttrMap::new();
+    /// attributes.insert("href".to_string(), "/about".to_string());
+    /// let node = Element::new("a".to_string(), attributes, vec![]);
+    /// assert_eq!(node.get_attribute("href"), Some("/about"));
+    /// assert_eq!(node.get_attribute("title"), None);
+    /// assert_eq!(Text::new("a".to_string()).get_attribute("href"), None);
+    /// ```
+    pub fn get_attribute(&self, name: &str) -> Option<&str> {
+        match &self.node_type {
+            NodeType::Element(e) => e.attributes.get(name).map(String::as_str),
+            NodeType::Text(_) => None,
+        }
+    }
+
+    /// Whether the node has attribute `name`, never true of a text node
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse_fragment;
+    /// let nodes = parse_fragment("<input disabled>");
+    /// assert!(nodes[0].has_attribute("disabled"));
+    /// assert!(!nodes[0].has_attribute("checked"));
+    /// ```
+    pub fn has_attribute(&self, name: &str) -> bool {
+        self.get_attribute(name).is_some()
+    }
+
+    /// Set attribute `name` to `value`, replacing any value it had
+    ///
+    /// Text nodes have no attributes, so nothing changes on them.
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse_fragment;
+    /// let mut nodes = parse_fragment(r#"<p class="a">x</p>"#);
+    /// nodes[0].set_attribute("class", "b");
+    /// assert_eq!(nodes[0].outer_html(), r#"<p class="b">x</p>"#);
+    /// ```
+    pub fn set_attribute(&mut self, name: &str, value: &str) {
+        if let NodeType::Element(e) = &mut self.node_type {
+            e.attributes.insert(name.to_string(), value.to_string());
+        }
+    }
+
+    /// Remove attribute `name`, returning the value it had
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse_fragment;
+    /// let mut nodes = parse_fragment("<p hidden>x</p>");
+    /// assert_eq!(nodes[0].remove_attribute("hidden"), Some("".to_string()));
+    /// assert_eq!(nodes[0].remove_attribute("hidden"), None);
+    /// ```
+    pub fn remove_attribute(&mut self, name: &str) -> Option<String> {
+        match &mut self.node_type {
+            NodeType::Element(e) => e.attributes.remove(name),
+            NodeType::Text(_) => None,
+        }
+    }
+
+    /// Get the classes of the element, each once, in the order they first appear
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse_fragment;
+    /// let nodes = parse_fragment(r#"<p class=" note  warning note">a</p>"#);
+    /// assert_eq!(nodes[0].class_list(), vec!["note", "warning"]);
+    /// ```
+    pub fn class_list(&self) -> Vec<&str> {
+        self.get_attribute("class")
+            .map(class_tokens)
+            .unwrap_or_default()
+    }
+
+    /// Whether `class` is one of the classes of the element
+    pub fn has_class(&self, class: &str) -> bool {
+        self.class_list().contains(&class)
+    }
+
+    /// Add `class` to the classes of the element unless it is there already
+    ///
+    /// The `class` attribute is rewritten with the classes separated by single
+    /// spaces. Nothing changes on a text node, or when `class` is empty or holds
+    /// whitespace, which could never be a single class.
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse_fragment;
+    /// let mut nodes = parse_fragment(r#"<p class="a">x</p>"#);
+    /// nodes[0].add_class("b");
+    /// nodes[0].add_class("a");
+    /// assert_eq!(nodes[0].get_attribute("class"), Some("a b"));
+    /// ```
+    pub fn add_class(&mut self, class: &str) {
+        if !is_class_token(class) {
+            return;
+        }
+        let mut classes = self.class_list();
+        if !classes.contains(&class) {
+            classes.push(class);
+        }
+        let classes = classes.join(" ");
+        self.set_attribute("class", &classes);
+    }
+
+    /// Remove `class` from the classes of the element
+    ///
+    /// The `class` attribute is rewritten as `add_class` does, and is kept even if
+    /// no class is left in it.
+    pub fn remove_class(&mut self, class: &str) {
+        if !self.has_attribute("class") {
+            return;
+        }
+        let mut classes = self.class_list();
+        classes.retain(|c| *c != class);
+        let classes = classes.join(" ");
+        self.set_attribute("class", &classes);
+    }
+
+    /// Remove `class` if the element has it and add it otherwise, returning whether
+    /// the element has it now
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse_fragment;
+    /// let mut nodes = parse_fragment(r#"<p class="open">x</p>"#);
+    /// assert!(!nodes[0].toggle_class("open"));
+    /// assert!(nodes[0].toggle_class("open"));
+    /// ```
+    pub fn toggle_class(&mut self, class: &str) -> bool {
+        if self.has_class(class) {
+            self.remove_class(class);
+        } else {
+            self.add_class(class);
+        }
+        self.has_class(class)
+    }
+
+    /// Copy the node, with copies of its descendants when `deep` and none otherwise
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse_fragment;
+    /// let nodes = parse_fragment(r#"<p class="a">x <b>y</b></p>"#);
+    /// assert_eq!(nodes[0].clone_node(true).outer_html(), r#"<p class="a">x <b>y</b></p>"#);
+    /// assert_eq!(nodes[0].clone_node(false).outer_html(), r#"<p class="a"></p>"#);
+    /// ```
+    pub fn clone_node(&self, deep: bool) -> Box<Node> {
+        Box::new(Node {
+            node_type: self.node_type.clone(),
+            children: if deep { self.children.clone() } else { vec![] },
+            span: self.span.clone(),
+        })
+    }
+
+    /// Get the position of `child` among the children, comparing by identity
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse_fragment;
+    /// let nodes = parse_fragment("<ul><li>a</li><li>b</li></ul>");
+    /// let second = &nodes[0].children[1];
+    /// assert_eq!(nodes[0].child_index(second), Some(1));
+    /// assert_eq!(nodes[0].child_index(&nodes[0]), None);
+    /// ```
+    pub fn child_index(&self, child: &Node) -> Option<usize> {
+        self.children
+            .iter()
+            .position(|c| std::ptr::eq(c.as_ref(), child))
+    }
+
+    /// Add `child` after the last child
+    ///
+    /// Text nodes can't have children, so `child` is refused on them.
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::dom::Text;
+    /// use tiny_browserbook::html::html::parse_fragment;
+    /// let mut nodes = parse_fragment("<p>a</p>");
+    /// nodes[0].append_child(Text::new("b".to_string())).unwrap();
+    /// assert_eq!(nodes[0].outer_html(), "<p>ab</p>");
+    /// ```
+    pub fn append_child(&mut self, child: Box<Node>) -> Result<(), String> {
+        let index = self.children.len();
+        self.insert_before(index, child)
+    }
+
+    /// Insert `child` before the child at `index`, or after the last child when
+    /// `index` is the number of children
+    ///
+    /// Use [`Node::child_index`] to insert before a reference child. Fails on text
+    /// nodes and when `index` is past the end.
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse_fragment;
+    /// let mut nodes = parse_fragment("<ul><li>a</li><li>c</li></ul>");
+    /// let b = parse_fragment("<li>b</li>").remove(0);
+    /// let index = nodes[0].child_index(&nodes[0].children[1]).unwrap();
+    /// nodes[0].insert_before(index, b).unwrap();
+    /// assert_eq!(nodes[0].inner_text(), "abc");
+    /// assert!(nodes[0].insert_before(9, parse_fragment("x").remove(0)).is_err());
+    /// ```
+    pub fn insert_before(&mut self, index: usize, child: Box<Node>) -> Result<(), String> {
+        if let NodeType::Text(_) = self.node_type {
+            return Err("text nodes can't have children".to_string());
+        }
+        if index > self.children.len() {
+            return Err(format!(
+                "index {} is out of range for {} children",
+                index,
+                self.children.len()
+            ));
+        }
+        self.children.insert(index, child);
+        Ok(())
+    }
+
+    /// Take out the child at `index`, or get `None` if there is no such child
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse_fragment;
+    /// let mut nodes = parse_fragment("<p>a<b>b</b></p>");
+    /// let b = nodes[0].remove_child(1).unwrap();
+    /// assert_eq!(b.outer_html(), "<b>b</b>");
+    /// assert_eq!(nodes[0].outer_html(), "<p>a</p>");
+    /// assert!(nodes[0].remove_child(1).is_none());
+    /// ```
+    pub fn remove_child(&mut self, index: usize) -> Option<Box<Node>> {
+        if index < self.children.len() {
+            Some(self.children.remove(index))
+        } else {
+            None
+        }
+    }
+
+    /// Put `child` in place of the child at `index`, returning the child it replaced
+    ///
+    /// Gets `None`, dropping `child`, if there is no child at `index`.
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse_fragment;
+    /// let mut nodes = parse_fragment("<p><i>a</i></p>");
+    /// let b = parse_fragment("<b>b</b>").remove(0);
+    /// let i = nodes[0].replace_child(0, b).unwrap();
+    /// assert_eq!(i.outer_html(), "<i>a</i>");
+    /// assert_eq!(nodes[0].outer_html(), "<p><b>b</b></p>");
+    /// ```
+    pub fn replace_child(&mut self, index: usize, child: Box<Node>) -> Option<Box<Node>> {
+        self.children
+            .get_mut(index)
+            .map(|old| std::mem::replace(old, child))
+    }
+
+    /// Replace all the children with `children`, returning the old ones
+    ///
+    /// Text nodes can't have children, so they refuse any but an empty list.
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse_fragment;
+    /// let mut nodes = parse_fragment("<div><p>old</p></div>");
+    /// let old = nodes[0].replace_children(parse_fragment("<p>new</p>")).unwrap();
+    /// assert_eq!(old[0].inner_text(), "old");
+    /// assert_eq!(nodes[0].inner_text(), "new");
+    /// ```
+    pub fn replace_children(&mut self, children: Vec<Box<Node>>) -> Result<Vec<Box<Node>>, String> {
+        if let NodeType::Text(_) = self.node_type {
+            if !children.is_empty() {
+                return Err("text nodes can't have children".to_string());
+            }
+        }
+        Ok(std::mem::replace(&mut self.children, children))
+    }
+
+    /// Get the `data-*` attributes of the element with the `data-` prefix stripped
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse_fragment;
+    /// let nodes = parse_fragment(r#"<p data-id="5" data-user-name="x" id="p">a</p>"#);
+    /// let dataset = nodes[0].dataset();
+    /// assert_eq!(dataset.len(), 2);
+    /// assert_eq!(dataset["user-name"], "x");
+    /// ```
+    pub fn dataset(&self) -> AttrMap {
+        match &self.node_type {
+            NodeType::Element(e) => e
+                .attributes
+                .iter()
+                .filter_map(|(name, value)| {
+                    name.strip_prefix("data-")
+                        .map(|key| (key.to_string(), value.clone()))
+                })
+                .collect(),
+            NodeType::Text(_) => AttrMap::new(),
+        }
+    }
+
+    fn step_name(&self) -> String {
+        match &self.node_type {
+            NodeType::Element(e) => e.tag_name.clone(),
+            NodeType::Text(_) => "text()".to_string(),
+        }
+    }
+
+    fn path_steps(&self, target: &Node) -> Option<Vec<String>> {
+        if std::ptr::eq(self, target) {
+            return Some(vec![]);
+        }
+        self.children.iter().enumerate().find_map(|(i, child)| {
+            child.path_steps(target).map(|mut steps| {
+                let name = child.step_name();
+                let position = self.children[..i]
+                    .iter()
+                    .filter(|sibling| sibling.step_name() == name)
+                    .count()
+                    + 1;
+                steps.insert(0, format!("{}[{}]", name, position));
+                steps
+            })
+        })
+    }
+}
+
+/// Characters of text shown by `Node::to_tree_string` before it is cut short
+pub const TREE_TEXT_LIMIT: usize = 40;
+
+/// The whitespace-separated tokens of a `class` attribute, without repeats
+fn class_tokens(classes: &str) -> Vec<&str> {
+    let mut tokens: Vec<&str> = vec![];
+    for token in classes.split_ascii_whitespace() {
+        if !tokens.contains(&token) {
+            tokens.push(token);
+        }
+    }
+    tokens
+}
+
+fn is_class_token(class: &str) -> bool {
+    !class.is_empty() && !class.contains(|c: char| c.is_ascii_whitespace())
+}
+
+fn parse_step(step: &str) -> Option<(&str, usize)> {
+    match step.strip_suffix(']') {
+        Some(rest) => {
+            let (name, position) = rest.rsplit_once('[')?;
+            Some((name, position.parse().ok()?))
+        }
+        None => Some((step, 1)),
+    }
+}
+
+/// Escape markup characters in `text`, and `quote` too for an attribute value
+fn escape(text: &str, quote: Option<char>) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' if quote == Some('"') => out.push_str("&quot;"),
+            '\'' if quote == Some('\'') => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// How `Node::to_html_with` quotes attribute values
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuoteStyle {
+    Double,
+    Single,
+    /// Unquoted where the value allows it, double quoted otherwise
+    Minimal,
+}
+
+/// How `Node::to_html_with` writes markup
+///
+/// Attribute values are always escaped for the quotes around them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SerializeOptions {
+    /// Escape `&`, `<` and `>` in text rather than writing it as it is
+    pub escape_text: bool,
+    pub quote_attrs: QuoteStyle,
+    /// Put every element and text on a line of its own, indented by its depth
+    pub pretty: bool,
+    /// Spaces per level of nesting when `pretty`
+    pub indent: usize,
+}
+
+impl Default for SerializeOptions {
+    fn default() -> Self {
+        SerializeOptions {
+            escape_text: true,
+            quote_attrs: QuoteStyle::Double,
+            pretty: false,
+            indent: 2,
+        }
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// What `Node::collect` gathers from each matching element
+#[derive(Debug, Clone, PartialEq)]
+pub enum Collect {
+    InnerText,
+    InnerHtml,
+    Attr(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum NodeType {
     Element(Element),
     Text(Text),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Element {
     pub tag_name: String,
     pub attributes: AttrMap,
@@ -56,11 +1170,68 @@ impl Element {
                 attributes,
             }),
             children,
+            span: None,
         })
     }
+
+    /// Get the name an interactive element is known by, from the first that isn't blank
+    /// of `aria-label`, the text of its `label`, `title`, `placeholder` and `name`
+    ///
+    /// The content of the element is not looked at, so a button showing text has a name
+    /// only if one of these gives it.
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::parse_fragment;
+    /// use tiny_browserbook::html::dom::NodeType;
+    /// let nodes = parse_fragment(r#"<input name="q" placeholder="Search" aria-label=" ">"#);
+    /// let NodeType::Element(input) = &nodes[0].node_type else { unreachable!() };
+    /// assert_eq!(input.accessible_name(None), Some("Search".to_string()));
+    /// assert_eq!(input.accessible_name(Some("Query")), Some("Query".to_string()));
+    /// ```
+    pub fn accessible_name(&self, label: Option<&str>) -> Option<String> {
+        let attribute = |name: &str| self.attributes.get(name).map(|value| value.as_str());
+        [
+            attribute("aria-label"),
+            label,
+            attribute("title"),
+            attribute("placeholder"),
+            attribute("name"),
+        ]
+        .into_iter()
+        .flatten()
+        .map(str::trim)
+        .find(|name| !name.is_empty())
+        .map(|name| name.to_string())
+    }
+
+    fn has_raw_text(&self) -> bool {
+        matches!(self.tag_name.as_str(), "script" | "style")
+    }
+
+    fn write_open_tag(&self, out: &mut String, quote_attrs: QuoteStyle) {
+        out.push('<');
+        out.push_str(&self.tag_name);
+        for (name, value) in self.attributes.iter() {
+            let unquoted = !value.is_empty()
+                && !value
+                    .chars()
+                    .any(|c| c.is_ascii_whitespace() || "\"'=<>`".contains(c));
+            let quote = match quote_attrs {
+                QuoteStyle::Minimal if unquoted => None,
+                QuoteStyle::Double | QuoteStyle::Minimal => Some('"'),
+                QuoteStyle::Single => Some('\''),
+            };
+            let value = escape(value, quote);
+            match quote {
+                Some(q) => out.push_str(&format!(" {}={}{}{}", name, q, value, q)),
+                None => out.push_str(&format!(" {}={}", name, value)),
+            }
+        }
+        out.push('>');
+    }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Text {
     pub data: String,
 }
@@ -70,6 +1241,1263 @@ impl Text {
         Box::new(Node {
             node_type: NodeType::Text(Text { data: text }),
             children: vec![],
+            span: None,
+        })
+    }
+}
+
+/// Pre-order iterator over the descendants of a node, made by [`Node::descendants`]
+pub struct Descendants<'a> {
+    /// Nodes left to visit, the next one last
+    stack: Vec<&'a Node>,
+}
+
+impl<'a> Iterator for Descendants<'a> {
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.stack
+            .extend(node.children.iter().rev().map(|child| child.as_ref()));
+        Some(node)
+    }
+}
+
+/// Handle to a node of a [`Document`], which stays valid as the document is mutated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    /// Get the position of the node in the arena of its document
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Debug)]
+struct DocumentNode {
+    node_type: NodeType,
+    span: Option<Range<usize>>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+/// A tree of nodes kept in an arena, where every node knows its parent
+///
+/// Nodes are never freed: removing a node detaches it with its subtree, and it can be
+/// inserted again. Converts from and into the `Box<Node>` tree the parsers build.
+/// # Example
+/// ```
+/// use tiny_browserbook::html::dom::Document;
+/// use tiny_browserbook::html::html::parse_fragment;
+/// let mut document = Document::from(parse_fragment("<ul><li>a</li><li>b</li></ul>").remove(0));
+/// let ul = document.root();
+/// let a = document.children(ul)[0];
+/// let b = document.next_sibling(a).unwrap();
+/// assert_eq!(document.parent(b), Some(ul));
+/// document.remove_child(ul, a).unwrap();
+/// document.append_child(ul, a).unwrap();
+/// assert_eq!(document.into_node().outer_html(), "<ul><li>b</li><li>a</li></ul>");
+/// ```
+#[derive(Debug)]
+pub struct Document {
+    nodes: Vec<DocumentNode>,
+    root: NodeId,
+}
+
+impl From<Box<Node>> for Document {
+    fn from(node: Box<Node>) -> Self {
+        let mut document = Document {
+            nodes: vec![],
+            root: NodeId(0),
+        };
+        document.root = document.adopt(*node, None);
+        document
+    }
+}
+
+impl Document {
+    /// Create a document made of an empty element named `tag_name`
+    pub fn new(tag_name: &str) -> Self {
+        Document::from(Element::new(tag_name.to_string(), AttrMap::new(), vec![]))
+    }
+
+    /// Move `node` and its descendants into the arena, under `parent`
+    fn adopt(&mut self, node: Node, parent: Option<NodeId>) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(DocumentNode {
+            node_type: node.node_type,
+            span: node.span,
+            parent,
+            children: vec![],
+        });
+        let children = node
+            .children
+            .into_iter()
+            .map(|child| self.adopt(*child, Some(id)))
+            .collect();
+        self.nodes[id.0].children = children;
+        id
+    }
+
+    /// Turn the document back into a `Box<Node>` tree, leaving out detached nodes
+    pub fn into_node(self) -> Box<Node> {
+        let root = self.root;
+        let mut nodes: Vec<Option<DocumentNode>> = self.nodes.into_iter().map(Some).collect();
+        Self::take_node(&mut nodes, root)
+    }
+
+    fn take_node(nodes: &mut [Option<DocumentNode>], id: NodeId) -> Box<Node> {
+        let node = nodes[id.0].take().unwrap();
+        Box::new(Node {
+            node_type: node.node_type,
+            children: node
+                .children
+                .into_iter()
+                .map(|child| Self::take_node(nodes, child))
+                .collect(),
+            span: node.span,
         })
     }
+
+    pub fn root(&self) -> NodeId {
+        self.root
+    }
+
+    /// Get the number of nodes in the arena, detached ones included
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn node_type(&self, id: NodeId) -> &NodeType {
+        &self.nodes[id.0].node_type
+    }
+
+    pub fn node_type_mut(&mut self, id: NodeId) -> &mut NodeType {
+        &mut self.nodes[id.0].node_type
+    }
+
+    /// Get the byte range of the node in the source it was parsed from
+    pub fn source_span(&self, id: NodeId) -> Option<Range<usize>> {
+        self.nodes[id.0].span.clone()
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id.0].parent
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.nodes[id.0].children
+    }
+
+    pub fn next_sibling(&self, id: NodeId) -> Option<NodeId> {
+        let siblings = self.children(self.parent(id)?);
+        let index = self.index_in_parent(id)?;
+        siblings.get(index + 1).copied()
+    }
+
+    pub fn previous_sibling(&self, id: NodeId) -> Option<NodeId> {
+        let siblings = self.children(self.parent(id)?);
+        let index = self.index_in_parent(id)?;
+        index.checked_sub(1).map(|index| siblings[index])
+    }
+
+    fn index_in_parent(&self, id: NodeId) -> Option<usize> {
+        self.children(self.parent(id)?)
+            .iter()
+            .position(|&child| child == id)
+    }
+
+    /// Get the nodes from the root of the tree `id` is in down to `id`, both included
+    pub fn ancestors(&self, id: NodeId) -> Vec<NodeId> {
+        let mut nodes = vec![id];
+        while let Some(parent) = self.parent(nodes[0]) {
+            nodes.insert(0, parent);
+        }
+        nodes
+    }
+
+    /// Whether `id` is in the tree under the root, and not detached
+    pub fn is_attached(&self, id: NodeId) -> bool {
+        self.ancestors(id)[0] == self.root
+    }
+
+    /// Add a detached element, to be inserted with [`Document::append_child`] or
+    /// [`Document::insert_before`]
+    pub fn create_element(&mut self, tag_name: &str, attributes: AttrMap) -> NodeId {
+        self.adopt(
+            *Element::new(tag_name.to_string(), attributes, vec![]),
+            None,
+        )
+    }
+
+    /// Add a detached text node
+    pub fn create_text(&mut self, text: &str) -> NodeId {
+        self.adopt(*Text::new(text.to_string()), None)
+    }
+
+    /// Move a tree built by the parsers into the arena, detached, and get the id of its root
+    pub fn import(&mut self, node: Node) -> NodeId {
+        self.adopt(node, None)
+    }
+
+    /// Add `child` after the last child of `parent`, moving it from where it was
+    pub fn append_child(&mut self, parent: NodeId, child: NodeId) -> Result<(), BrowserError> {
+        self.insert_before(parent, child, None)
+    }
+
+    /// Insert `child` before `reference`, a child of `parent`, or after the last child
+    /// when there is no reference, moving it from where it was
+    ///
+    /// Fails when `parent` is a text node, when `child` is `parent` or one of its
+    /// ancestors, and when `reference` isn't a child of `parent`.
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::dom::Document;
+    /// let mut document = Document::new("ul");
+    /// let ul = document.root();
+    /// let b = document.create_text("b");
+    /// let a = document.create_text("a");
+    /// document.append_child(ul, b).unwrap();
+    /// document.insert_before(ul, a, Some(b)).unwrap();
+    /// assert_eq!(document.children(ul), [a, b]);
+    /// assert!(document.append_child(a, b).is_err());
+    /// ```
+    pub fn insert_before(
+        &mut self,
+        parent: NodeId,
+        child: NodeId,
+        reference: Option<NodeId>,
+    ) -> Result<(), BrowserError> {
+        if let NodeType::Text(_) = self.node_type(parent) {
+            return Err(DomError::TextParent.into());
+        }
+        if self.ancestors(parent).contains(&child) {
+            return Err(DomError::Cycle.into());
+        }
+        if let Some(reference) = reference {
+            if reference == child {
+                return Ok(());
+            }
+            if self.parent(reference) != Some(parent) {
+                return Err(DomError::NotAChild.into());
+            }
+        }
+        self.detach(child);
+        let siblings = &mut self.nodes[parent.0].children;
+        let index = match reference {
+            Some(reference) => siblings.iter().position(|&c| c == reference).unwrap(),
+            None => siblings.len(),
+        };
+        siblings.insert(index, child);
+        self.nodes[child.0].parent = Some(parent);
+        Ok(())
+    }
+
+    /// Detach `child` and its subtree from `parent`
+    pub fn remove_child(&mut self, parent: NodeId, child: NodeId) -> Result<(), BrowserError> {
+        if self.parent(child) != Some(parent) {
+            return Err(DomError::NotAChild.into());
+        }
+        self.detach(child);
+        Ok(())
+    }
+
+    /// Put `child` in place of `old`, a child of `parent`, detaching `old`
+    pub fn replace_child(
+        &mut self,
+        parent: NodeId,
+        child: NodeId,
+        old: NodeId,
+    ) -> Result<(), BrowserError> {
+        if child == old {
+            return if self.parent(old) == Some(parent) {
+                Ok(())
+            } else {
+                Err(DomError::NotAChild.into())
+            };
+        }
+        self.insert_before(parent, child, Some(old))?;
+        self.remove_child(parent, old)
+    }
+
+    /// Take `id` out of the children of its parent, if it has one
+    pub fn detach(&mut self, id: NodeId) {
+        if let Some(parent) = self.nodes[id.0].parent.take() {
+            self.nodes[parent.0].children.retain(|&child| child != id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::{
+        css::css::SimpleSelector,
+        html::html::{parse, parse_fragment},
+    };
+
+    use super::*;
+
+    const HTML: &str = r#"<body>
+    <p>hello</p>
+    <p class="inline">world</p>
+    <div class="none"><p>this should not be shown</p></div>
+    <div id="result">
+        <p>not loaded</p>
+    </div>
+</body>"#;
+
+    /// The previous implementation, joining the text of every level
+    fn inner_text_joined(node: &Node) -> String {
+        node.children
+            .iter()
+            .map(|node| match &node.node_type {
+                NodeType::Text(t) => t.data.clone(),
+                _ => inner_text_joined(node),
+            })
+            .collect::<Vec<_>>()
+            .join("")
+    }
+
+    /// A tree of nested divs with `20 * width^depth` text nodes at the leaves
+    fn large_tree(width: usize, depth: usize) -> Box<Node> {
+        if depth == 0 {
+            return Element::new(
+                "span".to_string(),
+                AttrMap::new(),
+                (0..20).map(|i| Text::new(i.to_string())).collect(),
+            );
+        }
+        Element::new(
+            "div".to_string(),
+            AttrMap::new(),
+            (0..width).map(|_| large_tree(width, depth - 1)).collect(),
+        )
+    }
+
+    #[test]
+    fn test_inner_text() {
+        let node = parse(HTML);
+        assert_eq!(node.inner_text(), inner_text_joined(&node));
+        assert_eq!(
+            parse("<p>hello <b>wor<i>l</i>d</b></p>").inner_text(),
+            "hello world"
+        );
+    }
+
+    #[test]
+    fn test_inner_text_large_tree() {
+        let node = large_tree(50, 2);
+        assert_eq!(node.inner_text(), inner_text_joined(&node));
+    }
+
+    #[test]
+    fn test_closest_delegation() {
+        let node = parse(
+            r#"<ul class="menu"><li id="a"><span><b>first</b></span></li><li id="b"><span>second</span></li></ul>"#,
+        );
+        let li = SimpleSelector::TypeSelector {
+            tag_name: "li".into(),
+        };
+        let body = node.body().unwrap();
+        let id = |n: &Node| match &n.node_type {
+            NodeType::Element(e) => e.attributes.get("id").cloned(),
+            NodeType::Text(_) => None,
+        };
+        let first = &body.children[0].children[0].children[0].children[0].children[0];
+        let second = &body.children[0].children[1].children[0].children[0];
+        assert_eq!(node.closest(first, &li).and_then(id), Some("a".to_string()));
+        assert_eq!(
+            node.closest(second, &li).and_then(id),
+            Some("b".to_string())
+        );
+        assert!(std::ptr::eq(
+            node.closest(&body.children[0].children[0], &li).unwrap(),
+            body.children[0].children[0].as_ref()
+        ));
+        assert!(node
+            .closest(
+                first,
+                &SimpleSelector::TypeSelector {
+                    tag_name: "p".into()
+                }
+            )
+            .is_none());
+    }
+
+    #[test]
+    fn test_matches() {
+        let nodes = parse_fragment(r#"<p id="a" class="x">text</p>"#);
+        let p = nodes[0].as_ref();
+        assert!(p.matches(&SimpleSelector::UniversalSelector));
+        assert!(p.matches(&SimpleSelector::TypeSelector {
+            tag_name: "p".into()
+        }));
+        assert!(p.matches(&SimpleSelector::ClassSelector {
+            class_name: "x".into()
+        }));
+        assert!(!p.matches(&SimpleSelector::TypeSelector {
+            tag_name: "div".into()
+        }));
+        assert!(!p.children[0].matches(&SimpleSelector::ClassSelector {
+            class_name: "x".into()
+        }));
+    }
+
+    #[test]
+    fn test_attr_map_order() {
+        let mut attributes: AttrMap = ["b", "a", "c"]
+            .iter()
+            .map(|name| (name.to_string(), name.to_uppercase()))
+            .collect();
+        attributes.insert("a".to_string(), "again".to_string());
+        attributes.insert("d".to_string(), "D".to_string());
+        assert_eq!(attributes.remove("b"), Some("B".to_string()));
+        assert_eq!(attributes.remove("b"), None);
+        let entries: Vec<_> = attributes.clone().into_iter().collect();
+        assert_eq!(
+            entries,
+            vec![
+                ("a".to_string(), "again".to_string()),
+                ("c".to_string(), "C".to_string()),
+                ("d".to_string(), "D".to_string()),
+            ]
+        );
+        assert_eq!(attributes["c"], "C");
+        assert_eq!(
+            format!("{:?}", attributes),
+            r#"{"a": "again", "c": "C", "d": "D"}"#
+        );
+    }
+
+    #[test]
+    fn test_attr_map_eq_ignores_order() {
+        let forward: AttrMap = [("a", "1"), ("b", "2")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let backward: AttrMap = [("b", "2"), ("a", "1")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        assert_eq!(forward, backward);
+        let mut changed = backward.clone();
+        changed.insert("a".to_string(), "3".to_string());
+        assert_ne!(forward, changed);
+        changed.remove("a");
+        assert_ne!(forward, changed);
+    }
+
+    #[test]
+    fn test_outer_html_keeps_attribute_order() {
+        let html = r#"<p title="t" id="x" class="a" data-z="1">x</p>"#;
+        assert_eq!(parse_fragment(html)[0].outer_html(), html);
+    }
+
+    #[test]
+    fn test_dataset() {
+        let node =
+            parse(r#"<div data-id="5" data-x-y="z" aria-label="x" data-="empty"><p>a</p></div>"#);
+        let mut expected = AttrMap::new();
+        expected.insert("id".to_string(), "5".to_string());
+        expected.insert("x-y".to_string(), "z".to_string());
+        expected.insert("".to_string(), "empty".to_string());
+        let div = &node.body().unwrap().children[0];
+        assert_eq!(div.dataset(), expected);
+        assert_eq!(div.children[0].dataset(), AttrMap::new());
+        assert_eq!(div.children[0].children[0].dataset(), AttrMap::new());
+    }
+
+    #[test]
+    fn test_closest_attribute() {
+        let node = parse(
+            r#"<body title="page"><a href="/x" title="go"><b>link</b></a><p>plain</p></body>"#,
+        );
+        let body = node.body().unwrap();
+        let bold_text = &body.children[0].children[0].children[0];
+        assert_eq!(node.closest_attribute(bold_text, "title"), Some("go"));
+        assert_eq!(node.closest_attribute(bold_text, "href"), Some("/x"));
+        assert_eq!(
+            node.closest_attribute(&body.children[1], "title"),
+            Some("page")
+        );
+        assert_eq!(node.closest_attribute(&body.children[1], "href"), None);
+    }
+
+    #[test]
+    fn test_node_path() {
+        let node = parse(HTML);
+        let body = node.body().unwrap();
+        assert_eq!(node.node_path(&node), Some("/html".to_string()));
+        assert_eq!(node.node_path(body), Some("/html/body[1]".to_string()));
+        assert_eq!(
+            node.node_path(&body.children[1]),
+            Some("/html/body[1]/p[2]".to_string())
+        );
+        assert_eq!(
+            node.node_path(&body.children[3].children[0]),
+            Some("/html/body[1]/div[2]/p[1]".to_string())
+        );
+        assert_eq!(
+            node.node_path(&body.children[0].children[0]),
+            Some("/html/body[1]/p[1]/text()[1]".to_string())
+        );
+    }
+
+    #[test]
+    fn test_node_path_not_descendant() {
+        let node = parse(HTML);
+        let other = Text::new("hello".to_string());
+        assert_eq!(node.node_path(&other), None);
+    }
+
+    #[test]
+    fn test_resolve_path_round_trip() {
+        let node = parse(HTML);
+        let body = node.body().unwrap();
+        for target in [
+            &body.children[0],
+            &body.children[2],
+            &body.children[2].children[0],
+            &body.children[3].children[0].children[0],
+        ] {
+            let path = node.node_path(target).unwrap();
+            let resolved = node.resolve_path(&path).unwrap();
+            assert!(std::ptr::eq(resolved, target.as_ref()));
+
+            let reparsed = parse(HTML);
+            assert_eq!(reparsed.resolve_path(&path), Some(target.as_ref()));
+        }
+    }
+
+    #[test]
+    fn test_resolve_path_stale() {
+        let node = parse(HTML);
+        assert!(node.resolve_path("/html/body[1]/div[3]/p[1]").is_none());
+        assert!(node.resolve_path("/html/body[1]/p[0]").is_none());
+        assert!(node.resolve_path("/html/p[1]").is_none());
+        assert!(node.resolve_path("/body/p[1]").is_none());
+        assert!(node.resolve_path("/html/body[1]/p[x]").is_none());
+        assert!(node.resolve_path("html/body[1]/p[1]").is_none());
+    }
+
+    #[test]
+    fn test_outer_html_round_trip() {
+        for raw in [
+            HTML,
+            r#"<body><p class="a" id="b">hello</p><div><p>nested <b>bold</b></p></div></body>"#,
+            r#"<head><title>t</title></head><body><a href="x?a=1&amp;b=&quot;2&quot;">1 &lt; 2 &amp; 3 &gt; 0</a></body>"#,
+            r#"<body><p>line<br>break</p><img src="a.png"></body>"#,
+            r#"<head><style>p > a { color: red; }</style></head><body><script>if (a < b && c) {}</script></body>"#,
+        ] {
+            let node = parse(raw);
+            assert_eq!(parse(&node.outer_html()), node, "{}", raw);
+        }
+    }
+
+    #[test]
+    fn test_inner_html() {
+        let node =
+            parse(r#"<body><p data-x="a&quot;b">x &amp; y<br></p><script>a > b</script></body>"#);
+        assert_eq!(
+            node.body().unwrap().inner_html(),
+            r#"<p data-x="a&quot;b">x &amp; y<br></p><script>a > b</script>"#
+        );
+    }
+
+    const LINKS: &str = r#"<body>
+    <p>see <a href="/one">one</a> and <a class="ext" href="https://example.com/">two</a></p>
+    <div class="note"><a name="anchor">no link</a><b>bold</b>, &amp; more</div>
+</body>"#;
+
+    #[test]
+    fn test_collect_attributes() {
+        let node = parse(LINKS);
+        assert_eq!(
+            node.collect("a", Collect::Attr("href".to_string())),
+            vec!["/one", "https://example.com/"]
+        );
+        assert_eq!(
+            node.collect("a", Collect::Attr("name".to_string())),
+            vec!["anchor"]
+        );
+        assert!(node.collect("a {", Collect::InnerText).is_empty());
+    }
+
+    #[test]
+    fn test_collect_inner_html() {
+        let node = parse(LINKS);
+        assert_eq!(
+            node.collect(".note", Collect::InnerHtml),
+            vec![r#"<a name="anchor">no link</a><b>bold</b>, &amp; more"#]
+        );
+        assert_eq!(
+            node.collect(".ext, b", Collect::InnerText),
+            vec!["two", "bold"]
+        );
+    }
+
+    #[test]
+    fn test_get_element_by_id_nested() {
+        let node = parse(HTML);
+        let result = node.get_element_by_id("result").unwrap();
+        assert_eq!(result.inner_text(), "not loaded");
+        let node = parse(r#"<div id="outer"><ul><li><span id="inner">deep</span></li></ul></div>"#);
+        assert_eq!(
+            node.get_element_by_id("inner").unwrap().inner_text(),
+            "deep"
+        );
+        assert_eq!(node.get_element_by_id("outer").unwrap().children.len(), 1);
+    }
+
+    #[test]
+    fn test_get_element_by_id_missing() {
+        let node = parse(r#"<p id="a">a</p><p>id</p>"#);
+        assert!(node.get_element_by_id("b").is_none());
+        assert!(node.get_element_by_id("id").is_none());
+        assert!(node.get_element_by_id("").is_none());
+    }
+
+    #[test]
+    fn test_get_element_by_id_duplicate() {
+        let mut node = parse(r#"<div><p id="x">first</p></div><p id="x">second</p>"#);
+        assert_eq!(node.get_element_by_id("x").unwrap().inner_text(), "first");
+        node.get_element_by_id_mut("x").unwrap().children = vec![Text::new("changed".to_string())];
+        assert_eq!(node.body().unwrap().inner_text(), "changedsecond");
+    }
+
+    const QUOTED: &str = r#"<div id="main" class="a b" title="say &quot;hi&quot; &amp; 'bye'"><p>x &amp; y</p><p>1 &lt; 2</p></div>"#;
+
+    #[rstest]
+    #[case(
+        true,
+        QuoteStyle::Double,
+        r#"<div id="main" class="a b" title="say &quot;hi&quot; &amp; 'bye'"><p>x &amp; y</p><p>1 &lt; 2</p></div>"#
+    )]
+    #[case(
+        false,
+        QuoteStyle::Double,
+        r#"<div id="main" class="a b" title="say &quot;hi&quot; &amp; 'bye'"><p>x & y</p><p>1 < 2</p></div>"#
+    )]
+    #[case(
+        true,
+        QuoteStyle::Single,
+        r#"<div id='main' class='a b' title='say "hi" &amp; &#39;bye&#39;'><p>x &amp; y</p><p>1 &lt; 2</p></div>"#
+    )]
+    #[case(
+        false,
+        QuoteStyle::Single,
+        r#"<div id='main' class='a b' title='say "hi" &amp; &#39;bye&#39;'><p>x & y</p><p>1 < 2</p></div>"#
+    )]
+    #[case(
+        true,
+        QuoteStyle::Minimal,
+        r#"<div id=main class="a b" title="say &quot;hi&quot; &amp; 'bye'"><p>x &amp; y</p><p>1 &lt; 2</p></div>"#
+    )]
+    #[case(
+        false,
+        QuoteStyle::Minimal,
+        r#"<div id=main class="a b" title="say &quot;hi&quot; &amp; 'bye'"><p>x & y</p><p>1 < 2</p></div>"#
+    )]
+    fn test_to_html_with(
+        #[case] escape_text: bool,
+        #[case] quote_attrs: QuoteStyle,
+        #[case] expected: &str,
+    ) {
+        let node = parse(QUOTED);
+        let options = SerializeOptions {
+            escape_text,
+            quote_attrs,
+            ..SerializeOptions::default()
+        };
+        assert_eq!(
+            node.body().unwrap().children[0].to_html_with(&options),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case(
+        true,
+        2,
+        "<body>\n  <div id=main class=a>\n    <p>x &amp; y</p>\n    <pre> a\n  b</pre>\n  </div>\n</body>"
+    )]
+    #[case(
+        false,
+        4,
+        "<body>\n    <div id=main class=a>\n        <p>x & y</p>\n        <pre> a\n  b</pre>\n    </div>\n</body>"
+    )]
+    fn test_to_html_with_pretty(
+        #[case] escape_text: bool,
+        #[case] indent: usize,
+        #[case] expected: &str,
+    ) {
+        let node = parse("<div id=\"main\" class=\"a\"><p>x &amp; y</p><pre> a\n  b</pre></div>");
+        let options = SerializeOptions {
+            escape_text,
+            quote_attrs: QuoteStyle::Minimal,
+            pretty: true,
+            indent,
+        };
+        assert_eq!(node.body().unwrap().to_html_with(&options), expected);
+    }
+
+    #[test]
+    fn test_to_html_with_default_is_outer_html() {
+        let node = parse(QUOTED);
+        let div = &node.body().unwrap().children[0];
+        assert_eq!(
+            div.to_html_with(&SerializeOptions::default()),
+            div.outer_html()
+        );
+        assert_eq!(div.inner_html(), "<p>x &amp; y</p><p>1 &lt; 2</p>");
+    }
+
+    #[test]
+    fn test_get_elements_by_tag_name() {
+        let node = parse(
+            r#"<div><p>a<span><p>not nested</p></span></p><SECTION><Span>b</Span></SECTION></div>"#,
+        );
+        let spans = node.get_elements_by_tag_name("span");
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[1].inner_text(), "b");
+        assert_eq!(node.get_elements_by_tag_name("SPAN").len(), 2);
+        assert_eq!(node.get_elements_by_tag_name("p").len(), 2);
+        assert!(node.get_elements_by_tag_name("table").is_empty());
+        let div = &node.body().unwrap().children[0];
+        assert_eq!(div.get_elements_by_tag_name("*").len(), 6);
+    }
+
+    #[test]
+    fn test_get_elements_by_class_name() {
+        let node = parse(
+            "<ul class=\"menu\"><li class=\"a  b\n\tc\">1</li><li class=\"ab\"><b class=\" b \">2</b></li></ul>",
+        );
+        let texts = |class| {
+            node.get_elements_by_class_name(class)
+                .iter()
+                .map(|n| n.inner_text())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(texts("b"), vec!["1", "2"]);
+        assert_eq!(texts("c"), vec!["1"]);
+        assert_eq!(texts("menu"), vec!["12"]);
+        assert!(texts("a b").is_empty());
+        assert!(texts("").is_empty());
+    }
+
+    const QUERIED: &str = r#"<body><div class="card"><p lang="en">hello</p><p class="note">a note</p></div><p lang="ja">konnichiwa</p></body>"#;
+
+    #[test]
+    fn test_query_selector_type() {
+        let node = parse(QUERIED);
+        assert_eq!(node.query_selector("p").unwrap().inner_text(), "hello");
+        assert_eq!(node.query_selector_all("p").len(), 3);
+    }
+
+    #[test]
+    fn test_query_selector_class() {
+        let node = parse(QUERIED);
+        assert_eq!(node.query_selector(".note").unwrap().inner_text(), "a note");
+        assert_eq!(node.query_selector_all(".card").len(), 1);
+    }
+
+    #[test]
+    fn test_query_selector_attribute() {
+        let node = parse(QUERIED);
+        assert_eq!(
+            node.query_selector("p[lang=ja]").unwrap().inner_text(),
+            "konnichiwa"
+        );
+        assert_eq!(node.query_selector_all("p[lang=en], .note").len(), 2);
+    }
+
+    #[test]
+    fn test_query_selector_universal() {
+        let node = parse(QUERIED);
+        assert!(std::ptr::eq(
+            node.query_selector("*").unwrap(),
+            node.as_ref()
+        ));
+        // Only elements are selected, as in the DOM
+        let all = node.query_selector_all("*");
+        assert_eq!(all.len(), count_elements(&node));
+        assert!(all
+            .iter()
+            .all(|n| matches!(n.node_type, NodeType::Element(_))));
+    }
+
+    #[test]
+    fn test_query_selector_no_match() {
+        let node = parse(QUERIED);
+        assert!(node.query_selector("table").is_none());
+        assert!(node.query_selector("p[lang=fr]").is_none());
+        assert!(node.query_selector("p {").is_none());
+        assert!(node.query_selector_all(".missing").is_empty());
+    }
+
+    fn count_elements(node: &Node) -> usize {
+        let own = matches!(node.node_type, NodeType::Element(_)) as usize;
+        own + node
+            .children
+            .iter()
+            .map(|c| count_elements(c))
+            .sum::<usize>()
+    }
+
+    #[test]
+    fn test_set_attribute_new() {
+        let mut nodes = parse_fragment("<p>a</p>");
+        nodes[0].set_attribute("title", "note");
+        assert_eq!(nodes[0].get_attribute("title"), Some("note"));
+        assert!(nodes[0].has_attribute("title"));
+    }
+
+    #[test]
+    fn test_set_attribute_overwrites() {
+        let mut nodes = parse_fragment(r#"<p class="a" id="x">a</p>"#);
+        nodes[0].set_attribute("class", "b");
+        assert_eq!(nodes[0].get_attribute("class"), Some("b"));
+        assert_eq!(nodes[0].get_attribute("id"), Some("x"));
+    }
+
+    #[test]
+    fn test_remove_attribute() {
+        let mut nodes = parse_fragment(r#"<p class="a" id="x">a</p>"#);
+        assert_eq!(nodes[0].remove_attribute("class"), Some("a".to_string()));
+        assert!(!nodes[0].has_attribute("class"));
+        assert_eq!(nodes[0].remove_attribute("missing"), None);
+        assert_eq!(nodes[0].outer_html(), r#"<p id="x">a</p>"#);
+    }
+
+    #[test]
+    fn test_attributes_of_text() {
+        let mut node = Text::new("a".to_string());
+        node.set_attribute("class", "b");
+        assert_eq!(node.get_attribute("class"), None);
+        assert!(!node.has_attribute("class"));
+        assert_eq!(node.remove_attribute("class"), None);
+    }
+
+    #[test]
+    fn test_class_list_normalizes_source() {
+        let nodes = parse_fragment("<p class=\"  b a\tb  c \">x</p>");
+        assert_eq!(nodes[0].class_list(), vec!["b", "a", "c"]);
+        assert!(nodes[0].has_class("a"));
+        assert!(!nodes[0].has_class("b a"));
+        assert!(!nodes[0].has_class(""));
+        // Reading leaves the attribute as written
+        assert_eq!(nodes[0].get_attribute("class"), Some("  b a\tb  c "));
+    }
+
+    #[test]
+    fn test_add_class() {
+        let mut nodes = parse_fragment("<p class=\" a  a \">x</p><p>y</p>");
+        nodes[0].add_class("b");
+        assert_eq!(nodes[0].get_attribute("class"), Some("a b"));
+        nodes[0].add_class("b");
+        nodes[0].add_class("c d");
+        nodes[0].add_class("");
+        assert_eq!(nodes[0].class_list(), vec!["a", "b"]);
+        nodes[1].add_class("new");
+        assert_eq!(nodes[1].get_attribute("class"), Some("new"));
+    }
+
+    #[test]
+    fn test_remove_class() {
+        let mut nodes = parse_fragment("<p class=\"a b a c\">x</p><p>y</p>");
+        nodes[0].remove_class("a");
+        assert_eq!(nodes[0].get_attribute("class"), Some("b c"));
+        nodes[0].remove_class("missing");
+        assert_eq!(nodes[0].get_attribute("class"), Some("b c"));
+        nodes[0].remove_class("b");
+        nodes[0].remove_class("c");
+        assert_eq!(nodes[0].get_attribute("class"), Some(""));
+        assert!(nodes[0].class_list().is_empty());
+        nodes[1].remove_class("a");
+        assert!(!nodes[1].has_attribute("class"));
+    }
+
+    #[test]
+    fn test_toggle_class() {
+        let mut nodes = parse_fragment("<p class=\"a  b\">x</p>");
+        assert!(nodes[0].toggle_class("c"));
+        assert_eq!(nodes[0].get_attribute("class"), Some("a b c"));
+        assert!(!nodes[0].toggle_class("a"));
+        assert_eq!(nodes[0].get_attribute("class"), Some("b c"));
+        let mut text = Text::new("t".to_string());
+        assert!(!text.toggle_class("a"));
+        assert!(text.class_list().is_empty());
+    }
+
+    #[test]
+    fn test_insert_before_reference() {
+        let mut nodes = parse_fragment("<ul><li>a</li><li>c</li></ul>");
+        let ul = &mut nodes[0];
+        let index = ul.child_index(&ul.children[1]).unwrap();
+        ul.insert_before(index, parse_fragment("<li>b</li>").remove(0))
+            .unwrap();
+        ul.insert_before(0, parse_fragment("<li>0</li>").remove(0))
+            .unwrap();
+        ul.append_child(parse_fragment("<li>d</li>").remove(0))
+            .unwrap();
+        assert_eq!(
+            ul.outer_html(),
+            "<ul><li>0</li><li>a</li><li>b</li><li>c</li><li>d</li></ul>"
+        );
+        assert_eq!(
+            ul.insert_before(6, Text::new("x".to_string())),
+            Err("index 6 is out of range for 5 children".to_string())
+        );
+    }
+
+    #[test]
+    fn test_remove_and_replace_child() {
+        let mut nodes = parse_fragment("<p>a<b>b</b>c</p>");
+        let p = &mut nodes[0];
+        let b = p.remove_child(1).unwrap();
+        assert_eq!(p.outer_html(), "<p>ac</p>");
+        let a = p.replace_child(0, b).unwrap();
+        assert_eq!(a.outer_html(), "a");
+        assert_eq!(p.outer_html(), "<p><b>b</b>c</p>");
+        assert!(p.replace_child(2, Text::new("x".to_string())).is_none());
+        assert!(p.remove_child(2).is_none());
+        assert_eq!(p.children.len(), 2);
+    }
+
+    #[test]
+    fn test_mutate_text_node() {
+        let mut text = Text::new("t".to_string());
+        assert_eq!(
+            text.append_child(Text::new("u".to_string())),
+            Err("text nodes can't have children".to_string())
+        );
+        assert!(text
+            .replace_children(vec![Text::new("u".to_string())])
+            .is_err());
+        assert_eq!(text.replace_children(vec![]), Ok(vec![]));
+        assert!(text.remove_child(0).is_none());
+        assert!(text.children.is_empty());
+    }
+
+    #[rstest]
+    #[case("<p>a <b>b</b></p><ul><li>c</li></ul>", "a bc", 2)]
+    #[case("just text", "just text", 1)]
+    #[case("", "", 0)]
+    fn test_set_inner_html(#[case] html: &str, #[case] text: &str, #[case] children: usize) {
+        let mut node = parse(HTML);
+        let result = node.get_element_by_id_mut("result").unwrap();
+        result.set_inner_html(html).unwrap();
+        assert_eq!(result.children.len(), children);
+        assert_eq!(result.inner_text(), text);
+        assert_eq!(node.get_element_by_id("result").unwrap().inner_html(), html);
+        assert!(!node.inner_text().contains("not loaded"));
+    }
+
+    #[test]
+    fn test_set_inner_text() {
+        let mut node = parse(HTML);
+        let result = node.get_element_by_id_mut("result").unwrap();
+        result.set_inner_text("a & <b>");
+        assert_eq!(result.children, vec![Text::new("a & <b>".to_string())]);
+        assert_eq!(result.inner_text(), "a & <b>");
+        result.set_inner_text("");
+        assert!(result.children.is_empty());
+
+        let mut text = Text::new("t".to_string());
+        text.set_inner_text("u");
+        text.set_inner_html("<p>u</p>").unwrap();
+        assert_eq!(text, Text::new("t".to_string()));
+    }
+
+    #[rstest]
+    #[case(
+        r#"<label for="x">Label</label><input id="x" aria-label="Aria" title="Title">"#,
+        "Aria"
+    )]
+    #[case(
+        r#"<label for="x">Label</label><input id="x" title="Title" placeholder="P">"#,
+        "Label"
+    )]
+    #[case(
+        r#"<label> Wrapping <b>label</b> <input id="x" title="Title"></label>"#,
+        "Wrapping label"
+    )]
+    #[case(
+        r#"<label for="y">Other</label><input id="x" title="Title" placeholder="P">"#,
+        "Title"
+    )]
+    #[case(
+        r#"<input id="x" type="search" placeholder="Search" name="q">"#,
+        "Search"
+    )]
+    #[case(r#"<input id="x" name="q">"#, "q")]
+    #[case(r#"<label for="x"> </label><button id="x" aria-label="" title="Close"><img alt=""></button>"#, "Close")]
+    fn test_accessible_name(#[case] html: &str, #[case] expected: &str) {
+        let node = parse(html);
+        let target = node.get_element_by_id("x").unwrap();
+        assert_eq!(node.accessible_name(target), Some(expected.to_string()));
+    }
+
+    #[test]
+    fn test_accessible_name_missing() {
+        let node = parse(r#"<button id="x">Send</button>"#);
+        let target = node.get_element_by_id("x").unwrap();
+        assert_eq!(node.accessible_name(target), None);
+        assert_eq!(node.accessible_name(&target.children[0]), None);
+    }
+
+    fn tag_of(document: &Document, id: NodeId) -> String {
+        match document.node_type(id) {
+            NodeType::Element(e) => e.tag_name.clone(),
+            NodeType::Text(t) => t.data.clone(),
+        }
+    }
+
+    #[test]
+    fn test_document_round_trip() {
+        let node = parse(HTML);
+        let html = node.outer_html();
+        let document = Document::from(node);
+        assert!((0..document.len())
+            .map(NodeId)
+            .all(|id| document.is_attached(id)));
+        assert_eq!(document.into_node().outer_html(), html);
+    }
+
+    #[test]
+    fn test_document_navigation() {
+        let document =
+            Document::from(parse_fragment("<ul><li>a</li><li>b</li><li>c</li></ul>").remove(0));
+        let ul = document.root();
+        let [a, b, c] = document.children(ul) else {
+            unreachable!()
+        };
+        assert_eq!(document.parent(ul), None);
+        assert_eq!(document.next_sibling(*a), Some(*b));
+        assert_eq!(document.next_sibling(*c), None);
+        assert_eq!(document.previous_sibling(*c), Some(*b));
+        assert_eq!(document.previous_sibling(*a), None);
+        assert_eq!(document.next_sibling(ul), None);
+        let text = document.children(*b)[0];
+        assert_eq!(tag_of(&document, text), "b");
+        assert_eq!(document.ancestors(text), vec![ul, *b, text]);
+        assert_eq!(document.source_span(*a), Some(4..8));
+    }
+
+    #[test]
+    fn test_document_mutation_keeps_ids() {
+        let mut document = Document::from(parse_fragment("<div><p>a</p><p>b</p></div>").remove(0));
+        let div = document.root();
+        let [a, b] = *document.children(div) else {
+            unreachable!()
+        };
+        let span = document.create_element("span", AttrMap::new());
+        let text = document.create_text("new");
+        document.append_child(span, text).unwrap();
+        document.insert_before(div, span, Some(b)).unwrap();
+        assert_eq!(document.children(div), [a, span, b]);
+
+        // Moving a node takes it out of its old parent
+        document.append_child(a, b).unwrap();
+        assert_eq!(document.children(div), [a, span]);
+        assert_eq!(document.parent(b), Some(a));
+
+        document.replace_child(div, b, span).unwrap();
+        assert!(!document.is_attached(span));
+        assert_eq!(document.parent(text), Some(span));
+        assert_eq!(document.children(div), [a, b]);
+
+        if let NodeType::Element(e) = document.node_type_mut(b) {
+            e.attributes.insert("id".to_string(), "moved".to_string());
+        }
+        assert_eq!(tag_of(&document, b), "p");
+        assert_eq!(
+            document.into_node().outer_html(),
+            r#"<div><p>a</p><p id="moved">b</p></div>"#
+        );
+    }
+
+    #[test]
+    fn test_document_detached_subtree_into_node() {
+        let mut document = Document::new("div");
+        let span = document.create_element("span", AttrMap::new());
+        let imported = document.import(*parse_fragment("<i>i</i>").remove(0));
+        document.append_child(span, imported).unwrap();
+        document.append_child(document.root(), span).unwrap();
+        assert_eq!(document.parent(imported), Some(span));
+        assert_eq!(
+            document.into_node().outer_html(),
+            "<div><span><i>i</i></span></div>"
+        );
+    }
+
+    #[test]
+    fn test_document_invalid_mutation() {
+        let mut document = Document::from(parse_fragment("<div><p>a</p></div>").remove(0));
+        let div = document.root();
+        let p = document.children(div)[0];
+        let text = document.children(p)[0];
+        let other = document.create_text("x");
+        assert_eq!(
+            document.append_child(text, other),
+            Err(BrowserError::Dom(DomError::TextParent))
+        );
+        assert_eq!(
+            document.append_child(p, div),
+            Err(BrowserError::Dom(DomError::Cycle))
+        );
+        assert!(document.append_child(p, p).is_err());
+        assert_eq!(
+            document.insert_before(div, other, Some(text)),
+            Err(BrowserError::Dom(DomError::NotAChild))
+        );
+        assert_eq!(
+            document.remove_child(div, text),
+            Err(BrowserError::Dom(DomError::NotAChild))
+        );
+        assert_eq!(
+            document.replace_child(div, other, text),
+            Err(BrowserError::Dom(DomError::NotAChild))
+        );
+        assert_eq!(document.children(div), [p]);
+        assert!(!document.is_attached(other));
+
+        document.remove_child(div, p).unwrap();
+        assert!(!document.is_attached(text));
+        assert_eq!(document.into_node().outer_html(), "<div></div>");
+    }
+
+    fn names<'a>(nodes: impl Iterator<Item = &'a Node>) -> Vec<String> {
+        nodes
+            .map(|node| match &node.node_type {
+                NodeType::Element(e) => e.tag_name.clone(),
+                NodeType::Text(t) => t.data.clone(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_descendants_order() {
+        let nodes = parse_fragment("<div><h1>t</h1><ul><li>a</li><li><b>b</b></li></ul>x</div>");
+        assert_eq!(
+            names(nodes[0].descendants()),
+            vec!["h1", "t", "ul", "li", "a", "li", "b", "b", "x"]
+        );
+        assert_eq!(names(nodes[0].children_elements()), vec!["h1", "ul"]);
+    }
+
+    #[test]
+    fn test_descendants_single_node() {
+        let text = Text::new("t".to_string());
+        assert_eq!(text.descendants().count(), 0);
+        assert_eq!(text.children_elements().count(), 0);
+        let nodes = parse_fragment("<br>");
+        assert_eq!(nodes[0].descendants().count(), 0);
+        assert_eq!(nodes[0].get_elements_by_tag_name("br").len(), 1);
+    }
+
+    #[test]
+    fn test_for_each_descendant_mut_order() {
+        let mut nodes = parse_fragment("<div><p>a</p><p><i>b</i></p></div>");
+        let mut visited = vec![];
+        nodes[0].for_each_descendant_mut(&mut |node| {
+            if let NodeType::Text(t) = &mut node.node_type {
+                t.data = t.data.to_uppercase();
+            }
+            visited.push(node.outer_html());
+        });
+        assert_eq!(
+            visited,
+            vec!["<p>a</p>", "A", "<p><i>b</i></p>", "<i>b</i>", "B"]
+        );
+        assert_eq!(nodes[0].inner_text(), "AB");
+    }
+
+    #[test]
+    fn test_clone_does_not_share_mutation() {
+        let original = parse(HTML);
+        let mut copy = original.clone();
+        assert_eq!(copy, original);
+        assert_eq!(copy.source_span(), original.source_span());
+
+        copy.get_element_by_id_mut("result")
+            .unwrap()
+            .set_inner_text("loaded");
+        copy.for_each_descendant_mut(&mut |node| {
+            if node.has_class("inline") {
+                node.set_attribute("class", "changed");
+            }
+        });
+        assert_ne!(copy, original);
+        assert_eq!(
+            original
+                .get_element_by_id("result")
+                .unwrap()
+                .inner_text()
+                .trim(),
+            "not loaded"
+        );
+        assert_eq!(original.get_elements_by_class_name("inline").len(), 1);
+        assert!(copy.get_elements_by_class_name("inline").is_empty());
+    }
+
+    #[test]
+    fn test_clone_node_shallow_and_deep() {
+        let nodes = parse_fragment(r#"<ul id="list"><li>a</li></ul>"#);
+        let mut deep = nodes[0].clone_node(true);
+        let mut shallow = nodes[0].clone_node(false);
+        assert_eq!(deep, nodes[0]);
+        assert!(shallow.children.is_empty());
+        assert_eq!(shallow.get_attribute("id"), Some("list"));
+
+        deep.children[0].set_inner_text("b");
+        shallow.set_attribute("id", "copy");
+        assert_eq!(nodes[0].outer_html(), r#"<ul id="list"><li>a</li></ul>"#);
+        assert_eq!(deep.inner_text(), "b");
+
+        let text = Text::new("t".to_string());
+        assert_eq!(text.clone_node(false), text);
+    }
+
+    #[test]
+    fn test_to_tree_string_golden() {
+        assert_eq!(
+            parse(HTML).to_tree_string(),
+            r#"html
+  head
+  body
+    p
+      #text "hello"
+    p.inline
+      #text "world"
+    div.none
+      p
+        #text "this should not be shown"
+    div#result
+      p
+        #text "not loaded""#
+        );
+    }
+
+    #[test]
+    fn test_to_tree_string_truncates_text() {
+        let long = "a".repeat(TREE_TEXT_LIMIT + 1);
+        let nodes = parse_fragment(&format!("<p>{}</p><p>\"quoted\"\n</p>", long));
+        assert_eq!(
+            nodes[0].to_tree_string(),
+            format!("p\n  #text \"{}…\"", &long[..TREE_TEXT_LIMIT])
+        );
+        assert_eq!(
+            nodes[1].to_tree_string(),
+            "p\n  #text \"\\\"quoted\\\"\\n\""
+        );
+        assert_eq!(Text::new("t".to_string()).to_tree_string(), "#text \"t\"");
+    }
 }
@@ -1,268 +1,2434 @@
+use std::io::Read;
+use std::ops::Range;
+
+use crate::error::error::HtmlParseError;
 use crate::html::dom::AttrMap;
 use crate::html::dom::Element;
 use crate::html::dom::Node;
+use crate::html::dom::NodeType;
 use crate::html::dom::Text;
+use crate::html::parser::close_tag;
+use crate::html::parser::decode_entities;
+use crate::html::parser::open_tag;
+use crate::html::parser::open_tag_with_duplicates;
+use crate::html::parser::self_closing_tag_with_duplicates;
+use crate::html::parser::tag_name;
+use crate::html::parser::whitespaces;
 use combine::attempt;
-use combine::between;
 use combine::choice;
+use combine::eof;
 use combine::error::ParseError;
 use combine::error::StreamError;
 use combine::many;
+use combine::optional;
 use combine::parser;
 use combine::parser::char::char;
-use combine::parser::char::letter;
-use combine::parser::char::newline;
-use combine::parser::char::space;
+use combine::parser::char::string;
+use combine::parser::char::string_cmp;
+use combine::parser::repeat::skip_until;
+use combine::parser::repeat::take_until;
 use combine::satisfy;
-use combine::sep_by;
+use combine::skip_many;
+use combine::stream::position;
+use combine::stream::position::Positioner;
+use combine::stream::PointerOffset;
+use combine::value;
 use combine::{many1, Parser, Stream};
 
-fn whitespaces<Input>() -> impl Parser<Input, Output = String>
+/// How many elements may be open at once before the parser stops nesting them
+///
+/// Keeping [`Node`] trees this shallow means walking them recursively cannot
+/// overflow the stack.
+pub const DEFAULT_MAX_DEPTH: usize = 256;
+
+/// How deep the recursive grammar nests elements before leaving a document to the
+/// tree builder, kept low enough not to overflow a thread's stack in debug builds
+const GRAMMAR_MAX_DEPTH: usize = 32;
+
+/// Elements that never have children and are written without a close tag
+pub const VOID_ELEMENTS: [&str; 13] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// Elements whose end tag may be left out, so that closing their parent closes them quietly
+const OPTIONAL_END_TAGS: [&str; 8] = ["li", "p", "dt", "dd", "option", "tr", "td", "th"];
+
+/// Elements that don't flow inline with their siblings, so that whitespace next to them
+/// separates nothing
+const BLOCK_ELEMENTS: [&str; 46] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "body",
+    "br",
+    "dd",
+    "details",
+    "dialog",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "header",
+    "hr",
+    "html",
+    "li",
+    "link",
+    "main",
+    "meta",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "script",
+    "section",
+    "style",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "title",
+    "tr",
+];
+
+/// Elements whose whitespace is kept as written
+const PRESERVED_WHITESPACE: [&str; 4] = ["pre", "textarea", "script", "style"];
+
+/// The open elements whose end is implied by opening `tag_name`, and the open elements
+/// that stop the search for them
+fn implied_end_tags(tag_name: &str) -> Option<(&'static [&'static str], &'static [&'static str])> {
+    match tag_name {
+        "li" => Some((&["li"], &["ul", "ol"])),
+        "p" => Some((&["p"], &["button", "table", "td", "th"])),
+        "dt" | "dd" => Some((&["dt", "dd"], &["dl"])),
+        "option" => Some((&["option"], &["select", "datalist", "optgroup"])),
+        "tr" => Some((&["tr"], &["table", "thead", "tbody", "tfoot"])),
+        "td" | "th" => Some((&["td", "th"], &["tr", "table"])),
+        _ => None,
+    }
+}
+
+/// Tracks the byte offset into a `&str` input, so that parsed nodes get source spans
+#[derive(Clone, Debug, Default)]
+struct ByteOffset(usize);
+
+impl Positioner<char> for ByteOffset {
+    type Position = usize;
+    type Checkpoint = Self;
+
+    fn position(&self) -> usize {
+        self.0
+    }
+
+    fn update(&mut self, token: &char) {
+        self.0 += token.len_utf8()
+    }
+
+    fn checkpoint(&self) -> Self {
+        self.clone()
+    }
+
+    fn reset(&mut self, checkpoint: Self) {
+        *self = checkpoint
+    }
+}
+
+fn spanned_input(raw: &str, offset: usize) -> position::Stream<&str, ByteOffset> {
+    position::Stream::with_positioner(raw, ByteOffset(offset))
+}
+
+/// A stream position that may know its byte offset
+trait SourceOffset {
+    fn source_offset(&self) -> Option<usize>;
+}
+
+impl SourceOffset for usize {
+    fn source_offset(&self) -> Option<usize> {
+        Some(*self)
+    }
+}
+
+impl SourceOffset for PointerOffset<str> {
+    fn source_offset(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// Run `parser`, also returning the byte range it consumed if the input tracks offsets
+fn spanned<Input, P>(parser: P) -> impl Parser<Input, Output = (P::Output, Option<Range<usize>>)>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+    Input::Position: SourceOffset,
+    P: Parser<Input>,
+{
+    (combine::position(), parser, combine::position()).map(
+        |(start, output, end): (Input::Position, _, Input::Position)| {
+            let span = start
+                .source_offset()
+                .zip(end.source_offset())
+                .map(|(start, end)| start..end);
+            (output, span)
+        },
+    )
+}
+
+fn with_span(mut node: Box<Node>, span: Option<Range<usize>>) -> Box<Node> {
+    node.span = span;
+    node
+}
+
+/// Sibling nodes at nesting `depth`, failing on elements nested deeper than `max_depth`
+fn nodes_<Input>(depth: usize, max_depth: usize) -> impl Parser<Input, Output = Vec<Box<Node>>>
 where
     Input: Stream<Token = char>,
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+    Input::Position: SourceOffset,
 {
-    many::<String, _, _>(space().or(newline()))
+    attempt(many(choice((
+        comment().map(|_| None),
+        attempt(element_at(depth, max_depth)).map(Some),
+        attempt(text()).map(Some),
+    ))))
+    .map(|nodes: Vec<Option<Box<Node>>>| nodes.into_iter().flatten().collect())
 }
 
-fn nodes_<Input>() -> impl Parser<Input, Output = Vec<Box<Node>>>
+fn comment<Input>() -> impl Parser<Input, Output = ()>
 where
     Input: Stream<Token = char>,
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
 {
-    attempt(many(
-        choice((attempt(element()), attempt(text()))).skip(whitespaces()),
-    ))
+    (
+        attempt(string("<!--")),
+        skip_until(attempt(string("-->"))),
+        string("-->"),
+    )
+        .map(|_| ())
 }
 
 fn text<Input>() -> impl Parser<Input, Output = Box<Node>>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+    Input::Position: SourceOffset,
+{
+    spanned(many1(satisfy(|c: char| c != '<')))
+        .map(|(t, span): (String, _)| with_span(Text::new(decode_entities(&t)), span))
+}
+
+/// Elements whose contents are raw text up to their close tag
+fn raw_text_end(tag_name: &str) -> Option<&'static str> {
+    match tag_name {
+        "script" => Some("</script"),
+        "style" => Some("</style"),
+        _ => None,
+    }
+}
+
+fn raw_text<Input>(end: &'static str) -> impl Parser<Input, Output = String>
 where
     Input: Stream<Token = char>,
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
 {
-    many1(satisfy(|c: char| c != '<')).map(|t| Text::new(t))
+    take_until(attempt(string_cmp(end, |l, r| l.eq_ignore_ascii_case(&r))))
 }
 
 fn element<Input>() -> impl Parser<Input, Output = Box<Node>>
 where
     Input: Stream<Token = char>,
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+    Input::Position: SourceOffset,
 {
-    (
-        open_tag().skip(whitespaces()),
-        nodes().skip(whitespaces()),
-        close_tag(),
-    )
-        .and_then(|((open_tag_name, attributes), children, close_tag_name)| {
-            if open_tag_name == close_tag_name {
-                Ok(Element::new(open_tag_name, attributes, children))
-            } else {
+    element_at(1, GRAMMAR_MAX_DEPTH)
+}
+
+fn element_at<Input>(depth: usize, max_depth: usize) -> impl Parser<Input, Output = Box<Node>>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+    Input::Position: SourceOffset,
+{
+    let self_closing_element = spanned(unique_attributes(self_closing_tag_with_duplicates())).map(
+        |((tag_name, attributes), span)| {
+            with_span(Element::new(tag_name, attributes, vec![]), span)
+        },
+    );
+    let normal_element =
+        spanned(unique_attributes(open_tag_with_duplicates())).and_then(move |tag| {
+            if depth > max_depth {
                 Err(<Input::Error as combine::error::ParseError<
                     char,
                     Input::Range,
                     Input::Position,
                 >>::StreamError::message_static_message(
-                    "tag name of open tag and close tag mismatched",
+                    "elements are nested too deeply",
                 ))
+            } else {
+                Ok(tag)
             }
-        })
+        });
+    let normal_element = normal_element.then(move |((open_tag_name, attributes), span)| {
+        if VOID_ELEMENTS.contains(&open_tag_name.as_str()) {
+            return value((open_tag_name, attributes, span))
+                .map(|(tag_name, attributes, span)| {
+                    with_span(Element::new(tag_name, attributes, vec![]), span)
+                })
+                .left();
+        }
+        let children = match raw_text_end(&open_tag_name) {
+            Some(end) => spanned(raw_text(end))
+                .map(|(text, span)| {
+                    if text.is_empty() {
+                        vec![]
+                    } else {
+                        vec![with_span(Text::new(text), span)]
+                    }
+                })
+                .left(),
+            None => nodes(depth + 1, max_depth).right(),
+        };
+        (children, close_tag())
+            .and_then(move |(children, close_tag_name)| {
+                if open_tag_name != close_tag_name {
+                    Err(<Input::Error as combine::error::ParseError<
+                        char,
+                        Input::Range,
+                        Input::Position,
+                    >>::StreamError::message_static_message(
+                        "tag name of open tag and close tag mismatched",
+                    ))
+                } else if implies_end(&open_tag_name, &children, &mut vec![]) {
+                    Err(<Input::Error as combine::error::ParseError<
+                        char,
+                        Input::Range,
+                        Input::Position,
+                    >>::StreamError::message_static_message(
+                        "element is closed implicitly by a descendant",
+                    ))
+                } else {
+                    Ok(with_span(
+                        Element::new(open_tag_name.clone(), attributes.clone(), children),
+                        span.clone(),
+                    ))
+                }
+            })
+            .right()
+    });
+    choice((attempt(self_closing_element), normal_element))
 }
 
-fn attribute<Input>() -> impl Parser<Input, Output = (String, String)>
-where
-    Input: Stream<Token = char>,
-    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
-{
-    (
-        many1::<String, _, _>(letter()),
-        many::<String, _, _>(space().or(newline())),
-        char('='),
-        many::<String, _, _>(space().or(newline())),
-        between(
-            char('"'),
-            char('"'),
-            many1::<String, _, _>(satisfy(|c: char| c != '"')),
-        ),
-    )
-        .map(|v| (v.0, v.4))
+/// Whether opening one of the descendants in `children` implies the end of the
+/// `tag_name` element holding them, with `path` the elements between
+///
+/// The tree builder closes such an element before the descendant, so the grammar
+/// must leave markup like `<p>a<p>b</p></p>` to it.
+fn implies_end<'a>(tag_name: &str, children: &'a [Box<Node>], path: &mut Vec<&'a str>) -> bool {
+    children.iter().any(|child| {
+        let NodeType::Element(element) = &child.node_type else {
+            return false;
+        };
+        let name = element.tag_name.as_str();
+        if implied_end_tags(name).is_some_and(|(closes, scope)| {
+            closes.contains(&tag_name) && !path.iter().any(|n| scope.contains(n))
+        }) {
+            return true;
+        }
+        path.push(name);
+        let implied = implies_end(tag_name, &child.children, path);
+        path.pop();
+        implied
+    })
 }
 
-fn attributes<Input>() -> impl Parser<Input, Output = AttrMap>
+/// A tag read by `tag`, failing if it repeats an attribute so that the tree builder
+/// gets to warn about it
+fn unique_attributes<Input, P>(tag: P) -> impl Parser<Input, Output = (String, AttrMap)>
 where
     Input: Stream<Token = char>,
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+    P: Parser<Input, Output = (String, AttrMap, Vec<String>)>,
 {
-    sep_by::<Vec<(String, String)>, _, _, _>(
-        attribute(),
-        many::<String, _, _>(space().or(newline())),
-    )
-    .map(|attrs: Vec<(String, String)>| attrs.into_iter().collect::<AttrMap>())
+    tag.and_then(|(name, attributes, duplicates)| {
+        if duplicates.is_empty() {
+            Ok((name, attributes))
+        } else {
+            Err(<Input::Error as combine::error::ParseError<
+                char,
+                Input::Range,
+                Input::Position,
+            >>::StreamError::message_static_message(
+                "attribute is repeated"
+            ))
+        }
+    })
 }
 
 parser! {
-    fn nodes[Input]()(Input) -> Vec<Box<Node>>
-    where [Input: Stream<Token = char>]
+    fn nodes[Input](depth: usize, max_depth: usize)(Input) -> Vec<Box<Node>>
+    where [Input: Stream<Token = char>, Input::Position: SourceOffset]
     {
-        nodes_()
+        nodes_(*depth, *max_depth)
     }
 }
 
-fn open_tag<Input>() -> impl Parser<Input, Output = (String, AttrMap)>
+fn doctype<Input>() -> impl Parser<Input, Output = ()>
 where
     Input: Stream<Token = char>,
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
 {
-    let open_tag_name = many1::<String, _, _>(letter());
-    let open_tag_content = (
-        open_tag_name,
-        many::<String, _, _>(space().or(newline())),
-        attributes(),
+    (
+        attempt(string_cmp("<!doctype", |l, r| l.eq_ignore_ascii_case(&r))),
+        skip_until(char('>')),
+        char('>'),
     )
-        .map(|v: (String, _, AttrMap)| (v.0, v.2));
-    between(char('<'), char('>'), open_tag_content)
+        .map(|_| ())
 }
 
-fn close_tag<Input>() -> impl Parser<Input, Output = String>
+fn document<Input>(max_depth: usize) -> impl Parser<Input, Output = Vec<Box<Node>>>
 where
     Input: Stream<Token = char>,
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+    Input::Position: SourceOffset,
 {
-    let close_tag_name = many1::<String, _, _>(letter());
-    let close_tag_content = (char('/'), close_tag_name).map(|v| v.1);
-    between(char('<'), char('>'), close_tag_content)
+    (
+        whitespaces(),
+        optional(doctype()),
+        whitespaces(),
+        nodes(1, max_depth),
+    )
+        .map(|(_, _, _, nodes)| nodes)
+}
+
+/// Elements that belong in the `<head>` when written at the top level or in the body
+const HEAD_ELEMENTS: [&str; 4] = ["link", "meta", "style", "title"];
+
+/// Arrange top-level nodes into an `html` element holding a `head` and a `body`
+///
+/// The attributes and children of explicit `html`, `head` and `body` elements are
+/// merged into the generated ones.
+fn into_document(nodes: impl IntoIterator<Item = Box<Node>>) -> Box<Node> {
+    let head = Element::new("head".to_string(), AttrMap::new(), vec![]);
+    let body = Element::new("body".to_string(), AttrMap::new(), vec![]);
+    let mut html = Element::new("html".to_string(), AttrMap::new(), vec![head, body]);
+    for node in nodes {
+        place_in_document(&mut html, node, false);
+    }
+    html
+}
+
+fn place_in_document(html: &mut Node, mut node: Box<Node>, in_body: bool) {
+    let tag_name = match &node.node_type {
+        NodeType::Element(e) => e.tag_name.clone(),
+        NodeType::Text(_) => {
+            html.children[1].children.push(node);
+            return;
+        }
+    };
+    let target: Option<&mut Node> = match tag_name.as_str() {
+        "html" if !in_body => Some(html),
+        "head" if !in_body => Some(&mut html.children[0]),
+        "body" if !in_body => Some(&mut html.children[1]),
+        _ => None,
+    };
+    let Some(target) = target else {
+        // A scoped style applies to the subtree it is written in, so it stays there
+        let index = if HEAD_ELEMENTS.contains(&tag_name.as_str()) && !node.has_attribute("scoped") {
+            0
+        } else {
+            1
+        };
+        html.children[index].children.push(node);
+        return;
+    };
+
+    if let (NodeType::Element(target), NodeType::Element(element)) =
+        (&mut target.node_type, &mut node.node_type)
+    {
+        for (name, value) in std::mem::take(&mut element.attributes) {
+            if !target.attributes.contains_key(&name) {
+                target.attributes.insert(name, value);
+            }
+        }
+    }
+    if target.span.is_none() {
+        target.span = node.span.clone();
+    }
+    let children = std::mem::take(&mut node.children);
+    if tag_name == "head" {
+        target.children.extend(children);
+    } else {
+        for child in children {
+            place_in_document(html, child, tag_name == "body");
+        }
+    }
 }
 
-/// Parse HTML
+/// Parse an HTML document
+///
+/// The result is always an `html` element holding a `head` and a `body`, however
+/// much of that structure the markup spells out. Malformed markup is recovered from
+/// as `parse_with_warnings` does.
 /// # Example
 /// ```
 /// use tiny_browserbook::html::html::parse;
-/// let node = parse("<p>hello world</p>");
-/// assert_eq!(node.inner_text(), "hello world");
+/// let node = parse("<title>greeting</title><p>hello world</p>");
+/// assert_eq!(node.head().unwrap().inner_text(), "greeting");
+/// assert_eq!(node.body().unwrap().inner_text(), "hello world");
 /// ```
 pub fn parse(raw: &str) -> Box<Node> {
-    let mut nodes = parse_raw(raw);
-    if nodes.len() == 1 {
-        nodes.pop().unwrap()
-    } else {
-        Element::new("html".to_string(), AttrMap::new(), nodes)
+    parse_with_warnings(raw).0
+}
+
+/// Parse HTML into a best-effort DOM, with a warning for every problem recovered from
+///
+/// A close tag that does not match the innermost open element closes open
+/// elements until it does, and the end of the document closes all of them.
+/// # Example
+/// ```
+/// use tiny_browserbook::html::html::parse_with_warnings;
+/// let (node, warnings) = parse_with_warnings("<div><b>hello</div>");
+/// assert_eq!(node.body().unwrap().children[0].inner_text(), "hello");
+/// assert_eq!(warnings.len(), 1);
+/// ```
+pub fn parse_with_warnings(raw: &str) -> (Box<Node>, Vec<ParseWarning>) {
+    parse_with_options(raw, ParseOptions::default())
+}
+
+/// How `parse_with_options` builds the tree
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParseOptions {
+    /// How many elements may be open at once
+    ///
+    /// An element opened inside `max_depth` open elements is closed right away, so that
+    /// what would be its children follow it instead.
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::html::html::{parse_with_options, ParseOptions};
+    /// let options = ParseOptions {
+    ///     max_depth: 2,
+    ///     ..ParseOptions::default()
+    /// };
+    /// let (node, warnings) = parse_with_options("<div><div><p>a", options);
+    /// let inner = &node.body().unwrap().children[0].children[0];
+    /// assert_eq!(inner.children.len(), 2);
+    /// assert!(inner.children[0].children.is_empty());
+    /// assert_eq!(inner.children[1].inner_text(), "");
+    /// assert_eq!(warnings.len(), 3);
+    /// ```
+    pub max_depth: usize,
+    /// Keep the text nodes that hold only whitespace, as written
+    ///
+    /// By default they are dropped, except between inline siblings where they are
+    /// kept as a single space, and inside `pre` and `textarea`.
+    pub preserve_whitespace_nodes: bool,
+    /// How many nodes to keep, in document order, before dropping the rest with a warning
+    pub max_nodes: Option<usize>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            max_depth: DEFAULT_MAX_DEPTH,
+            preserve_whitespace_nodes: false,
+            max_nodes: None,
+        }
+    }
+}
+
+/// Parse HTML as `parse_with_warnings` does, with `options`
+/// # Example
+/// ```
+/// use tiny_browserbook::html::html::{parse_with_options, ParseOptions};
+/// let raw = "<ul>\n  <li>a</li>\n</ul>";
+/// let (node, _) = parse_with_options(raw, ParseOptions::default());
+/// assert_eq!(node.body().unwrap().children[0].children.len(), 1);
+/// let options = ParseOptions {
+///     preserve_whitespace_nodes: true,
+///     ..ParseOptions::default()
+/// };
+/// let (node, _) = parse_with_options(raw, options);
+/// assert_eq!(node.body().unwrap().children[0].children.len(), 3);
+/// ```
+pub fn parse_with_options(raw: &str, options: ParseOptions) -> (Box<Node>, Vec<ParseWarning>) {
+    let (nodes, warnings) = parse_raw_with_options(raw, options);
+    (into_document(nodes), warnings)
+}
+
+/// Parse HTML, failing on malformed markup or unparsed trailing input
+/// # Example
+/// ```
+/// use tiny_browserbook::html::html::try_parse;
+/// assert_eq!(try_parse("<p>hello</p>").unwrap().inner_text(), "hello");
+/// let error = try_parse("<p>hello</div>").unwrap_err();
+/// assert_eq!((error.line, error.column), (1, 9));
+/// assert_eq!(error.message, "unexpected close tag </div>");
+/// ```
+pub fn try_parse(raw: &str) -> Result<Box<Node>, HtmlParseError> {
+    match document(GRAMMAR_MAX_DEPTH)
+        .skip(eof())
+        .parse(spanned_input(raw, 0))
+    {
+        Ok((nodes, _)) => Ok(into_document(normalized(nodes))),
+        Err(_) => strictly_built(raw).map(|root| into_document(normalized(root.children))),
     }
 }
 
+/// Parse a sequence of sibling nodes that need not share a root element
+///
+/// Malformed markup is recovered from as `parse` does.
+/// # Example
+/// ```
+/// use tiny_browserbook::html::html::parse_fragment;
+/// let nodes = parse_fragment("<p>a</p>text<p>b</p>");
+/// assert_eq!(nodes.len(), 3);
+/// assert_eq!(nodes[2].inner_text(), "b");
+/// ```
+pub fn parse_fragment(raw: &str) -> Vec<Box<Node>> {
+    try_parse_fragment(raw).unwrap_or_else(|_| parse_raw(raw))
+}
+
+/// Parse a sequence of sibling nodes, failing on malformed markup or unparsed trailing input
+/// # Example
+/// ```
+/// use tiny_browserbook::html::html::try_parse_fragment;
+/// assert_eq!(try_parse_fragment("<p>a</p><p>b</p>").unwrap().len(), 2);
+/// let error = try_parse_fragment("<p>a</p></div>").unwrap_err();
+/// assert_eq!(error.message, "unexpected close tag </div>");
+/// ```
+pub fn try_parse_fragment(raw: &str) -> Result<Vec<Box<Node>>, HtmlParseError> {
+    (whitespaces(), nodes(1, GRAMMAR_MAX_DEPTH))
+        .map(|(_, nodes)| nodes)
+        .skip(eof())
+        .parse(spanned_input(raw, 0))
+        .map(|(nodes, _)| normalized(nodes).collect())
+        .or_else(|_| strictly_built(raw).map(|root| normalized(root.children).collect()))
+}
+
+/// Build the tree of markup the grammar rejected, failing on the first problem found
+///
+/// The grammar also rejects documents nested too deeply for it to recurse into, which
+/// are fine as long as the tree builder has nothing to warn about.
+fn strictly_built(raw: &str) -> Result<Box<Node>, HtmlParseError> {
+    // The tree builder pinpoints the first problem better than the
+    // backtracking grammar, which reports where the enclosing element started
+    let (root, warnings, _) = build_tree(raw, DEFAULT_MAX_DEPTH, None);
+    let Some(ParseWarning { position, message }) = warnings.into_iter().next() else {
+        return Ok(root);
+    };
+    let before = &raw[..position];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Err(HtmlParseError {
+        message,
+        position,
+        line: before.matches('\n').count() + 1,
+        column: before[line_start..].chars().count() + 1,
+    })
+}
+
 pub fn parse_raw(raw: &str) -> Vec<Box<Node>> {
-    let (nodes, _) = nodes().parse(raw).unwrap();
-    nodes
+    parse_raw_with_warnings(raw).0
 }
 
-#[cfg(test)]
-mod tests {
-    use combine::EasyParser;
+/// A problem in the markup that the parser recovered from
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseWarning {
+    /// Byte offset in the input
+    pub position: usize,
+    pub message: String,
+}
 
-    use super::*;
+pub fn parse_raw_with_warnings(raw: &str) -> (Vec<Box<Node>>, Vec<ParseWarning>) {
+    parse_raw_with_options(raw, ParseOptions::default())
+}
 
-    #[test]
-    fn test_parse_attribut() {
-        assert_eq!(
-            attribute().parse("test=\"foobar\""),
-            Ok((("test".to_string(), "foobar".to_string()), ""))
-        );
+/// Parse HTML as `parse_raw_with_warnings` does, with `options`
+pub fn parse_raw_with_options(
+    raw: &str,
+    options: ParseOptions,
+) -> (Vec<Box<Node>>, Vec<ParseWarning>) {
+    // The grammar builds the whole tree, so with a node limit the tree builder, which
+    // stops adding nodes at the limit, is used instead
+    let parsed = match options.max_nodes {
+        Some(_) => None,
+        None => document(options.max_depth.min(GRAMMAR_MAX_DEPTH))
+            .skip(eof())
+            .parse(spanned_input(raw, 0))
+            .ok(),
+    };
+    let (nodes, mut warnings, mut cut) = match parsed {
+        Some((nodes, _)) => (nodes, vec![], None),
+        None => {
+            let (root, warnings, cut) = build_tree(raw, options.max_depth, options.max_nodes);
+            (root.children, warnings, cut)
+        }
+    };
+    let nodes = if options.preserve_whitespace_nodes {
+        nodes
+    } else {
+        normalized(nodes).collect()
+    };
+    let mut root = Element::new("html".to_string(), AttrMap::new(), nodes);
+    if let Some(max_nodes) = options.max_nodes {
+        // The tree builder doesn't count whitespace, most of which is dropped, so what
+        // is kept of it can still go over the limit
+        if let Some(dropped) = truncate_children(&mut root, &mut { max_nodes }) {
+            cut = Some(dropped.span.map_or(raw.len(), |span| span.start));
+        }
+        if let Some(position) = cut {
+            warnings.push(ParseWarning {
+                position,
+                message: format!("document truncated after {} nodes", max_nodes),
+            });
+        }
     }
+    (root.children, warnings)
+}
 
-    #[test]
-    fn test_parse_attribut_has_space() {
-        assert_eq!(
-            attribute().parse("test = \"foobar\""),
-            Ok((("test".to_string(), "foobar".to_string()), ""))
-        );
+/// Keep `node` and the first of its descendants in document order, `max_nodes` nodes
+/// in all, getting the first node dropped
+///
+/// `node` itself is kept even when `max_nodes` is 0.
+/// # Example
+/// ```
+/// use tiny_browserbook::html::html::{parse_fragment, truncate_tree};
+/// let mut nodes = parse_fragment("<ul><li>a</li><li>b</li></ul>");
+/// let dropped = truncate_tree(&mut nodes[0], 3).unwrap();
+/// assert_eq!(dropped.outer_html(), "<li>b</li>");
+/// assert_eq!(nodes[0].outer_html(), "<ul><li>a</li></ul>");
+/// assert!(truncate_tree(&mut nodes[0], 3).is_none());
+/// ```
+pub fn truncate_tree(node: &mut Node, max_nodes: usize) -> Option<Box<Node>> {
+    truncate_children(node, &mut max_nodes.saturating_sub(1))
+}
+
+/// Keep the first `budget` descendants of `node` in document order, using the budget
+/// up, and get the first node dropped
+fn truncate_children(node: &mut Node, budget: &mut usize) -> Option<Box<Node>> {
+    for i in 0..node.children.len() {
+        if *budget == 0 {
+            return node.children.drain(i..).next();
+        }
+        *budget -= 1;
+        if let Some(dropped) = truncate_children(&mut node.children[i], budget) {
+            node.children.truncate(i + 1);
+            return Some(dropped);
+        }
     }
+    None
+}
 
-    #[test]
-    fn test_parse_attributes() {
-        let mut expected_map = AttrMap::new();
-        expected_map.insert("test".to_string(), "foobar".to_string());
-        expected_map.insert("abc".to_string(), "def".to_string());
-        assert_eq!(
-            attributes().easy_parse("test=\"foobar\" abc=\"def\""),
-            Ok((expected_map, ""))
-        )
+fn is_inline(node: &Node) -> bool {
+    match &node.node_type {
+        NodeType::Element(e) => !BLOCK_ELEMENTS.contains(&e.tag_name.as_str()),
+        NodeType::Text(_) => true,
     }
+}
 
-    #[test]
-    fn test_parse_non_attributes() {
-        assert_eq!(attributes().easy_parse(""), Ok((AttrMap::new(), "")))
+/// `nodes` without whitespace-only text nodes, as `normalize_whitespace` leaves them
+fn normalized(nodes: impl IntoIterator<Item = Box<Node>>) -> impl Iterator<Item = Box<Node>> {
+    let mut holder = Element::new(String::new(), AttrMap::new(), nodes.into_iter().collect());
+    normalize_whitespace(&mut holder);
+    holder.children.into_iter()
+}
+
+/// Drop the whitespace-only text nodes among the descendants of `parent`
+///
+/// Whitespace between two inline siblings separates them, so it is kept as a single
+/// space, and so is the whitespace starting a text that follows an inline sibling.
+/// Whitespace starting any other text is dropped.
+fn normalize_whitespace(parent: &mut Node) {
+    let mut children = std::mem::take(&mut parent.children).into_iter().peekable();
+    while let Some(mut node) = children.next() {
+        let after_inline = parent.children.last().is_some_and(|n| is_inline(n));
+        match &mut node.node_type {
+            NodeType::Text(t) => {
+                let trimmed = t.data.trim_start_matches(|c: char| c.is_ascii_whitespace());
+                let leading = t.data.len() - trimmed.len();
+                if trimmed.is_empty() {
+                    if !after_inline || !children.peek().is_some_and(|n| is_inline(n)) {
+                        continue;
+                    }
+                    t.data = " ".to_string();
+                } else if leading > 0 {
+                    let kept = if after_inline { 1 } else { 0 };
+                    t.data = format!("{}{}", &" "[..kept], trimmed);
+                    node.span = node.span.clone().map(|s| s.start + leading - kept..s.end);
+                }
+            }
+            NodeType::Element(e) => {
+                if !PRESERVED_WHITESPACE.contains(&e.tag_name.as_str()) {
+                    normalize_whitespace(&mut node);
+                }
+            }
+        }
+        parent.children.push(node);
     }
+}
 
-    #[test]
-    fn test_parse_open_tag() {
-        assert_eq!(
-            open_tag().easy_parse("<p>aaaa"),
-            Ok((("p".to_string(), AttrMap::new()), "aaaa"))
-        );
+/// Parse HTML with the tree builder, which keeps its open elements on an explicit stack,
+/// returning the holder of the top-level nodes
+fn build_tree(
+    raw: &str,
+    max_depth: usize,
+    max_nodes: Option<usize>,
+) -> (Box<Node>, Vec<ParseWarning>, Option<usize>) {
+    let mut builder = TreeBuilder::new(max_depth, max_nodes);
+    let mut rest = raw.trim_start();
+    while !rest.is_empty() && builder.cut.is_none() {
+        let position = raw.len() - rest.len();
+        let token = match token().parse(rest) {
+            Ok((token, next)) => {
+                rest = next;
+                token
+            }
+            Err(_) if rest.starts_with("<!--") => {
+                builder.warn(position, "comment is never closed".to_string());
+                break;
+            }
+            Err(_) => {
+                builder.warn(position, "stray '<' is treated as text".to_string());
+                rest = &rest[1..];
+                Token::Text("<".to_string())
+            }
+        };
+        let span = position..raw.len() - rest.len();
+        if let Some(end) = builder.push(token, span) {
+            let start = raw.len() - rest.len();
+            match raw_text(end).parse(rest) {
+                Ok((text, next)) => {
+                    rest = next;
+                    builder.push_raw_text(text, start..raw.len() - rest.len());
+                }
+                Err(_) => {
+                    builder.push_raw_text(rest.to_string(), start..raw.len());
+                    rest = "";
+                }
+            }
+        }
     }
-    #[test]
-    fn test_parse_open_tag_has_an_attribute() {
-        let mut attributes = AttrMap::new();
-        attributes.insert("id".to_string(), "test".to_string());
-        assert_eq!(
-            open_tag().easy_parse("<p id=\"test\">"),
-            Ok((("p".to_string(), attributes), ""))
-        )
+    builder.finish()
+}
+
+enum Token {
+    /// Tag name, attributes and the attribute names repeated in the tag
+    Open(String, AttrMap, Vec<String>),
+    SelfClosing(String, AttrMap, Vec<String>),
+    /// Close tag name and whether it was terminated by `>`
+    Close(String, bool),
+    Text(String),
+    Ignored,
+}
+
+fn token<Input>() -> impl Parser<Input, Output = Token>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    choice((
+        attempt(comment()).map(|_| Token::Ignored),
+        attempt(doctype()).map(|_| Token::Ignored),
+        attempt(lenient_close_tag()).map(|(name, closed)| Token::Close(name, closed)),
+        attempt(self_closing_tag_with_duplicates())
+            .map(|(name, attributes, duplicates)| Token::SelfClosing(name, attributes, duplicates)),
+        attempt(open_tag_with_duplicates())
+            .map(|(name, attributes, duplicates)| Token::Open(name, attributes, duplicates)),
+        many1(satisfy(|c: char| c != '<')).map(Token::Text),
+    ))
+}
+
+/// A close tag which may be missing its `>`, like `</div` followed by another tag
+fn lenient_close_tag<Input>() -> impl Parser<Input, Output = (String, bool)>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        string("</"),
+        tag_name(),
+        skip_many(satisfy(|c: char| c != '<' && c != '>')),
+        optional(char('>')),
+    )
+        .map(|(_, name, _, end)| (name.to_ascii_lowercase(), end.is_some()))
+}
+
+struct OpenElement {
+    name: String,
+    node: Box<Node>,
+}
+
+struct TreeBuilder {
+    open_elements: Vec<OpenElement>,
+    /// Holds the top-level nodes as its children
+    root: Box<Node>,
+    warnings: Vec<ParseWarning>,
+    in_text: bool,
+    max_depth: usize,
+    /// Whether the raw text that follows goes in the last child of the current element,
+    /// a `script` or `style` closed right away for being nested too deep
+    raw_text_in_last: bool,
+    /// How many nodes other than whitespace are added before the rest is dropped
+    max_nodes: Option<usize>,
+    nodes: usize,
+    /// Where the first node over `max_nodes` starts, once nothing more is added
+    cut: Option<usize>,
+}
+
+impl TreeBuilder {
+    fn new(max_depth: usize, max_nodes: Option<usize>) -> Self {
+        TreeBuilder {
+            open_elements: vec![],
+            root: Element::new("html".to_string(), AttrMap::new(), vec![]),
+            warnings: vec![],
+            in_text: false,
+            max_depth,
+            raw_text_in_last: false,
+            max_nodes,
+            nodes: 0,
+            cut: None,
+        }
     }
-    #[test]
-    fn test_parse_open_tag_has_attributes() {
-        let result = open_tag().easy_parse("<p id=\"test\" class=\"sample\">");
-        let mut attributes = AttrMap::new();
-        attributes.insert("id".to_string(), "test".to_string());
-        attributes.insert("class".to_string(), "sample".to_string());
-        assert_eq!(result, Ok((("p".to_string(), attributes), "")));
+
+    /// Count a node starting at `position` that is about to be added, getting whether
+    /// it fits under `max_nodes`
+    ///
+    /// Whitespace isn't counted. Once a node doesn't fit, nothing more is added.
+    fn admit(&mut self, position: usize, whitespace: bool) -> bool {
+        if self.cut.is_some() {
+            return false;
+        }
+        if whitespace {
+            return true;
+        }
+        if self
+            .max_nodes
+            .is_some_and(|max_nodes| self.nodes >= max_nodes)
+        {
+            self.cut = Some(position);
+            return false;
+        }
+        self.nodes += 1;
+        true
     }
 
-    #[test]
-    fn test_parse_open_tag_invalid() {
-        assert!(open_tag().easy_parse("<p id>").is_err());
+    fn warn(&mut self, position: usize, message: String) {
+        self.warnings.push(ParseWarning { position, message });
     }
 
-    #[test]
-    fn test_parse_close_tag() {
-        let result = close_tag().parse("</p>");
-        assert_eq!(result, Ok(("p".to_string(), "")));
+    fn current(&mut self) -> &mut Box<Node> {
+        match self.open_elements.last_mut() {
+            Some(element) => &mut element.node,
+            None => &mut self.root,
+        }
     }
 
-    #[test]
-    fn test_parse_element_is_empty() {
-        assert_eq!(
-            element().parse("<p></p>"),
-            Ok((Element::new("p".to_string(), AttrMap::new(), vec![]), ""))
-        );
+    /// Add a token read from `span` to the tree, returning the end of the raw text that
+    /// follows it if any
+    fn push(&mut self, token: Token, span: Range<usize>) -> Option<&'static str> {
+        let in_text = std::mem::replace(&mut self.in_text, false);
+        match token {
+            Token::Open(..) | Token::SelfClosing(..) if !self.admit(span.start, false) => {}
+            Token::Open(name, attributes, duplicates) => {
+                self.warn_duplicates(&name, duplicates, span.start);
+                let node = with_span(
+                    Element::new(name.clone(), attributes, vec![]),
+                    Some(span.clone()),
+                );
+                if VOID_ELEMENTS.contains(&name.as_str()) {
+                    self.current().children.push(node);
+                    return None;
+                }
+                self.close_implied(&name, span.start);
+                let end = raw_text_end(&name);
+                if self.open_elements.len() >= self.max_depth {
+                    self.warn(
+                        span.start,
+                        format!(
+                            "<{}> is nested deeper than {} elements and is closed immediately",
+                            name, self.max_depth
+                        ),
+                    );
+                    self.current().children.push(node);
+                    self.raw_text_in_last = end.is_some();
+                    return end;
+                }
+                self.open_elements.push(OpenElement { node, name });
+                return end;
+            }
+            Token::SelfClosing(name, attributes, duplicates) => {
+                self.warn_duplicates(&name, duplicates, span.start);
+                let node = with_span(Element::new(name, attributes, vec![]), Some(span));
+                self.current().children.push(node);
+            }
+            Token::Close(name, closed) => {
+                if !closed {
+                    self.warn(span.start, format!("close tag </{}> is missing '>'", name));
+                }
+                self.close(&name, span.start);
+            }
+            Token::Text(text) => self.push_text(&text, span, in_text),
+            Token::Ignored => {}
+        }
+        None
     }
 
-    #[test]
-    fn test_parse_element_has_value() {
-        assert_eq!(
-            element().parse("<p>hello world</p>"),
-            Ok((
-                Element::new(
-                    "p".to_string(),
-                    AttrMap::new(),
-                    vec![Text::new("hello world".to_string())]
-                ),
-                ""
-            ))
-        );
+    fn warn_duplicates(&mut self, tag_name: &str, duplicates: Vec<String>, position: usize) {
+        for name in duplicates {
+            self.warn(
+                position,
+                format!("repeated attribute {} on <{}> is ignored", name, tag_name),
+            );
+        }
     }
 
-    #[test]
-    fn test_parse_text() {
-        assert_eq!(
-            text().parse("hello world"),
-            Ok((Text::new("hello world".to_string()), ""))
-        );
+    fn push_text(&mut self, text: &str, span: Range<usize>, in_text: bool) {
+        if in_text {
+            let last = self.current().children.last_mut();
+            if let Some(node) = last {
+                if let NodeType::Text(t) = &mut node.node_type {
+                    t.data.push_str(&decode_entities(text));
+                    node.span = node.span.clone().map(|s| s.start..span.end);
+                    self.in_text = true;
+                    return;
+                }
+            }
+        }
+        if !text.is_empty() && self.admit(span.start, text.trim().is_empty()) {
+            let node = with_span(Text::new(decode_entities(text)), Some(span));
+            self.current().children.push(node);
+            self.in_text = true;
+        }
     }
 
-    #[test]
-    fn test_parse_text_with_tag() {
-        assert_eq!(
-            text().parse("hello world<"),
-            Ok((Text::new("hello world".to_string()), "<"))
+    fn push_raw_text(&mut self, text: String, span: Range<usize>) {
+        let in_last = std::mem::replace(&mut self.raw_text_in_last, false);
+        if !text.is_empty() && self.admit(span.start, text.trim().is_empty()) {
+            let node = with_span(Text::new(text), Some(span));
+            let parent = self.current();
+            match parent.children.last_mut() {
+                Some(element) if in_last => element.children.push(node),
+                _ => parent.children.push(node),
+            }
+        }
+    }
+
+    fn close(&mut self, name: &str, position: usize) {
+        let Some(index) = self.open_elements.iter().rposition(|e| e.name == name) else {
+            self.warn(position, format!("unexpected close tag </{}>", name));
+            return;
+        };
+        self.close_from(index, &format!("</{}>", name), position);
+    }
+
+    /// Close the open element whose end is implied by opening `name`, if any
+    fn close_implied(&mut self, name: &str, position: usize) {
+        let Some((closes, scope)) = implied_end_tags(name) else {
+            return;
+        };
+        let found = self
+            .open_elements
+            .iter()
+            .rev()
+            .map(|e| e.name.as_str())
+            .take_while(|name| !scope.contains(name))
+            .position(|name| closes.contains(&name));
+        if let Some(from_end) = found {
+            let index = self.open_elements.len() - 1 - from_end;
+            self.close_from(index, &format!("<{}>", name), position);
+        }
+    }
+
+    /// Close the open element at `index`, warning about each element above it that
+    /// `closer` closes implicitly unless its end tag is optional
+    fn close_from(&mut self, index: usize, closer: &str, position: usize) {
+        while self.open_elements.len() > index + 1 {
+            let implicit = self.pop();
+            if !OPTIONAL_END_TAGS.contains(&implicit.as_str()) {
+                self.warn(
+                    position,
+                    format!("<{}> is closed implicitly by {}", implicit, closer),
+                );
+            }
+        }
+        self.pop();
+    }
+
+    fn pop(&mut self) -> String {
+        let element = self.open_elements.pop().unwrap();
+        self.current().children.push(element.node);
+        element.name
+    }
+
+    /// Close every open element, returning the holder of the top-level nodes and
+    /// where the nodes over `max_nodes` were cut off
+    fn finish(mut self) -> (Box<Node>, Vec<ParseWarning>, Option<usize>) {
+        while let Some(span) = self.open_elements.last().map(|e| e.node.source_span()) {
+            let name = self.pop();
+            // The end of the input closes these as any other end tag would, and the
+            // elements left open by a cut may be closed in what was dropped
+            if OPTIONAL_END_TAGS.contains(&name.as_str()) || self.cut.is_some() {
+                continue;
+            }
+            let position = span.map_or(0, |s| s.start);
+            self.warn(position, format!("<{}> is never closed", name));
+        }
+        (self.root, self.warnings, self.cut)
+    }
+}
+
+/// Collects a document that arrives in chunks and parses it once it is complete
+///
+/// `feed` only buffers its chunk, and `finish` parses the whole document as `try_parse`
+/// does, so no node is built before then. `parse_streaming` is the way to get the
+/// top-level nodes as soon as their close tags arrive.
+/// # Example
+/// ```
+/// use tiny_browserbook::html::html::HtmlParser;
+/// let mut parser = HtmlParser::new();
+/// parser.feed("<p cla");
+/// parser.feed("ss=\"a\">hel");
+/// parser.feed("lo</p>");
+/// assert_eq!(parser.finish().unwrap().inner_text(), "hello");
+/// ```
+#[derive(Debug, Default)]
+pub struct HtmlParser {
+    buffer: String,
+}
+
+impl HtmlParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append the next chunk of the document
+    pub fn feed(&mut self, chunk: &str) {
+        self.buffer.push_str(chunk);
+    }
+
+    /// Parse everything fed so far as a complete document
+    pub fn finish(self) -> Result<Box<Node>, HtmlParseError> {
+        try_parse(&self.buffer)
+    }
+}
+
+/// Parse HTML from `reader`, calling `on_node` with every top-level node as soon as it is complete
+///
+/// A leading `<html>` or `<body>` tag is skipped so that the body's children are streamed. Input
+/// that does not parse by the end of the stream is recovered as `parse_raw` does.
+/// # Example
+/// ```
+/// use tiny_browserbook::html::html::parse_streaming;
+/// let mut texts = vec![];
+/// parse_streaming("<body><p>a</p><p>b</p></body>".as_bytes(), |node| {
+///     texts.push(node.inner_text())
+/// })
+/// .unwrap();
+/// assert_eq!(texts, vec!["a", "b"]);
+/// ```
+pub fn parse_streaming<R, F>(mut reader: R, mut on_node: F) -> std::io::Result<()>
+where
+    R: Read,
+    F: FnMut(Box<Node>),
+{
+    let mut undecoded = vec![];
+    let mut pending = String::new();
+    // Bytes of the stream drained from `pending`
+    let mut base = 0;
+    let mut started = false;
+    let mut retry_at = 0;
+    let mut chunk = [0; 8192];
+    loop {
+        let n = reader.read(&mut chunk)?;
+        if n == 0 {
+            break;
+        }
+        undecoded.extend_from_slice(&chunk[..n]);
+        let valid = match std::str::from_utf8(&undecoded) {
+            Ok(s) => s.len(),
+            Err(e) => e.valid_up_to(),
+        };
+        pending.push_str(std::str::from_utf8(&undecoded[..valid]).unwrap());
+        undecoded.drain(..valid);
+
+        // A node which is still incomplete is retried only once the input has doubled,
+        // so large nested elements don't get parsed over and over
+        if pending.len() < retry_at {
+            continue;
+        }
+        let consumed = stream_nodes(&pending, base, &mut started, &mut on_node);
+        pending.drain(..consumed);
+        base += consumed;
+        retry_at = pending.len() * 2;
+    }
+    pending.push_str(&String::from_utf8_lossy(&undecoded));
+
+    let rest = match prologue().parse(pending.as_str()) {
+        Ok((_, rest)) if !started => rest,
+        _ => pending.as_str(),
+    };
+    let offset = base + pending.len() - rest.len();
+    for mut node in parse_raw(rest) {
+        shift_spans(&mut node, offset);
+        on_node(node);
+    }
+    Ok(())
+}
+
+fn shift_spans(node: &mut Node, offset: usize) {
+    node.span = node.span.clone().map(|s| s.start + offset..s.end + offset);
+    for child in node.children.iter_mut() {
+        shift_spans(child, offset);
+    }
+}
+
+/// Pass the complete top-level nodes at the start of `pending`, which is found at byte
+/// `base` of the stream, to `on_node`, returning the number of bytes consumed
+fn stream_nodes<F>(pending: &str, base: usize, started: &mut bool, on_node: &mut F) -> usize
+where
+    F: FnMut(Box<Node>),
+{
+    let mut offset = 0;
+    if !*started {
+        // A prologue split by the end of the input, inside the doctype say, needs more of it
+        let Ok((_, rest)) = prologue().parse(pending) else {
+            return 0;
+        };
+        // Wait until a tag being read, or a doctype, could no longer turn out to be
+        // part of the prologue
+        if rest.is_empty() || (rest.starts_with('<') && !rest.contains('>')) {
+            return 0;
+        }
+        *started = true;
+        offset = pending.len() - rest.len();
+    }
+    loop {
+        let input = &pending[offset..];
+        let input = input.trim_start();
+        offset = pending.len() - input.len();
+        // Close tags of the body and html are left for the end of the stream
+        if input.is_empty() || input.starts_with("</") {
+            return offset;
+        }
+        match top_level_node().parse(spanned_input(input, base + offset)) {
+            // A node reaching the end of the input may still be incomplete
+            Ok((node, rest)) if !rest.input.is_empty() => {
+                offset = pending.len() - rest.input.len();
+                for node in normalized(node) {
+                    on_node(node);
+                }
+            }
+            _ => return offset,
+        }
+    }
+}
+
+fn top_level_node<Input>() -> impl Parser<Input, Output = Option<Box<Node>>>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+    Input::Position: SourceOffset,
+{
+    choice((
+        comment().map(|_| None),
+        attempt(element()).map(Some),
+        attempt(text()).map(Some),
+    ))
+}
+
+/// Whitespace, doctype and the opening `<html>` and `<body>` tags of a document
+fn prologue<Input>() -> impl Parser<Input, Output = ()>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let wrapper = |name: &'static str| {
+        attempt(open_tag().and_then(move |(tag_name, _)| {
+            if tag_name == name {
+                Ok(())
+            } else {
+                Err(<Input::Error as combine::error::ParseError<
+                    char,
+                    Input::Range,
+                    Input::Position,
+                >>::StreamError::message_static_message(
+                    "not a document wrapper tag",
+                ))
+            }
+        }))
+    };
+    (
+        whitespaces(),
+        optional(attempt(doctype())),
+        whitespaces(),
+        optional(wrapper("html")),
+        whitespaces(),
+        optional(wrapper("body")),
+    )
+        .map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(
+        head: impl IntoIterator<Item = Box<Node>>,
+        body: impl IntoIterator<Item = Box<Node>>,
+    ) -> Box<Node> {
+        Element::new(
+            "html".to_string(),
+            AttrMap::new(),
+            vec![
+                Element::new(
+                    "head".to_string(),
+                    AttrMap::new(),
+                    head.into_iter().collect(),
+                ),
+                Element::new(
+                    "body".to_string(),
+                    AttrMap::new(),
+                    body.into_iter().collect(),
+                ),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_parse_element_has_boolean_attribute() {
+        let mut attributes = AttrMap::new();
+        attributes.insert("defer".to_string(), "".to_string());
+        attributes.insert("src".to_string(), "x".to_string());
+        assert_eq!(
+            element().parse("<script defer src=\"x\"></script>"),
+            Ok((Element::new("script".to_string(), attributes, vec![]), ""))
+        );
+    }
+
+    #[test]
+    fn test_parse_pretty_printed_document() {
+        let raw = "<body>\r\n\t<div\r\n\t\tclass=\"a\"\r\n\t>\r\n\t\t<p>hello</p>\r\n\t</div\r\n\t>\r\n</body>\r\n";
+        let node = try_parse(raw).unwrap();
+        assert_eq!(node.body().unwrap().children[0].inner_text(), "hello");
+    }
+
+    #[test]
+    fn test_parse_element_self_closing() {
+        let mut attributes = AttrMap::new();
+        attributes.insert("src".to_string(), "x".to_string());
+        assert_eq!(
+            element().parse("<img src=\"x\"/>"),
+            Ok((Element::new("img".to_string(), attributes, vec![]), ""))
+        );
+    }
+
+    #[test]
+    fn test_parse_self_closing_nested() {
+        assert_eq!(
+            element().parse("<p>line one<br/>line two</p>"),
+            Ok((
+                Element::new(
+                    "p".to_string(),
+                    AttrMap::new(),
+                    vec![
+                        Text::new("line one".to_string()),
+                        Element::new("br".to_string(), AttrMap::new(), vec![]),
+                        Text::new("line two".to_string()),
+                    ]
+                ),
+                ""
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_mixed_self_closing_and_normal() {
+        let mut attributes = AttrMap::new();
+        attributes.insert("type".to_string(), "text".to_string());
+        assert_eq!(
+            parse("<body><div><input type=\"text\" /></div><hr/><p>end</p></body>"),
+            document(
+                vec![],
+                vec![
+                    Element::new(
+                        "div".to_string(),
+                        AttrMap::new(),
+                        vec![Element::new("input".to_string(), attributes, vec![])]
+                    ),
+                    Element::new("hr".to_string(), AttrMap::new(), vec![]),
+                    Element::new(
+                        "p".to_string(),
+                        AttrMap::new(),
+                        vec![Text::new("end".to_string())]
+                    ),
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_void_element() {
+        assert_eq!(
+            element().parse("<hr>"),
+            Ok((Element::new("hr".to_string(), AttrMap::new(), vec![]), ""))
+        );
+    }
+
+    #[test]
+    fn test_parse_void_element_followed_by_text() {
+        assert_eq!(
+            element().parse("<p>line one<br>line two</p>"),
+            Ok((
+                Element::new(
+                    "p".to_string(),
+                    AttrMap::new(),
+                    vec![
+                        Text::new("line one".to_string()),
+                        Element::new("br".to_string(), AttrMap::new(), vec![]),
+                        Text::new("line two".to_string()),
+                    ]
+                ),
+                ""
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_void_element_with_attributes() {
+        let mut attributes = AttrMap::new();
+        attributes.insert("charset".to_string(), "utf-8".to_string());
+        assert_eq!(
+            parse("<body><meta charset=\"utf-8\"><p>hello</p></body>"),
+            document(
+                vec![Element::new("meta".to_string(), attributes, vec![])],
+                vec![Element::new(
+                    "p".to_string(),
+                    AttrMap::new(),
+                    vec![Text::new("hello".to_string())]
+                )]
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_comment() {
+        assert_eq!(
+            comment().parse("<!-- a < b > c\n -->rest"),
+            Ok(((), "rest"))
+        );
+    }
+
+    #[test]
+    fn test_parse_comment_between_elements() {
+        assert_eq!(
+            parse("<body><!-- navigation --><p>hi</p></body>"),
+            document(
+                vec![],
+                vec![Element::new(
+                    "p".to_string(),
+                    AttrMap::new(),
+                    vec![Text::new("hi".to_string())]
+                )]
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_comment_between_text() {
+        let node = parse("<p>hello <!-- <b>not bold</b> -->world</p>");
+        assert_eq!(
+            node,
+            document(
+                vec![],
+                vec![Element::new(
+                    "p".to_string(),
+                    AttrMap::new(),
+                    vec![
+                        Text::new("hello ".to_string()),
+                        Text::new("world".to_string())
+                    ]
+                )]
+            )
+        );
+        assert_eq!(node.inner_text(), "hello world");
+    }
+
+    #[test]
+    fn test_parse_unterminated_comment() {
+        assert!(nodes(1, GRAMMAR_MAX_DEPTH)
+            .parse("<p>hi</p><!-- never closed")
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_doctype() {
+        assert_eq!(doctype().parse("<!DOCTYPE html>"), Ok(((), "")));
+        assert_eq!(doctype().parse("<!doctype html>"), Ok(((), "")));
+        assert_eq!(
+            doctype().parse(
+                r#"<!DOCTYPE HTML PUBLIC "-//W3C//DTD HTML 4.01//EN" "http://www.w3.org/TR/html4/strict.dtd">"#
+            ),
+            Ok(((), ""))
+        );
+    }
+
+    #[test]
+    fn test_parse_document_with_doctype() {
+        assert_eq!(
+            parse("<!doctype html><body><p>x</p></body>"),
+            parse("<body><p>x</p></body>")
+        );
+        assert_eq!(
+            parse("\n<!DOCTYPE html>\n<body><p>x</p></body>"),
+            parse("<body><p>x</p></body>")
+        );
+    }
+
+    #[test]
+    fn test_try_parse() {
+        assert_eq!(
+            try_parse("<p>hello</p>"),
+            Ok(document(
+                vec![],
+                vec![Element::new(
+                    "p".to_string(),
+                    AttrMap::new(),
+                    vec![Text::new("hello".to_string())]
+                )]
+            ))
+        );
+    }
+
+    #[test]
+    fn test_try_parse_mismatched() {
+        let raw = "<body>\n  <p>hello</p>\n  <p>world</div>\n</body>";
+        let error = try_parse(raw).unwrap_err();
+        assert_eq!((error.line, error.column), (3, 11));
+        assert_eq!(&raw[error.position..error.position + 6], "</div>");
+        assert_eq!(error.message, "unexpected close tag </div>");
+    }
+
+    #[test]
+    fn test_parse_fragment() {
+        assert_eq!(parse_fragment(""), vec![]);
+        assert_eq!(
+            parse_fragment("just text"),
+            vec![Text::new("just text".to_string())]
+        );
+        assert_eq!(
+            parse_fragment("<p>a</p>and <b>b</b>"),
+            vec![
+                Element::new(
+                    "p".to_string(),
+                    AttrMap::new(),
+                    vec![Text::new("a".to_string())]
+                ),
+                Text::new("and ".to_string()),
+                Element::new(
+                    "b".to_string(),
+                    AttrMap::new(),
+                    vec![Text::new("b".to_string())]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_fragment_trailing_input() {
+        let error = try_parse_fragment("<p>a</p>\n<p>b</div>").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 5));
+        assert_eq!(error.message, "unexpected close tag </div>");
+
+        let nodes = parse_fragment("<p>a</p>\n<p>b</div>");
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[1].inner_text(), "b");
+    }
+
+    #[test]
+    fn test_parse_bare_text_document() {
+        assert_eq!(
+            parse("hello"),
+            document(vec![], vec![Text::new("hello".to_string())])
+        );
+        assert_eq!(parse(""), document(vec![], vec![]));
+    }
+
+    #[test]
+    fn test_parse_document_with_body() {
+        let raw = r#"<!DOCTYPE html><html lang="en"><head><title>t</title></head><body class="page"><p>a</p></body></html>"#;
+        let node = parse(raw);
+        let mut html_attributes = AttrMap::new();
+        html_attributes.insert("lang".to_string(), "en".to_string());
+        let mut body_attributes = AttrMap::new();
+        body_attributes.insert("class".to_string(), "page".to_string());
+        assert_eq!(
+            node,
+            Element::new(
+                "html".to_string(),
+                html_attributes,
+                vec![
+                    Element::new(
+                        "head".to_string(),
+                        AttrMap::new(),
+                        vec![Element::new(
+                            "title".to_string(),
+                            AttrMap::new(),
+                            vec![Text::new("t".to_string())]
+                        )]
+                    ),
+                    Element::new(
+                        "body".to_string(),
+                        body_attributes,
+                        vec![Element::new(
+                            "p".to_string(),
+                            AttrMap::new(),
+                            vec![Text::new("a".to_string())]
+                        )]
+                    ),
+                ]
+            )
+        );
+        assert_eq!(source(raw, node.body().unwrap()), r#"<body class="page">"#);
+    }
+
+    #[test]
+    fn test_parse_moves_head_elements() {
+        let style = Element::new(
+            "style".to_string(),
+            AttrMap::new(),
+            vec![Text::new("p { color: red; }".to_string())],
+        );
+        let p = || {
+            Element::new(
+                "p".to_string(),
+                AttrMap::new(),
+                vec![Text::new("a".to_string())],
+            )
+        };
+        assert_eq!(
+            parse("<p>a</p><style>p { color: red; }</style>"),
+            document(vec![style], vec![p()])
+        );
+
+        let node = parse("<body><p>a</p><link rel=\"x\"><div><style>s</style></div></body>");
+        assert_eq!(node.head().unwrap().children.len(), 1);
+        let body = node.body().unwrap();
+        assert_eq!(body.children.len(), 2);
+        assert_eq!(body.children[1].children[0].inner_text(), "s");
+    }
+
+    #[test]
+    fn test_parse_element_is_empty() {
+        assert_eq!(
+            element().parse("<p></p>"),
+            Ok((Element::new("p".to_string(), AttrMap::new(), vec![]), ""))
+        );
+    }
+
+    #[test]
+    fn test_parse_element_has_value() {
+        assert_eq!(
+            element().parse("<p>hello world</p>"),
+            Ok((
+                Element::new(
+                    "p".to_string(),
+                    AttrMap::new(),
+                    vec![Text::new("hello world".to_string())]
+                ),
+                ""
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_element_mixed_case() {
+        for raw in ["<DIV>hello</div>", "<div>hello</DIV>", "<Div>hello</dIv>"] {
+            assert_eq!(
+                element().parse(raw),
+                Ok((
+                    Element::new(
+                        "div".to_string(),
+                        AttrMap::new(),
+                        vec![Text::new("hello".to_string())]
+                    ),
+                    ""
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_element_mixed_case_mismatched() {
+        assert!(element().parse("<DIV>hello</p>").is_err());
+    }
+
+    #[test]
+    fn test_parse_void_element_upper_case() {
+        assert_eq!(
+            element().parse("<BR>"),
+            Ok((Element::new("br".to_string(), AttrMap::new(), vec![]), ""))
+        );
+    }
+
+    #[test]
+    fn test_parse_script_as_raw_text() {
+        assert_eq!(
+            element().parse("<script>if (a < b) { x = \"<p>hi</p>\"; }</script>"),
+            Ok((
+                Element::new(
+                    "script".to_string(),
+                    AttrMap::new(),
+                    vec![Text::new("if (a < b) { x = \"<p>hi</p>\"; }".to_string())]
+                ),
+                ""
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_script_with_split_close_tag() {
+        let source = "document.write(\"</scr\" + \"ipt>\"); a &amp; b";
+        let raw = format!("<SCRIPT>{}</SCRIPT>", source);
+        assert_eq!(
+            element().parse(raw.as_str()),
+            Ok((
+                Element::new(
+                    "script".to_string(),
+                    AttrMap::new(),
+                    vec![Text::new(source.to_string())]
+                ),
+                ""
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_style_as_raw_text() {
+        assert_eq!(
+            element().parse("<style>p > a { display: block; }</style>"),
+            Ok((
+                Element::new(
+                    "style".to_string(),
+                    AttrMap::new(),
+                    vec![Text::new("p > a { display: block; }".to_string())]
+                ),
+                ""
+            ))
+        );
+        assert_eq!(
+            element().parse("<style></style>"),
+            Ok((
+                Element::new("style".to_string(), AttrMap::new(), vec![]),
+                ""
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_text() {
+        assert_eq!(
+            text().parse("hello world"),
+            Ok((Text::new("hello world".to_string()), ""))
+        );
+    }
+
+    #[test]
+    fn test_parse_text_with_entities() {
+        assert_eq!(
+            text().parse("Fish &amp; Chips &lt;3"),
+            Ok((Text::new("Fish & Chips <3".to_string()), ""))
+        );
+    }
+
+    #[test]
+    fn test_parse_text_with_tag() {
+        assert_eq!(
+            text().parse("hello world<"),
+            Ok((Text::new("hello world".to_string()), "<"))
+        );
+    }
+
+    fn messages(warnings: &[ParseWarning]) -> Vec<&str> {
+        warnings.iter().map(|w| w.message.as_str()).collect()
+    }
+
+    #[test]
+    fn test_parse_with_warnings_valid() {
+        let (node, warnings) = parse_with_warnings("<p>hello <b>world</b></p>");
+        assert_eq!(node, parse("<p>hello <b>world</b></p>"));
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_parse_with_warnings_misnested() {
+        let (node, warnings) = parse_with_warnings("<b><i>x</b></i>");
+        assert_eq!(
+            node,
+            document(
+                vec![],
+                vec![Element::new(
+                    "b".to_string(),
+                    AttrMap::new(),
+                    vec![Element::new(
+                        "i".to_string(),
+                        AttrMap::new(),
+                        vec![Text::new("x".to_string())]
+                    )]
+                )]
+            )
+        );
+        assert_eq!(
+            messages(&warnings),
+            vec![
+                "<i> is closed implicitly by </b>",
+                "unexpected close tag </i>"
+            ]
+        );
+        assert_eq!(warnings[1].position, 11);
+    }
+
+    #[test]
+    fn test_parse_with_warnings_unclosed_at_eof() {
+        let (node, warnings) = parse_with_warnings("<div><span>hello");
+        assert_eq!(
+            node,
+            document(
+                vec![],
+                vec![Element::new(
+                    "div".to_string(),
+                    AttrMap::new(),
+                    vec![Element::new(
+                        "span".to_string(),
+                        AttrMap::new(),
+                        vec![Text::new("hello".to_string())]
+                    )]
+                )]
+            )
+        );
+        assert_eq!(
+            messages(&warnings),
+            vec!["<span> is never closed", "<div> is never closed"]
+        );
+        assert_eq!(warnings[0].position, 5);
+    }
+
+    #[test]
+    fn test_parse_with_warnings_close_tag_missing_bracket() {
+        let (node, warnings) = parse_with_warnings(
+            r#"<body>
+    <div id="result">
+        <p>not loaded</p>
+    </div
+    <script>a < b</script>
+</body>"#,
+        );
+        let mut attributes = AttrMap::new();
+        attributes.insert("id".to_string(), "result".to_string());
+        assert_eq!(
+            node,
+            document(
+                vec![],
+                vec![
+                    Element::new(
+                        "div".to_string(),
+                        attributes,
+                        vec![Element::new(
+                            "p".to_string(),
+                            AttrMap::new(),
+                            vec![Text::new("not loaded".to_string())]
+                        )]
+                    ),
+                    Element::new(
+                        "script".to_string(),
+                        AttrMap::new(),
+                        vec![Text::new("a < b".to_string())]
+                    ),
+                ]
+            )
+        );
+        assert_eq!(messages(&warnings), vec!["close tag </div> is missing '>'"]);
+    }
+
+    #[test]
+    fn test_parse_with_warnings_repeated_attribute() {
+        let raw = r#"<p id="a" class="x" id="b">hi</p><br id="c" id="d"/>"#;
+        let (node, warnings) = parse_with_warnings(raw);
+        let body = node.body().unwrap();
+        let ids: Vec<_> = body
+            .children
+            .iter()
+            .map(|child| match &child.node_type {
+                NodeType::Element(e) => e.attributes["id"].clone(),
+                NodeType::Text(_) => unreachable!(),
+            })
+            .collect();
+        assert_eq!(ids, vec!["a", "c"]);
+        assert_eq!(
+            messages(&warnings),
+            vec![
+                "repeated attribute id on <p> is ignored",
+                "repeated attribute id on <br> is ignored"
+            ]
+        );
+        assert_eq!(warnings[1].position, raw.find("<br").unwrap());
+        assert_eq!(
+            try_parse(raw).unwrap_err().message,
+            "repeated attribute id on <p> is ignored"
+        );
+    }
+
+    #[test]
+    fn test_parse_with_warnings_stray_lt() {
+        let (node, warnings) = parse_with_warnings("<p>a < b &amp; c</p>");
+        assert_eq!(node.inner_text(), "a < b & c");
+        assert_eq!(messages(&warnings), vec!["stray '<' is treated as text"]);
+    }
+
+    #[test]
+    fn test_parse_with_warnings_unclosed_comment() {
+        let (node, warnings) = parse_with_warnings("<p>hi</p><!-- never closed");
+        assert_eq!(
+            node,
+            document(
+                vec![],
+                vec![Element::new(
+                    "p".to_string(),
+                    AttrMap::new(),
+                    vec![Text::new("hi".to_string())]
+                )]
+            )
+        );
+        assert_eq!(messages(&warnings), vec!["comment is never closed"]);
+    }
+
+    #[test]
+    fn test_parse_with_warnings_unclosed_script() {
+        let (node, warnings) = parse_with_warnings("<script>let a = 1 < 2;");
+        assert_eq!(
+            node,
+            document(
+                vec![],
+                vec![Element::new(
+                    "script".to_string(),
+                    AttrMap::new(),
+                    vec![Text::new("let a = 1 < 2;".to_string())]
+                )]
+            )
+        );
+        assert_eq!(messages(&warnings), vec!["<script> is never closed"]);
+    }
+
+    #[test]
+    fn test_parse_with_options_max_nodes() {
+        let raw = (0..1000)
+            .map(|i| format!("<p>{}</p>", i))
+            .collect::<String>();
+        let (node, warnings) = parse_with_options(&raw, options_with_max_nodes(5));
+        assert_eq!(node.body().unwrap().inner_text(), "01");
+        assert_eq!(
+            warnings,
+            vec![ParseWarning {
+                position: 19,
+                message: "document truncated after 5 nodes".to_string()
+            }]
+        );
+        let (node, warnings) = parse_with_options("<p>0</p>", options_with_max_nodes(2));
+        assert_eq!(node.body().unwrap().inner_text(), "0");
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_parse_with_options_max_nodes_stops_building() {
+        let raw = "<div><p>0</p> <p>1</p></span><p>2</p></div><script>let a = 1;";
+        let (nodes, warnings) = parse_raw_with_options(raw, options_with_max_nodes(3));
+        assert_eq!(
+            nodes
+                .iter()
+                .map(|node| node.outer_html())
+                .collect::<String>(),
+            "<div><p>0</p></div>"
+        );
+        assert_eq!(
+            warnings,
+            vec![ParseWarning {
+                position: 14,
+                message: "document truncated after 3 nodes".to_string()
+            }]
+        );
+
+        let options = ParseOptions {
+            preserve_whitespace_nodes: true,
+            ..options_with_max_nodes(4)
+        };
+        let (nodes, warnings) = parse_raw_with_options(raw, options);
+        assert_eq!(
+            nodes
+                .iter()
+                .map(|node| node.outer_html())
+                .collect::<String>(),
+            "<div><p>0</p> </div>"
+        );
+        assert_eq!(
+            messages(&warnings),
+            vec!["document truncated after 4 nodes"]
+        );
+    }
+
+    fn options_with_max_nodes(max_nodes: usize) -> ParseOptions {
+        ParseOptions {
+            max_nodes: Some(max_nodes),
+            ..ParseOptions::default()
+        }
+    }
+
+    fn el(tag_name: &str, children: impl IntoIterator<Item = Box<Node>>) -> Box<Node> {
+        Element::new(
+            tag_name.to_string(),
+            AttrMap::new(),
+            children.into_iter().collect(),
+        )
+    }
+
+    fn txt(data: &str) -> Box<Node> {
+        Text::new(data.to_string())
+    }
+
+    #[test]
+    fn test_optional_end_tags_list_items() {
+        let raw = "<ul><li>one<li>two</ul>";
+        let (node, warnings) = parse_with_warnings(raw);
+        assert_eq!(
+            node,
+            document(
+                vec![],
+                vec![el(
+                    "ul",
+                    vec![el("li", vec![txt("one")]), el("li", vec![txt("two")])]
+                )]
+            )
+        );
+        assert!(warnings.is_empty());
+        assert_eq!(try_parse(raw).unwrap(), node);
+    }
+
+    #[test]
+    fn test_optional_end_tags_paragraphs() {
+        let (node, warnings) = parse_with_warnings("<p>first<p>second");
+        assert_eq!(
+            node,
+            document(
+                vec![],
+                vec![el("p", vec![txt("first")]), el("p", vec![txt("second")])]
+            )
+        );
+        assert!(warnings.is_empty());
+        assert_eq!(try_parse("<p>first<p>second"), Ok(node));
+    }
+
+    #[test]
+    fn test_optional_end_tags_paragraphs_redundant_close_tag() {
+        let raw = "<p>a<p>b</p></p>";
+        let (node, warnings) = parse_with_warnings(raw);
+        assert_eq!(
+            node,
+            document(
+                vec![],
+                vec![el("p", vec![txt("a")]), el("p", vec![txt("b")])]
+            )
+        );
+        assert_eq!(messages(&warnings), vec!["unexpected close tag </p>"]);
+        assert_eq!(
+            try_parse(raw).unwrap_err().message,
+            "unexpected close tag </p>"
+        );
+        assert!(element().parse(raw).is_err());
+    }
+
+    #[test]
+    fn test_optional_end_tags_list_items_redundant_close_tag() {
+        let raw = "<ul><li>one<li>two</li></li></ul>";
+        let (node, warnings) = parse_with_warnings(raw);
+        assert_eq!(
+            node,
+            document(
+                vec![],
+                vec![el(
+                    "ul",
+                    vec![el("li", vec![txt("one")]), el("li", vec![txt("two")])]
+                )]
+            )
+        );
+        assert_eq!(messages(&warnings), vec!["unexpected close tag </li>"]);
+        assert_eq!(
+            try_parse(raw).unwrap_err().message,
+            "unexpected close tag </li>"
+        );
+        assert!(element().parse(raw).is_err());
+    }
+
+    #[test]
+    fn test_optional_end_tags_at_eof() {
+        let (_, warnings) = parse_with_warnings("<ul><li>a<li>b</ul><dl><dt>t<dd>d");
+        assert_eq!(messages(&warnings), vec!["<dl> is never closed"]);
+    }
+
+    #[test]
+    fn test_optional_end_tags_table() {
+        let (node, warnings) = parse_with_warnings("<table><tr><td>a<td>b<tr><th>c<td>d</table>");
+        assert_eq!(
+            node,
+            document(
+                vec![],
+                vec![el(
+                    "table",
+                    vec![
+                        el(
+                            "tr",
+                            vec![el("td", vec![txt("a")]), el("td", vec![txt("b")])]
+                        ),
+                        el(
+                            "tr",
+                            vec![el("th", vec![txt("c")]), el("td", vec![txt("d")])]
+                        ),
+                    ]
+                )]
+            )
+        );
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_optional_end_tags_nested_lists() {
+        let (node, warnings) = parse_with_warnings("<ul><li>a<ul><li>b</ul><li><b>c<li>d</ul>");
+        assert_eq!(
+            node,
+            document(
+                vec![],
+                vec![el(
+                    "ul",
+                    vec![
+                        el(
+                            "li",
+                            vec![txt("a"), el("ul", vec![el("li", vec![txt("b")])])]
+                        ),
+                        el("li", vec![el("b", vec![txt("c")])]),
+                        el("li", vec![txt("d")]),
+                    ]
+                )]
+            )
+        );
+        assert_eq!(
+            messages(&warnings),
+            vec!["<b> is closed implicitly by <li>"]
+        );
+    }
+
+    fn depth(node: &Node) -> usize {
+        1 + node.children.iter().map(|c| depth(c)).max().unwrap_or(0)
+    }
+
+    #[test]
+    fn test_deeply_nested_input() {
+        let raw = "<div>".repeat(100_000);
+        assert!(try_parse(&raw).is_err());
+        let (node, warnings) = parse_with_warnings(&raw);
+        // html and body wrap the open divs, and the innermost holds the rest side by side
+        assert_eq!(depth(&node), DEFAULT_MAX_DEPTH + 3);
+        assert_eq!(warnings.len(), 100_000);
+        assert_eq!(
+            warnings[0].message,
+            "<div> is nested deeper than 256 elements and is closed immediately"
+        );
+    }
+
+    #[test]
+    fn test_raw_text_beyond_max_depth_stays_in_element() {
+        let (node, _) = parse_with_options(
+            "<div><script>let a = 1;</script><style>p { color: red; }</style>b</div>",
+            ParseOptions {
+                max_depth: 3,
+                ..ParseOptions::default()
+            },
+        );
+        let div = &node.body().unwrap().children[0];
+        assert_eq!(div.children.len(), 3);
+        assert_eq!(div.children[0].inner_text(), "let a = 1;");
+        assert_eq!(div.children[1].inner_text(), "p { color: red; }");
+        assert!(matches!(&div.children[2].node_type, NodeType::Text(t) if t.data == "b"));
+    }
+
+    #[test]
+    fn test_nesting_at_max_depth() {
+        let raw = format!(
+            "{}{}",
+            "<div>".repeat(DEFAULT_MAX_DEPTH),
+            "</div>".repeat(DEFAULT_MAX_DEPTH)
+        );
+        let node = try_parse(&raw).unwrap();
+        assert_eq!(depth(&node), DEFAULT_MAX_DEPTH + 2);
+    }
+
+    fn count(node: &Node) -> usize {
+        1 + node.children.iter().map(|c| count(c)).sum::<usize>()
+    }
+
+    const INDENTED: &str = "<body>
+  <ul>
+    <li>one</li>
+    <li>two</li>
+  </ul>
+  <pre>
+  kept
+  </pre>
+</body>
+";
+
+    #[test]
+    fn test_parse_drops_whitespace_nodes() {
+        let (node, _) = parse_with_options(INDENTED, ParseOptions::default());
+        // html, head, body, ul, two li with their texts, pre and its text
+        assert_eq!(count(&node), 10);
+        let pre = &node.body().unwrap().children[1];
+        assert_eq!(pre.inner_text(), "\n  kept\n  ");
+
+        let options = ParseOptions {
+            preserve_whitespace_nodes: true,
+            ..ParseOptions::default()
+        };
+        let (node, _) = parse_with_options(INDENTED, options);
+        assert_eq!(count(&node), 17);
+    }
+
+    #[test]
+    fn test_parse_keeps_spaces_between_inline_nodes() {
+        let node = parse_fragment("<p>a <b>b</b> c</p>");
+        assert_eq!(node[0].inner_text(), "a b c");
+        let node = parse_fragment("<p><b>a</b>\n  <i>b</i></p>");
+        assert_eq!(node[0].children.len(), 3);
+        assert_eq!(node[0].inner_text(), "a b");
+        let (node, _) = parse_with_warnings("<p>a <b>b</b> c");
+        assert_eq!(node.body().unwrap().inner_text(), "a b c");
+    }
+
+    fn source<'a>(raw: &'a str, node: &Node) -> &'a str {
+        &raw[node.source_span().unwrap()]
+    }
+
+    #[test]
+    fn test_source_span() {
+        let raw = "<body>\n  <p class=\"x\">héllo <b>wörld</b></p><br><img/>\n  <script>a < b</script></body>";
+        let (node, warnings) = parse_with_warnings(raw);
+        assert!(warnings.is_empty());
+        let node = node.body().unwrap();
+        let p = &node.children[0];
+        let script = &node.children[3];
+        assert_eq!(source(raw, node), "<body>");
+        assert_eq!(source(raw, p), "<p class=\"x\">");
+        assert_eq!(source(raw, &p.children[0]), "héllo ");
+        assert_eq!(source(raw, &p.children[1]), "<b>");
+        assert_eq!(source(raw, &p.children[1].children[0]), "wörld");
+        assert_eq!(source(raw, &node.children[1]), "<br>");
+        assert_eq!(source(raw, &node.children[2]), "<img/>");
+        assert_eq!(source(raw, script), "<script>");
+        assert_eq!(source(raw, &script.children[0]), "a < b");
+
+        assert_eq!(
+            source(raw, &try_parse(raw).unwrap().body().unwrap().children[0]),
+            "<p class=\"x\">"
+        );
+        assert_eq!(
+            try_parse_fragment(raw).unwrap()[0].children[0].source_span(),
+            p.source_span()
+        );
+    }
+
+    #[test]
+    fn test_source_span_recovered() {
+        let raw = "<div>\n<b>é < x</div><script>y";
+        let (node, warnings) = parse_with_warnings(raw);
+        assert_eq!(
+            messages(&warnings),
+            vec![
+                "stray '<' is treated as text",
+                "<b> is closed implicitly by </div>",
+                "<script> is never closed"
+            ]
+        );
+        let node = node.body().unwrap();
+        let b = &node.children[0].children[0];
+        assert_eq!(source(raw, &node.children[0]), "<div>");
+        assert_eq!(source(raw, b), "<b>");
+        assert_eq!(source(raw, &b.children[0]), "é < x");
+        assert_eq!(source(raw, &node.children[1]), "<script>");
+        assert_eq!(source(raw, &node.children[1].children[0]), "y");
+        assert_eq!(
+            warnings[2].position,
+            node.children[1].source_span().unwrap().start
+        );
+    }
+
+    #[test]
+    fn test_parse_without_span() {
+        let (node, _) = element().parse("<p>a</p>").unwrap();
+        assert_eq!(node.source_span(), None);
+        assert_eq!(node.children[0].source_span(), None);
+    }
+
+    /// A reader handing out at most `chunk` bytes per read, counting what it has read
+    struct ChunkedReader<'a> {
+        data: &'a [u8],
+        chunk: usize,
+        read: std::rc::Rc<std::cell::Cell<usize>>,
+    }
+
+    impl Read for ChunkedReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.chunk.min(buf.len()).min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            self.read.set(self.read.get() + n);
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_html_parser_split_inside_markup() {
+        let raw = r#"<!DOCTYPE html><html><body><p class="a b" id="x">héllo <b>wörld</b></p></body></html>"#;
+        let mut parser = HtmlParser::new();
+        // Inside the doctype, a tag name and an attribute value
+        for chunk in [
+            "<!DOC",
+            "TYPE html><html><bo",
+            "dy><p class=\"a",
+            " b\" id=\"x\">hé",
+            "llo <b>wörld</b></p></body></html>",
+        ] {
+            parser.feed(chunk);
+        }
+        assert_eq!(parser.finish(), try_parse(raw));
+    }
+
+    #[test]
+    fn test_html_parser_error() {
+        let mut parser = HtmlParser::new();
+        parser.feed("<p>a</d");
+        parser.feed("iv>");
+        assert_eq!(
+            parser.finish().unwrap_err().message,
+            "unexpected close tag </div>"
+        );
+    }
+
+    #[test]
+    fn test_parse_streaming_large_document() {
+        let paragraphs = (0..10000)
+            .map(|i| format!("<p>line {}</p>\n", i))
+            .collect::<String>();
+        let html = format!("<!DOCTYPE html>\n<body>\n{}</body>\n", paragraphs);
+        let read = std::rc::Rc::new(std::cell::Cell::new(0));
+        let reader = ChunkedReader {
+            data: html.as_bytes(),
+            chunk: 4096,
+            read: read.clone(),
+        };
+
+        let mut count = 0;
+        let mut read_at_first_node = None;
+        parse_streaming(reader, |node| {
+            assert_eq!(node.inner_text(), format!("line {}", count));
+            read_at_first_node.get_or_insert(read.get());
+            count += 1;
+        })
+        .unwrap();
+
+        assert_eq!(count, 10000);
+        assert!(read_at_first_node.unwrap() < html.len() / 10);
+    }
+
+    #[test]
+    fn test_parse_streaming_split_in_doctype() {
+        let html = "<!DOCTYPE html>\n<html><body><p>a</p><p>b</p></body></html>";
+        for chunk in [1, 5, 12] {
+            let reader = ChunkedReader {
+                data: html.as_bytes(),
+                chunk,
+                read: Default::default(),
+            };
+            let mut texts = vec![];
+            parse_streaming(reader, |node| texts.push(node.inner_text())).unwrap();
+            assert_eq!(texts, vec!["a", "b"], "chunks of {} bytes", chunk);
+        }
+    }
+
+    #[test]
+    fn test_parse_streaming_unterminated_doctype() {
+        let mut nodes = vec![];
+        parse_streaming("<!DOCTYPE html".as_bytes(), |node| nodes.push(node)).unwrap();
+        assert!(nodes.iter().all(|node| node.inner_text().is_empty()));
+    }
+
+    #[test]
+    fn test_parse_streaming_multibyte_split() {
+        let html = "<p>こんにちは</p><p>世界</p>";
+        let reader = ChunkedReader {
+            data: html.as_bytes(),
+            chunk: 1,
+            read: Default::default(),
+        };
+        let mut texts = vec![];
+        parse_streaming(reader, |node| texts.push(node.inner_text())).unwrap();
+        assert_eq!(texts, vec!["こんにちは", "世界"]);
+    }
+
+    #[test]
+    fn test_parse_streaming_source_spans() {
+        let html = "<body>\n<p>é</p><hr>\n<p>ü</p><div>ö";
+        let reader = ChunkedReader {
+            data: html.as_bytes(),
+            chunk: 3,
+            read: Default::default(),
+        };
+        let mut spans = vec![];
+        parse_streaming(reader, |node| {
+            spans.push(source(html, &node).to_string());
+            if let Some(text) = node.children.first() {
+                spans.push(source(html, text).to_string());
+            }
+        })
+        .unwrap();
+        assert_eq!(spans, vec!["<p>", "é", "<hr>", "<p>", "ü", "<div>", "ö"]);
+    }
+
+    #[test]
+    fn test_parse_streaming_recovers_at_end() {
+        let mut nodes = vec![];
+        parse_streaming("<body><p>a</p><div>b".as_bytes(), |node| nodes.push(node)).unwrap();
+        assert_eq!(
+            nodes,
+            vec![
+                Element::new(
+                    "p".to_string(),
+                    AttrMap::new(),
+                    vec![Text::new("a".to_string())]
+                ),
+                Element::new(
+                    "div".to_string(),
+                    AttrMap::new(),
+                    vec![Text::new("b".to_string())]
+                ),
+            ]
         );
     }
 }
@@ -0,0 +1,358 @@
+use std::{collections::HashMap, fmt};
+
+use super::dom::{Node, NodeType};
+
+/// A structural problem found in a document
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintWarning {
+    /// Where the node is, as [`Node::node_path`] writes it
+    pub node_path: String,
+    /// Name of the rule that found the problem
+    pub rule: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} ({})", self.node_path, self.message, self.rule)
+    }
+}
+
+/// A check over the document rooted at the node given, getting a warning for each
+/// problem it finds
+pub type LintRule = fn(&Node) -> Vec<LintWarning>;
+
+/// The rules `lint` is run with by default
+pub const DEFAULT_RULES: [LintRule; 6] = [
+    heading_levels,
+    img_alt,
+    duplicate_ids,
+    empty_href,
+    label_control,
+    table_cells,
+];
+
+/// Elements a `<label>` can name
+const LABELABLE_ELEMENTS: [&str; 7] = [
+    "button", "input", "meter", "output", "progress", "select", "textarea",
+];
+
+/// Run `rules` over the document rooted at `root`, getting their warnings rule by rule
+/// # Example
+/// ```
+/// use tiny_browserbook::html::{html::parse, lint::{lint, DEFAULT_RULES}};
+/// let node = parse(r#"<h1>Title</h1><h3>Part</h3><img src="a.png">"#);
+/// let warnings: Vec<_> = lint(&node, &DEFAULT_RULES)
+///     .iter()
+///     .map(|warning| warning.to_string())
+///     .collect();
+/// assert_eq!(
+///     warnings,
+///     vec![
+///         "/html/body[1]/h3[1]: <h3> follows <h1>, skipping a level (heading-levels)",
+///         "/html/body[1]/img[1]: <img> has no alt text (img-alt)",
+///     ]
+/// );
+/// ```
+pub fn lint(root: &Node, rules: &[LintRule]) -> Vec<LintWarning> {
+    rules.iter().flat_map(|rule| rule(root)).collect()
+}
+
+fn warning(root: &Node, node: &Node, rule: &'static str, message: String) -> LintWarning {
+    LintWarning {
+        node_path: root.node_path(node).unwrap_or_default(),
+        rule,
+        message,
+    }
+}
+
+fn tag_name(node: &Node) -> Option<&str> {
+    match &node.node_type {
+        NodeType::Element(e) => Some(e.tag_name.as_str()),
+        NodeType::Text(_) => None,
+    }
+}
+
+fn heading_level(node: &Node) -> Option<usize> {
+    match tag_name(node)?.as_bytes() {
+        [b'h', level @ b'1'..=b'6'] => Some((level - b'0') as usize),
+        _ => None,
+    }
+}
+
+/// Warn of a heading more than one level below the heading before it
+pub fn heading_levels(root: &Node) -> Vec<LintWarning> {
+    let mut warnings = vec![];
+    let mut previous = None;
+    for node in root.get_elements_by_tag_name("*") {
+        let Some(level) = heading_level(node) else {
+            continue;
+        };
+        if let Some(previous) = previous.filter(|previous| level > previous + 1) {
+            warnings.push(warning(
+                root,
+                node,
+                "heading-levels",
+                format!("<h{}> follows <h{}>, skipping a level", level, previous),
+            ));
+        }
+        previous = Some(level);
+    }
+    warnings
+}
+
+/// Warn of an `<img>` without an `alt`, which may be empty for a decorative image
+pub fn img_alt(root: &Node) -> Vec<LintWarning> {
+    root.get_elements_by_tag_name("img")
+        .into_iter()
+        .filter(|img| !img.has_attribute("alt"))
+        .map(|img| warning(root, img, "img-alt", "<img> has no alt text".to_string()))
+        .collect()
+}
+
+/// Warn of each element after the first with the same id
+pub fn duplicate_ids(root: &Node) -> Vec<LintWarning> {
+    let mut first: HashMap<&str, &Node> = HashMap::new();
+    let mut warnings = vec![];
+    for node in root.get_elements_by_tag_name("*") {
+        let Some(id) = node.get_attribute("id") else {
+            continue;
+        };
+        match first.get(id) {
+            Some(earlier) => warnings.push(warning(
+                root,
+                node,
+                "duplicate-ids",
+                format!(
+                    "id {:?} is already used by {}",
+                    id,
+                    root.node_path(earlier).unwrap_or_default()
+                ),
+            )),
+            None => {
+                first.insert(id, node);
+            }
+        }
+    }
+    warnings
+}
+
+/// Warn of an `<a>` whose `href` is there but blank
+pub fn empty_href(root: &Node) -> Vec<LintWarning> {
+    root.get_elements_by_tag_name("a")
+        .into_iter()
+        .filter(|a| {
+            a.get_attribute("href")
+                .is_some_and(|href| href.trim().is_empty())
+        })
+        .map(|a| warning(root, a, "empty-href", "link has an empty href".to_string()))
+        .collect()
+}
+
+/// Warn of a `<label>` whose `for` names no control, or without `for` and with no
+/// control in it
+pub fn label_control(root: &Node) -> Vec<LintWarning> {
+    let is_control = |node: &Node| tag_name(node).is_some_and(|t| LABELABLE_ELEMENTS.contains(&t));
+    root.get_elements_by_tag_name("label")
+        .into_iter()
+        .filter_map(|label| {
+            let message = match label.get_attribute("for") {
+                Some(id) if !root.get_element_by_id(id).is_some_and(is_control) => {
+                    format!("<label for={:?}> names no control", id)
+                }
+                None if !label.descendants().any(is_control) => {
+                    "<label> has no control in it".to_string()
+                }
+                _ => return None,
+            };
+            Some(warning(root, label, "label-control", message))
+        })
+        .collect()
+}
+
+/// The rows of `table`, leaving out those of tables inside it
+fn table_rows(table: &Node) -> Vec<&Node> {
+    table
+        .children_elements()
+        .flat_map(|child| match tag_name(child) {
+            Some("tr") => vec![child],
+            Some("thead" | "tbody" | "tfoot") => child
+                .children_elements()
+                .filter(|row| tag_name(row) == Some("tr"))
+                .collect(),
+            _ => vec![],
+        })
+        .collect()
+}
+
+/// How many columns the cells of `row` span
+fn row_width(row: &Node) -> usize {
+    row.children_elements()
+        .filter(|cell| matches!(tag_name(cell), Some("td" | "th")))
+        .map(|cell| {
+            cell.get_attribute("colspan")
+                .and_then(|span| span.trim().parse::<usize>().ok())
+                .filter(|span| *span > 0)
+                .unwrap_or(1)
+        })
+        .sum()
+}
+
+/// Warn of a table row spanning a different number of columns than the first row
+///
+/// Row spans are not followed, so a table using `rowspan` is not checked.
+pub fn table_cells(root: &Node) -> Vec<LintWarning> {
+    let mut warnings = vec![];
+    for table in root.get_elements_by_tag_name("table") {
+        let rows = table_rows(table);
+        let spans_rows = rows.iter().any(|row| {
+            row.children_elements()
+                .any(|cell| cell.has_attribute("rowspan"))
+        });
+        let Some((first, rest)) = rows.split_first().filter(|_| !spans_rows) else {
+            continue;
+        };
+        let width = row_width(first);
+        for row in rest {
+            let cells = row_width(row);
+            if cells != width {
+                warnings.push(warning(
+                    root,
+                    row,
+                    "table-cells",
+                    format!("row has {} cells, the first row has {}", cells, width),
+                ));
+            }
+        }
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::html::html::parse;
+
+    use super::*;
+
+    fn messages(rule: LintRule, html: &str) -> Vec<String> {
+        let node = parse(html);
+        rule(&node)
+            .into_iter()
+            .map(|warning| format!("{}: {}", warning.node_path, warning.message))
+            .collect()
+    }
+
+    #[rstest]
+    #[case("<h1>a</h1><h2>b</h2><h3>c</h3><h2>d</h2><h1>e</h1>", vec![])]
+    #[case("<h2>a</h2><h3>b</h3>", vec![])]
+    #[case(
+        "<h1>a</h1><section><h3>b</h3></section><h4>c</h4><h6>d</h6>",
+        vec![
+            "/html/body[1]/section[1]/h3[1]: <h3> follows <h1>, skipping a level",
+            "/html/body[1]/h6[1]: <h6> follows <h4>, skipping a level",
+        ]
+    )]
+    fn test_heading_levels(#[case] html: &str, #[case] expected: Vec<&str>) {
+        assert_eq!(messages(heading_levels, html), expected);
+    }
+
+    #[rstest]
+    #[case(r#"<img src="a.png" alt="A"><img src="line.png" alt="">"#, vec![])]
+    #[case(
+        r#"<img src="a.png" alt="A"><p><img src="b.png"></p>"#,
+        vec!["/html/body[1]/p[1]/img[1]: <img> has no alt text"]
+    )]
+    fn test_img_alt(#[case] html: &str, #[case] expected: Vec<&str>) {
+        assert_eq!(messages(img_alt, html), expected);
+    }
+
+    #[rstest]
+    #[case(r#"<p id="a">a</p><p id="b">b</p>"#, vec![])]
+    #[case(
+        r#"<p id="a">a</p><div id="a"><span id="a">b</span></div>"#,
+        vec![
+            r#"/html/body[1]/div[1]: id "a" is already used by /html/body[1]/p[1]"#,
+            r#"/html/body[1]/div[1]/span[1]: id "a" is already used by /html/body[1]/p[1]"#,
+        ]
+    )]
+    fn test_duplicate_ids(#[case] html: &str, #[case] expected: Vec<&str>) {
+        assert_eq!(messages(duplicate_ids, html), expected);
+    }
+
+    #[rstest]
+    #[case(r##"<a href="/">home</a><a href="#top">top</a><a name="x">x</a>"##, vec![])]
+    #[case(
+        r#"<a href="">a</a><a href="  ">b</a>"#,
+        vec![
+            "/html/body[1]/a[1]: link has an empty href",
+            "/html/body[1]/a[2]: link has an empty href",
+        ]
+    )]
+    fn test_empty_href(#[case] html: &str, #[case] expected: Vec<&str>) {
+        assert_eq!(messages(empty_href, html), expected);
+    }
+
+    #[rstest]
+    #[case(
+        r#"<label for="q">Search</label><input id="q"><label>Name <select></select></label>"#,
+        vec![]
+    )]
+    #[case(
+        r#"<label for="q">Search</label><label for="p">Text</label><p id="p">p</p><label>Name</label>"#,
+        vec![
+            r#"/html/body[1]/label[1]: <label for="q"> names no control"#,
+            r#"/html/body[1]/label[2]: <label for="p"> names no control"#,
+            "/html/body[1]/label[3]: <label> has no control in it",
+        ]
+    )]
+    fn test_label_control(#[case] html: &str, #[case] expected: Vec<&str>) {
+        assert_eq!(messages(label_control, html), expected);
+    }
+
+    #[rstest]
+    #[case(
+        r#"<table><thead><tr><th>a</th><th>b</th></tr></thead><tbody><tr><td colspan="2">c</td></tr></tbody></table>"#,
+        vec![]
+    )]
+    #[case(
+        "<table><tr><td>a</td><td><table><tr><td>b</td></tr></table></td></tr><tr><td>c</td><td>d</td></tr></table>",
+        vec![]
+    )]
+    #[case(
+        r#"<table><tr><td rowspan="2">a</td><td>b</td></tr><tr><td>c</td></tr></table>"#,
+        vec![]
+    )]
+    #[case(
+        "<table><tr><td>a</td><td>b</td></tr><tr><td>c</td></tr><tr><td>d</td><td>e</td><td>f</td></tr></table>",
+        vec![
+            "/html/body[1]/table[1]/tr[2]: row has 1 cells, the first row has 2",
+            "/html/body[1]/table[1]/tr[3]: row has 3 cells, the first row has 2",
+        ]
+    )]
+    fn test_table_cells(#[case] html: &str, #[case] expected: Vec<&str>) {
+        assert_eq!(messages(table_cells, html), expected);
+    }
+
+    #[test]
+    fn test_lint_with_custom_rule() {
+        fn no_marquee(root: &Node) -> Vec<LintWarning> {
+            root.get_elements_by_tag_name("marquee")
+                .into_iter()
+                .map(|node| warning(root, node, "no-marquee", "<marquee>".to_string()))
+                .collect()
+        }
+        let node = parse(r#"<marquee>hi</marquee><img src="a.png">"#);
+        let rules: Vec<LintRule> = DEFAULT_RULES
+            .into_iter()
+            .chain([no_marquee as LintRule])
+            .collect();
+        let rules: Vec<_> = lint(&node, &rules)
+            .into_iter()
+            .map(|warning| warning.rule)
+            .collect();
+        assert_eq!(rules, vec!["img-alt", "no-marquee"]);
+        assert!(lint(&parse("<p>clean</p>"), &DEFAULT_RULES).is_empty());
+    }
+}
@@ -0,0 +1,541 @@
+use crate::html::dom::AttrMap;
+use combine::between;
+use combine::choice;
+use combine::error::ParseError;
+use combine::many;
+use combine::many1;
+use combine::optional;
+use combine::parser::char::alpha_num;
+use combine::parser::char::char;
+use combine::parser::char::letter;
+use combine::parser::char::newline;
+use combine::parser::char::space;
+use combine::parser::char::string;
+use combine::satisfy;
+use combine::sep_end_by;
+use combine::{attempt, Parser, Stream};
+
+/// Any run of spaces and newlines, possibly empty
+pub fn whitespaces<Input>() -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    many::<String, _, _>(space().or(newline()))
+}
+
+/// Decode character references like `&amp;`, `&#65;` and `&#x41;`.
+/// Unknown or unterminated references are kept as they are. Numeric references to
+/// NUL, a surrogate or beyond U+10FFFF decode to U+FFFD, as in browsers.
+pub fn decode_entities(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest[1..]
+            .find(';')
+            .and_then(|end| decode_entity(&rest[1..end + 1]).map(|c| (c, end + 2)));
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn decode_entity(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some('\u{a0}'),
+        _ => {
+            let code = name.strip_prefix('#')?;
+            let (digits, radix) = match code.strip_prefix(['x', 'X']) {
+                Some(hex) => (hex, 16),
+                None => (code, 10),
+            };
+            if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+                return None;
+            }
+            let c = u32::from_str_radix(digits, radix)
+                .ok()
+                .filter(|&code| code != 0)
+                .and_then(char::from_u32);
+            Some(c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        }
+    }
+}
+
+/// Parse an attribute. An attribute without a value, like `disabled`, gets an
+/// empty string as its value, as `getAttribute` returns in browsers.
+pub fn attribute<Input>() -> impl Parser<Input, Output = (String, String)>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let name = many1::<String, _, _>(satisfy(|c: char| {
+        !c.is_whitespace() && !['=', '/', '>', '<', '"', '\''].contains(&c)
+    }));
+    let value = choice((
+        between(
+            char('"'),
+            char('"'),
+            many::<String, _, _>(satisfy(|c: char| c != '"')),
+        ),
+        between(
+            char('\''),
+            char('\''),
+            many::<String, _, _>(satisfy(|c: char| c != '\'')),
+        ),
+        many1::<String, _, _>(satisfy(|c: char| {
+            !c.is_whitespace() && c != '>' && c != '"' && c != '\''
+        })),
+    ));
+    (
+        name,
+        optional((
+            attempt((many::<String, _, _>(space().or(newline())), char('='))),
+            many::<String, _, _>(space().or(newline())),
+            value,
+        )),
+    )
+        .map(|(name, value)| {
+            let value = value.map(|v| decode_entities(&v.2)).unwrap_or_default();
+            (name, value)
+        })
+}
+
+/// Parse whitespace-separated attributes, keeping the first value of a repeated name
+pub fn attributes<Input>() -> impl Parser<Input, Output = AttrMap>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    attributes_with_duplicates().map(|(attributes, _)| attributes)
+}
+
+/// Parse whitespace-separated attributes as `attributes` does, along with the names
+/// repeated after their first occurrence
+/// # Example
+/// ```
+/// use combine::Parser;
+/// use tiny_browserbook::html::parser::attributes_with_duplicates;
+/// let ((attributes, duplicates), _) =
+///     attributes_with_duplicates().parse(r#"class="a" id="x" class="b""#).unwrap();
+/// assert_eq!(attributes["class"], "a");
+/// assert_eq!(duplicates, vec!["class"]);
+/// ```
+pub fn attributes_with_duplicates<Input>() -> impl Parser<Input, Output = (AttrMap, Vec<String>)>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    sep_end_by::<Vec<(String, String)>, _, _, _>(
+        attribute(),
+        many::<String, _, _>(space().or(newline())),
+    )
+    .map(|attrs: Vec<(String, String)>| {
+        let mut attributes = AttrMap::new();
+        let mut duplicates = vec![];
+        for (name, value) in attrs {
+            if attributes.contains_key(&name) {
+                duplicates.push(name);
+            } else {
+                attributes.insert(name, value);
+            }
+        }
+        (attributes, duplicates)
+    })
+}
+
+/// Parse an open tag like `<p class="a">`, lowercasing its name
+/// # Example
+/// ```
+/// use combine::Parser;
+/// use tiny_browserbook::html::parser::open_tag;
+/// let ((name, attributes), _) = open_tag().parse("<P class=\"a\">").unwrap();
+/// assert_eq!(name, "p");
+/// assert_eq!(attributes["class"], "a");
+/// ```
+pub fn open_tag<Input>() -> impl Parser<Input, Output = (String, AttrMap)>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    open_tag_with_duplicates().map(|(name, attributes, _)| (name, attributes))
+}
+
+/// Parse an open tag as `open_tag` does, along with its repeated attribute names
+pub fn open_tag_with_duplicates<Input>(
+) -> impl Parser<Input, Output = (String, AttrMap, Vec<String>)>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    between(char('<'), char('>'), tag_content())
+}
+
+/// Parse a self-closing tag like `<br />`, lowercasing its name
+pub fn self_closing_tag<Input>() -> impl Parser<Input, Output = (String, AttrMap)>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    self_closing_tag_with_duplicates().map(|(name, attributes, _)| (name, attributes))
+}
+
+/// Parse a self-closing tag as `self_closing_tag` does, along with its repeated attribute names
+pub fn self_closing_tag_with_duplicates<Input>(
+) -> impl Parser<Input, Output = (String, AttrMap, Vec<String>)>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    between(char('<'), string("/>"), tag_content())
+}
+
+/// A tag name like `p` or `h1`, as written
+pub fn tag_name<Input>() -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (letter(), many::<String, _, _>(alpha_num())).map(|(first, rest)| format!("{}{}", first, rest))
+}
+
+fn tag_content<Input>() -> impl Parser<Input, Output = (String, AttrMap, Vec<String>)>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        tag_name(),
+        many::<String, _, _>(space().or(newline())),
+        attributes_with_duplicates(),
+    )
+        .map(|(name, _, (attributes, duplicates)): (String, String, _)| {
+            (name.to_ascii_lowercase(), attributes, duplicates)
+        })
+}
+
+/// Parse a close tag like `</p>`, lowercasing its name
+pub fn close_tag<Input>() -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let close_tag_content =
+        (char('/'), tag_name(), whitespaces()).map(|v| v.1.to_ascii_lowercase());
+    between(char('<'), char('>'), close_tag_content)
+}
+
+#[cfg(test)]
+mod tests {
+    use combine::EasyParser;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_tag_name_with_digits() {
+        assert_eq!(
+            open_tag().parse("<H2 id=\"a\">"),
+            Ok((
+                (
+                    "h2".to_string(),
+                    [("id".to_string(), "a".to_string())].into_iter().collect()
+                ),
+                ""
+            ))
+        );
+        assert_eq!(close_tag().parse("</h2>"), Ok(("h2".to_string(), "")));
+        assert!(open_tag().parse("<2h>").is_err());
+    }
+
+    #[test]
+    fn test_parse_attribut() {
+        assert_eq!(
+            attribute().parse("test=\"foobar\""),
+            Ok((("test".to_string(), "foobar".to_string()), ""))
+        );
+    }
+
+    #[test]
+    fn test_parse_attribut_has_space() {
+        assert_eq!(
+            attribute().parse("test = \"foobar\""),
+            Ok((("test".to_string(), "foobar".to_string()), ""))
+        );
+    }
+
+    #[test]
+    fn test_parse_attribute_unquoted() {
+        assert_eq!(
+            attribute().parse("id=result"),
+            Ok((("id".to_string(), "result".to_string()), ""))
+        );
+    }
+
+    #[test]
+    fn test_parse_attribute_single_quoted() {
+        assert_eq!(
+            attribute().parse("class='inline'"),
+            Ok((("class".to_string(), "inline".to_string()), ""))
+        );
+        assert_eq!(
+            attribute().parse("class='a \"b\"'"),
+            Ok((("class".to_string(), "a \"b\"".to_string()), ""))
+        );
+        assert_eq!(
+            attribute().parse("title=\"it's\""),
+            Ok((("title".to_string(), "it's".to_string()), ""))
+        );
+    }
+
+    #[test]
+    fn test_parse_attribute_hyphenated_name() {
+        for (raw, name) in [
+            ("data-id=\"5\"", "data-id"),
+            ("aria-label=\"5\"", "aria-label"),
+            ("http-equiv=\"5\"", "http-equiv"),
+            ("xlink:href=\"5\"", "xlink:href"),
+            ("h2o=\"5\"", "h2o"),
+        ] {
+            assert_eq!(
+                attribute().parse(raw),
+                Ok(((name.to_string(), "5".to_string()), ""))
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_attribute_without_value() {
+        assert!(attribute().parse("id=").is_err());
+        assert!(attribute().parse("id= >").is_err());
+    }
+
+    #[test]
+    fn test_parse_attribute_empty_quoted() {
+        assert_eq!(
+            attribute().parse("alt=\"\""),
+            Ok((("alt".to_string(), "".to_string()), ""))
+        );
+        assert_eq!(
+            attribute().parse("alt=''"),
+            Ok((("alt".to_string(), "".to_string()), ""))
+        );
+    }
+
+    #[test]
+    fn test_parse_attributes() {
+        let mut expected_map = AttrMap::new();
+        expected_map.insert("test".to_string(), "foobar".to_string());
+        expected_map.insert("abc".to_string(), "def".to_string());
+        assert_eq!(
+            attributes().easy_parse("test=\"foobar\" abc=\"def\""),
+            Ok((expected_map, ""))
+        )
+    }
+
+    #[test]
+    fn test_parse_attributes_source_order() {
+        let (attributes, _) = attributes()
+            .parse(r#"title="t" id=x class="a" data-b="1" alt="" hidden"#)
+            .unwrap();
+        let names: Vec<_> = attributes.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["title", "id", "class", "data-b", "alt", "hidden"]
+        );
+    }
+
+    #[test]
+    fn test_parse_attributes_first_wins() {
+        let ((attributes, duplicates), _) = attributes_with_duplicates()
+            .parse(r#"class="a" id=x class="b" id=y class"#)
+            .unwrap();
+        assert_eq!(attributes.len(), 2);
+        assert_eq!(attributes["class"], "a");
+        assert_eq!(attributes["id"], "x");
+        assert_eq!(duplicates, vec!["class", "id", "class"]);
+        let ((_, attributes), _) = open_tag().parse(r#"<p class="a" class="b">"#).unwrap();
+        assert_eq!(attributes["class"], "a");
+    }
+
+    #[test]
+    fn test_parse_non_attributes() {
+        assert_eq!(attributes().easy_parse(""), Ok((AttrMap::new(), "")))
+    }
+
+    #[test]
+    fn test_parse_open_tag() {
+        assert_eq!(
+            open_tag().easy_parse("<p>aaaa"),
+            Ok((("p".to_string(), AttrMap::new()), "aaaa"))
+        );
+    }
+
+    #[test]
+    fn test_parse_open_tag_has_an_attribute() {
+        let mut attributes = AttrMap::new();
+        attributes.insert("id".to_string(), "test".to_string());
+        assert_eq!(
+            open_tag().easy_parse("<p id=\"test\">"),
+            Ok((("p".to_string(), attributes), ""))
+        )
+    }
+
+    #[test]
+    fn test_parse_open_tag_has_attributes() {
+        let result = open_tag().easy_parse("<p id=\"test\" class=\"sample\">");
+        let mut attributes = AttrMap::new();
+        attributes.insert("id".to_string(), "test".to_string());
+        attributes.insert("class".to_string(), "sample".to_string());
+        assert_eq!(result, Ok((("p".to_string(), attributes), "")));
+    }
+
+    #[test]
+    fn test_parse_open_tag_has_unquoted_attributes() {
+        let result = open_tag().easy_parse("<p class=inline id=x>");
+        let mut attributes = AttrMap::new();
+        attributes.insert("class".to_string(), "inline".to_string());
+        attributes.insert("id".to_string(), "x".to_string());
+        assert_eq!(result, Ok((("p".to_string(), attributes), "")));
+    }
+
+    #[test]
+    fn test_parse_open_tag_has_mixed_quoted_attributes() {
+        let result = open_tag().easy_parse("<p class='a \"b\"' id=\"x\" title=t>");
+        let mut attributes = AttrMap::new();
+        attributes.insert("class".to_string(), "a \"b\"".to_string());
+        attributes.insert("id".to_string(), "x".to_string());
+        attributes.insert("title".to_string(), "t".to_string());
+        assert_eq!(result, Ok((("p".to_string(), attributes), "")));
+    }
+
+    #[test]
+    fn test_parse_open_tag_has_boolean_attribute() {
+        let mut attributes = AttrMap::new();
+        attributes.insert("disabled".to_string(), "".to_string());
+        assert_eq!(
+            open_tag().easy_parse("<input disabled>"),
+            Ok((("input".to_string(), attributes.clone()), ""))
+        );
+
+        attributes.insert("type".to_string(), "text".to_string());
+        assert_eq!(
+            open_tag().easy_parse("<input disabled type=\"text\">"),
+            Ok((("input".to_string(), attributes), ""))
+        );
+    }
+
+    #[test]
+    fn test_parse_open_tag_multi_line() {
+        let mut attributes = AttrMap::new();
+        attributes.insert("id".to_string(), "a".to_string());
+        attributes.insert("class".to_string(), "b".to_string());
+        for raw in [
+            "<div\n  id=\"a\"\n  class=\"b\"\n>",
+            "<div\tid=\"a\"\tclass=\"b\"\t>",
+            "<div\r\n  id = \"a\"\r\n  class\r\n=\r\n\"b\"\r\n>",
+        ] {
+            assert_eq!(
+                open_tag().easy_parse(raw),
+                Ok((("div".to_string(), attributes.clone()), ""))
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_self_closing_tag_multi_line() {
+        let mut attributes = AttrMap::new();
+        attributes.insert("src".to_string(), "x".to_string());
+        assert_eq!(
+            self_closing_tag().easy_parse("<img\r\n\tsrc=\"x\"\r\n/>"),
+            Ok((("img".to_string(), attributes), ""))
+        );
+    }
+
+    #[test]
+    fn test_parse_close_tag_with_whitespace() {
+        assert_eq!(close_tag().parse("</p \t\r\n>"), Ok(("p".to_string(), "")));
+    }
+
+    #[test]
+    fn test_parse_open_tag_invalid() {
+        assert!(open_tag().easy_parse("<p id=>").is_err());
+    }
+
+    #[test]
+    fn test_parse_close_tag() {
+        let result = close_tag().parse("</p>");
+        assert_eq!(result, Ok(("p".to_string(), "")));
+    }
+
+    #[test]
+    fn test_parse_self_closing_tag() {
+        assert_eq!(
+            self_closing_tag().easy_parse("<br/>"),
+            Ok((("br".to_string(), AttrMap::new()), ""))
+        );
+        assert_eq!(
+            self_closing_tag().easy_parse("<br />"),
+            Ok((("br".to_string(), AttrMap::new()), ""))
+        );
+    }
+
+    #[test]
+    fn test_parse_self_closing_tag_has_attributes() {
+        let mut attributes = AttrMap::new();
+        attributes.insert("type".to_string(), "text".to_string());
+        attributes.insert("name".to_string(), "q".to_string());
+        assert_eq!(
+            self_closing_tag().easy_parse("<input type=\"text\" name=\"q\" />"),
+            Ok((("input".to_string(), attributes), ""))
+        );
+    }
+
+    #[test]
+    fn test_decode_entities() {
+        assert_eq!(decode_entities("Fish &amp; Chips"), "Fish & Chips");
+        assert_eq!(decode_entities("&lt;tag&gt;"), "<tag>");
+        assert_eq!(decode_entities("&quot;a&apos;"), "\"a'");
+        assert_eq!(decode_entities("a&nbsp;b"), "a\u{a0}b");
+        assert_eq!(decode_entities("&#65;&#x41;&#X42;"), "AAB");
+    }
+
+    #[test]
+    fn test_decode_entities_replacement() {
+        assert_eq!(decode_entities("&#0;"), "\u{fffd}");
+        assert_eq!(decode_entities("&#xD800;&#xdfff;"), "\u{fffd}\u{fffd}");
+        assert_eq!(decode_entities("&#1114112;&#x110000;"), "\u{fffd}\u{fffd}");
+        assert_eq!(decode_entities("&#99999999999;"), "\u{fffd}");
+        assert_eq!(decode_entities("&#x10FFFF;"), "\u{10ffff}");
+    }
+
+    #[test]
+    fn test_decode_entities_pass_through() {
+        assert_eq!(decode_entities("a & b"), "a & b");
+        assert_eq!(decode_entities("&unknown; &amp"), "&unknown; &amp");
+        assert_eq!(decode_entities("&#xzz; &#; &#x;"), "&#xzz; &#; &#x;");
+        assert_eq!(decode_entities("&&amp;"), "&&");
+    }
+
+    #[test]
+    fn test_parse_attribute_with_entities() {
+        assert_eq!(
+            attribute().parse("title=\"a &amp; b\""),
+            Ok((("title".to_string(), "a & b".to_string()), ""))
+        );
+    }
+}
@@ -6,7 +6,10 @@ use v8::{
     V8::{initialize, initialize_platform},
 };
 
-use crate::html::dom::Node;
+use crate::{
+    error::error::{BrowserError, JsError},
+    html::dom::Node,
+};
 
 use super::renderapi::RendererAPI;
 
@@ -90,6 +93,12 @@ impl JavascriptRuntime {
             }
         }
     }
+
+    /// Same as `execute`, with the failure as a `BrowserError`
+    pub fn execute_value(&mut self, filename: &str, source: &str) -> Result<String, BrowserError> {
+        self.execute(filename, source)
+            .map_err(|message| JsError { message }.into())
+    }
 }
 
 impl JavascriptRuntime {
@@ -225,4 +234,13 @@ mod tests {
             assert_eq!(result.unwrap(), "5");
         }
     }
+
+    #[rstest]
+    fn test_execute_value(mut runtime: JavascriptRuntime) {
+        assert_eq!(runtime.execute_value("", "1 + 1"), Ok("2".to_string()));
+        assert!(matches!(
+            runtime.execute_value("", "test"),
+            Err(BrowserError::Js(_))
+        ));
+    }
 }
@@ -2,7 +2,7 @@ use std::rc::Rc;
 
 use cursive::{views::LayerPosition, CbSink};
 
-use crate::renderer::renderer::Renderer;
+use crate::renderer::renderer::{error_layer, Renderer};
 
 pub struct RendererAPI {
     ui_cb_sink: Rc<CbSink>,
@@ -22,7 +22,9 @@ impl RendererAPI {
                     .unwrap()
                     .downcast_mut()
                     .unwrap();
-                layer.rerender()
+                if let Err(error) = layer.try_rerender() {
+                    s.add_layer(error_layer(&error));
+                }
             }))
             .unwrap();
     }
@@ -0,0 +1,156 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Unit {
+    /// One terminal cell
+    Ch,
+    Em,
+    Px,
+    /// Relative to the containing block
+    Percent,
+    /// No unit, as in `line-height: 1.5`, taken as cells where a length is needed
+    Number,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Length {
+    pub value: f32,
+    pub unit: Unit,
+}
+
+impl Length {
+    pub fn new(value: f32, unit: Unit) -> Self {
+        Length { value, unit }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Axis {
+    Horizontal,
+    Vertical,
+}
+
+/// How many terminal cells CSS units take up
+///
+/// A cell is about twice as tall as it is wide, so an em is two cells wide
+/// and one cell high. Pixels go through the width ratio and are scaled by
+/// the em ratio vertically, so `16px` is one em on both axes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UnitMetrics {
+    pub em_width: f32,
+    pub em_height: f32,
+    pub px_per_cell: f32,
+}
+
+impl Default for UnitMetrics {
+    fn default() -> Self {
+        UnitMetrics {
+            em_width: 2.0,
+            em_height: 1.0,
+            px_per_cell: 8.0,
+        }
+    }
+}
+
+/// What a length is resolved against
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UnitContext {
+    pub metrics: UnitMetrics,
+    /// Size of the containing block in cells, as (width, height)
+    pub containing_block: (usize, usize),
+}
+
+impl UnitContext {
+    /// Context of a child whose containing block is `containing_block`
+    pub fn with_containing_block(&self, containing_block: (usize, usize)) -> Self {
+        UnitContext {
+            metrics: self.metrics,
+            containing_block,
+        }
+    }
+}
+
+/// Resolve a length to a number of cells along `axis`
+/// # Example
+/// ```
+/// use tiny_browserbook::layout::units::{resolve, Axis, Length, Unit, UnitContext, UnitMetrics};
+/// let context = UnitContext {
+///     metrics: UnitMetrics::default(),
+///     containing_block: (80, 24),
+/// };
+/// assert_eq!(resolve(&Length::new(3.0, Unit::Em), Axis::Horizontal, &context), 6);
+/// assert_eq!(resolve(&Length::new(50.0, Unit::Percent), Axis::Vertical, &context), 12);
+/// ```
+pub fn resolve(length: &Length, axis: Axis, context: &UnitContext) -> usize {
+    let metrics = &context.metrics;
+    let em = match axis {
+        Axis::Horizontal => metrics.em_width,
+        Axis::Vertical => metrics.em_height,
+    };
+    let cells = match length.unit {
+        Unit::Ch | Unit::Number => length.value,
+        Unit::Em => length.value * em,
+        Unit::Px => length.value / metrics.px_per_cell * em / metrics.em_width,
+        Unit::Percent => {
+            let (width, height) = context.containing_block;
+            let base = match axis {
+                Axis::Horizontal => width,
+                Axis::Vertical => height,
+            };
+            base as f32 * length.value / 100.0
+        }
+    };
+    cells.round().max(0.0) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn context() -> UnitContext {
+        UnitContext {
+            metrics: UnitMetrics::default(),
+            containing_block: (80, 24),
+        }
+    }
+
+    #[rstest]
+    #[case(Length::new(10.0, Unit::Ch), 10, 10)]
+    #[case(Length::new(2.0, Unit::Em), 4, 2)]
+    #[case(Length::new(160.0, Unit::Px), 20, 10)]
+    #[case(Length::new(50.0, Unit::Percent), 40, 12)]
+    #[case(Length::new(-1.0, Unit::Ch), 0, 0)]
+    #[case(Length::new(3.0, Unit::Number), 3, 3)]
+    fn test_resolve(#[case] length: Length, #[case] horizontal: usize, #[case] vertical: usize) {
+        assert_eq!(resolve(&length, Axis::Horizontal, &context()), horizontal);
+        assert_eq!(resolve(&length, Axis::Vertical, &context()), vertical);
+    }
+
+    #[test]
+    fn test_resolve_custom_metrics() {
+        let context = UnitContext {
+            metrics: UnitMetrics {
+                em_width: 1.0,
+                em_height: 1.0,
+                px_per_cell: 10.0,
+            },
+            containing_block: (80, 24),
+        };
+        let length = Length::new(30.0, Unit::Px);
+        assert_eq!(resolve(&length, Axis::Horizontal, &context), 3);
+        assert_eq!(resolve(&length, Axis::Vertical, &context), 3);
+    }
+
+    #[test]
+    fn test_resolve_nested_percent() {
+        let half = Length::new(50.0, Unit::Percent);
+        let outer = context();
+        let parent = (
+            resolve(&half, Axis::Horizontal, &outer),
+            resolve(&half, Axis::Vertical, &outer),
+        );
+        let inner = outer.with_containing_block(parent);
+        assert_eq!(resolve(&half, Axis::Horizontal, &inner), 20);
+        assert_eq!(resolve(&half, Axis::Vertical, &inner), 6);
+    }
+}
@@ -1,7 +1,10 @@
+pub mod config;
 pub mod css;
+pub mod error;
 pub mod html;
 pub mod javascript;
 pub mod layout;
+pub mod net;
 pub mod render;
 pub mod renderer;
 pub mod style;
@@ -1,15 +1,22 @@
-use std::rc::Rc;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
 use tiny_browserbook::{
+    config::config::{default_path, Config},
     css::css,
-    html::{
-        dom::{Node, NodeType},
-        html::parse,
-    },
+    error::error::LoadError,
+    html::html::parse_with_options,
     layout::layout::to_layout_box,
+    net::{
+        loader::{FileLoader, LoggingLoader, RequestLog, ResourceLoader},
+        url::Url,
+    },
     render::render::to_element_container,
-    renderer::renderer::Renderer,
-    style::style::to_styled_node,
+    renderer::renderer::{Renderer, RendererOptions, ResourceLimits},
+    style::{style::to_styled_node, text_model},
 };
 
 const HTML: &str = r#"<body>
@@ -28,56 +35,182 @@ const HTML: &str = r#"<body>
 
     <div id="result">
         <p>not loaded</p>
-    </div
+    </div>
     <script>
-        document.getElementById("result").innerHTML = `\x3cp\x3eloaded\x3c/p\x3e`
+        document.getElementById("result").innerHTML = `<p>loaded</p>`
     </script> 
 </body>"#;
 
 const DEFAULT_STYLESHEET: &str = r#"
-script, style {
+head, script, style {
     display: none;
 }
-p, div {
+html, body, p, div {
     display: block;
 }
 "#;
 
-fn collect_tag_inners(node: &Box<Node>, tag_name: &str) -> Vec<String> {
-    if let NodeType::Element(ref element) = node.node_type {
-        if element.tag_name.as_str() == tag_name {
-            return vec![node.inner_text()];
+/// Read the config file, then apply `--key value` flags over it, getting the config
+/// and the path of the document to open, if one is given
+///
+/// `--config PATH` reads the file at `PATH` instead of the default one, and
+/// `--dump-dom`, `--dump-text` and `--lint`, which take no value, are left for `main`
+/// to handle.
+fn load_config() -> (Config, Option<PathBuf>) {
+    let mut args = env::args().skip(1);
+    let mut flags = vec![];
+    let mut path = default_path();
+    let mut explicit = false;
+    let mut document = None;
+    while let Some(arg) = args.next() {
+        if arg == "--dump-dom" || arg == "--dump-text" || arg == "--lint" {
+            continue;
+        }
+        let Some(key) = arg.strip_prefix("--") else {
+            if document.is_some() {
+                eprintln!("warning: unexpected argument {}", arg);
+            } else {
+                document = Some(PathBuf::from(arg));
+            }
+            continue;
+        };
+        let Some(value) = args.next() else {
+            eprintln!("warning: missing value for --{}", key);
+            break;
+        };
+        if key == "config" {
+            path = Some(PathBuf::from(value));
+            explicit = true;
+        } else {
+            flags.push((key.to_string(), value));
+        }
+    }
+
+    let mut config = match path.map(|path| (fs::read_to_string(&path), path)) {
+        Some((Ok(raw), path)) => {
+            let (config, warnings) = Config::parse(&raw);
+            for warning in warnings {
+                eprintln!("warning: {}: {}", path.display(), warning);
+            }
+            config
+        }
+        Some((Err(error), path)) if explicit => {
+            eprintln!("warning: can't read {}: {}", path.display(), error);
+            Config::default()
+        }
+        _ => Config::default(),
+    };
+    for (key, value) in flags {
+        if let Err(warning) = config.set(&key, &value) {
+            eprintln!("warning: --{}: {}", key, warning);
         }
     }
-    node.children
-        .iter()
-        .map(|child| collect_tag_inners(child, tag_name))
-        .collect::<Vec<Vec<String>>>()
-        .into_iter()
-        .flatten()
-        .collect()
+    (config, document)
+}
+
+/// Load the file at `path` as the document, recording the request in `log`, getting
+/// the URL it was loaded from and its markup
+fn load_document(
+    path: &Path,
+    limits: &ResourceLimits,
+    log: &RequestLog,
+) -> Result<(Url, String), LoadError> {
+    let path = fs::canonicalize(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+    let url = Url::parse(&format!("file://{}", path.display()))?;
+    let loader = LoggingLoader::new(limits.document_loader(FileLoader), log.clone());
+    let response = loader.load("GET", &url)?;
+    Ok((url, String::from_utf8_lossy(&response.body).into_owned()))
 }
 
 fn main() {
-    let mut siv = cursive::default();
+    let (config, document) = load_config();
+    let mut options = RendererOptions::default();
+    config.apply(&mut options);
+    options.lint = env::args().any(|arg| arg == "--lint");
+    if let Some(path) = &config.user_stylesheet {
+        match fs::read_to_string(path) {
+            Ok(css) => options.user_stylesheet = Some(css),
+            Err(error) => eprintln!("warning: can't read {}: {}", path.display(), error),
+        }
+    }
+
+    let log = RequestLog::default();
+    let (url, html) = match &document {
+        Some(path) => match load_document(path, &options.limits, &log) {
+            Ok((url, html)) => (Some(url), html),
+            Err(error) => {
+                eprintln!("error: {}", error);
+                return;
+            }
+        },
+        None => (None, HTML.to_string()),
+    };
+    let (node, _) = parse_with_options(&html, options.limits.parse_options());
+    if env::args().any(|arg| arg == "--dump-dom") {
+        println!("{}", node.to_tree_string());
+        return;
+    }
 
-    let node = parse(HTML);
     let stylesheet = css::parse(&format!(
-        "{}\n{}",
+        "{}\n{}\n{}",
         DEFAULT_STYLESHEET,
-        collect_tag_inners(&node, "style".into()).join("\n")
+        options.user_stylesheet.as_deref().unwrap_or(""),
+        node.head()
+            .unwrap_or(&node)
+            .get_elements_by_tag_name("style")
+            .iter()
+            .map(|style| style.inner_text())
+            .collect::<Vec<_>>()
+            .join("\n")
     ));
 
+    if env::args().any(|arg| arg == "--dump-text") {
+        if let Some(styled_node) = to_styled_node(&node, &stylesheet) {
+            print!(
+                "{}",
+                text_model::to_plain_text(&text_model::extract(&styled_node))
+            );
+        }
+        return;
+    }
+
+    let mut siv = cursive::default();
+
     let container = to_styled_node(&node, &stylesheet)
         .and_then(|styled_node| Some(to_layout_box(styled_node)))
-        .and_then(|layout_box| Some(to_element_container(layout_box)));
+        .and_then(|layout_box| Some(to_element_container(layout_box, &node)));
     if let Some(c) = container {
         siv.add_fullscreen_layer(c);
     }
 
-    let mut renderer = Renderer::new(Rc::new(siv.cb_sink().clone()), node);
-    renderer.execute_inline_scripts();
+    siv.set_theme(options.theme.to_cursive_theme());
+
+    let mut renderer = match Renderer::with_options(Rc::new(siv.cb_sink().clone()), node, options) {
+        Ok(renderer) => renderer,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            return;
+        }
+    };
+    renderer.set_source(&html);
+    renderer.set_request_log(log);
+    if let Some(url) = url {
+        renderer.set_loader(FileLoader, url);
+    }
+    for (from, to) in &config.key_bindings {
+        if let Err(warning) = renderer.key_registry_mut().rebind(from, to.clone()) {
+            eprintln!("warning: {}", warning);
+        }
+    }
+    if config.javascript != Some(false) {
+        renderer.execute_inline_scripts();
+    }
+    // Printed once the terminal is given back, as the screen would hide it before
+    let lint_warnings = renderer.lint_warnings().to_vec();
     siv.add_fullscreen_layer(renderer);
 
     siv.run();
+    for warning in lint_warnings {
+        eprintln!("lint: {}", warning);
+    }
 }
@@ -0,0 +1,543 @@
+use std::{
+    fs,
+    io::{self, Cursor, Read},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::error::error::LoadError;
+
+use super::url::Url;
+
+/// A response to a request for a resource
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Response {
+    pub status: u16,
+    /// Header names and values, in the order they were received
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// Whether the response was served from a cache instead of the network
+    pub from_cache: bool,
+}
+
+/// A response whose body is still to be read
+pub struct ResponseStream {
+    pub status: u16,
+    /// Header names and values, in the order they were received
+    pub headers: Vec<(String, String)>,
+    pub body: Box<dyn Read>,
+    /// Whether the response was served from a cache instead of the network
+    pub from_cache: bool,
+}
+
+impl ResponseStream {
+    /// Get the length of the body its `content-length` header declares, if any
+    pub fn content_length(&self) -> Option<usize> {
+        self.headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+            .and_then(|(_, value)| value.trim().parse().ok())
+    }
+}
+
+impl From<Response> for ResponseStream {
+    fn from(response: Response) -> Self {
+        ResponseStream {
+            status: response.status,
+            headers: response.headers,
+            body: Box::new(Cursor::new(response.body)),
+            from_cache: response.from_cache,
+        }
+    }
+}
+
+/// Fetches the documents and subresources the browser loads
+pub trait ResourceLoader {
+    /// Start a request, getting the response as soon as its headers are in
+    fn fetch(&self, method: &str, url: &Url) -> Result<ResponseStream, LoadError>;
+
+    /// Make a request and read the whole body of the response
+    fn load(&self, method: &str, url: &Url) -> Result<Response, LoadError> {
+        let mut response = self.fetch(method, url)?;
+        let mut body = vec![];
+        response
+            .body
+            .read_to_end(&mut body)
+            .map_err(|error| read_error(url, error))?;
+        Ok(Response {
+            status: response.status,
+            headers: response.headers,
+            body,
+            from_cache: response.from_cache,
+        })
+    }
+}
+
+impl<L: ResourceLoader + ?Sized> ResourceLoader for &L {
+    fn fetch(&self, method: &str, url: &Url) -> Result<ResponseStream, LoadError> {
+        (**self).fetch(method, url)
+    }
+
+    fn load(&self, method: &str, url: &Url) -> Result<Response, LoadError> {
+        (**self).load(method, url)
+    }
+}
+
+/// Get the load error a body failed to be read with, or else describe `error`
+fn read_error(url: &Url, error: io::Error) -> LoadError {
+    let message = format!("{}: {}", url, error);
+    match error
+        .into_inner()
+        .map(|inner| inner.downcast::<LoadError>())
+    {
+        Some(Ok(error)) => *error,
+        _ => LoadError::Failed(message),
+    }
+}
+
+/// What the network log keeps of a request
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestRecord {
+    pub method: String,
+    pub url: Url,
+    /// Status of the response, or `None` when the request failed
+    pub status: Option<u16>,
+    /// Length of the response body in bytes
+    pub size: usize,
+    pub duration: Duration,
+    pub cache_hit: bool,
+    pub headers: Vec<(String, String)>,
+    /// Why the request failed
+    pub error: Option<String>,
+}
+
+impl RequestRecord {
+    /// One line describing the request, as listed in the network panel
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use tiny_browserbook::net::loader::RequestRecord;
+    /// use tiny_browserbook::net::url::Url;
+    /// let record = RequestRecord {
+    ///     method: "GET".to_string(),
+    ///     url: Url::parse("https://example.com/style.css").unwrap(),
+    ///     status: Some(200),
+    ///     size: 512,
+    ///     duration: Duration::from_millis(12),
+    ///     cache_hit: true,
+    ///     headers: vec![],
+    ///     error: None,
+    /// };
+    /// assert_eq!(record.summary(), "GET 200 https://example.com/style.css 512 B 12 ms (cache)");
+    /// ```
+    pub fn summary(&self) -> String {
+        let status = match self.status {
+            Some(status) => status.to_string(),
+            None => "ERR".to_string(),
+        };
+        format!(
+            "{} {} {} {} B {} ms{}",
+            self.method,
+            status,
+            self.url,
+            self.size,
+            self.duration.as_millis(),
+            if self.cache_hit { " (cache)" } else { "" }
+        )
+    }
+}
+
+/// The requests made for the current document, in the order they were made
+pub type RequestLog = Arc<Mutex<Vec<RequestRecord>>>;
+
+/// Records every request made through `inner` in a request log
+pub struct LoggingLoader<L: ResourceLoader> {
+    inner: L,
+    log: RequestLog,
+}
+
+impl<L: ResourceLoader> LoggingLoader<L> {
+    pub fn new(inner: L, log: RequestLog) -> Self {
+        LoggingLoader { inner, log }
+    }
+
+    pub fn log(&self) -> &RequestLog {
+        &self.log
+    }
+}
+
+impl<L: ResourceLoader> ResourceLoader for LoggingLoader<L> {
+    fn fetch(&self, method: &str, url: &Url) -> Result<ResponseStream, LoadError> {
+        // The body is read whole for its size to be logged
+        self.load(method, url).map(ResponseStream::from)
+    }
+
+    fn load(&self, method: &str, url: &Url) -> Result<Response, LoadError> {
+        let start = Instant::now();
+        let result = self.inner.load(method, url);
+        let (status, size, cache_hit, headers, error) = match &result {
+            Ok(response) => (
+                Some(response.status),
+                response.body.len(),
+                response.from_cache,
+                response.headers.clone(),
+                None,
+            ),
+            Err(error) => (None, 0, false, vec![], Some(error.to_string())),
+        };
+        self.log.lock().unwrap().push(RequestRecord {
+            method: method.to_string(),
+            url: url.clone(),
+            status,
+            size,
+            duration: start.elapsed(),
+            cache_hit,
+            headers,
+            error,
+        });
+        result
+    }
+}
+
+/// Refuses the responses of `inner` larger than `max_bytes`, by their `content-length`
+/// header before any of the body is read, or else once more of the body is read
+pub struct LimitedLoader<L: ResourceLoader> {
+    inner: L,
+    max_bytes: usize,
+}
+
+impl<L: ResourceLoader> LimitedLoader<L> {
+    pub fn new(inner: L, max_bytes: usize) -> Self {
+        LimitedLoader { inner, max_bytes }
+    }
+}
+
+impl<L: ResourceLoader> ResourceLoader for LimitedLoader<L> {
+    fn fetch(&self, method: &str, url: &Url) -> Result<ResponseStream, LoadError> {
+        let mut response = self.inner.fetch(method, url)?;
+        if let Some(size) = response.content_length() {
+            if size > self.max_bytes {
+                return Err(LoadError::TooLarge {
+                    size,
+                    limit: self.max_bytes,
+                });
+            }
+        }
+        response.body = Box::new(LimitedBody {
+            inner: response.body,
+            read: 0,
+            limit: self.max_bytes,
+        });
+        Ok(response)
+    }
+}
+
+/// Reads a body up to one byte past `limit`, then fails with `LoadError::TooLarge`
+struct LimitedBody {
+    inner: Box<dyn Read>,
+    read: usize,
+    limit: usize,
+}
+
+impl Read for LimitedBody {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let room = (self.limit + 1 - self.read).min(buf.len());
+        let read = self.inner.read(&mut buf[..room])?;
+        self.read += read;
+        if self.read > self.limit {
+            return Err(io::Error::other(LoadError::TooLarge {
+                size: self.read,
+                limit: self.limit,
+            }));
+        }
+        Ok(read)
+    }
+}
+
+/// Reads `file:` URLs from the local file system
+pub struct FileLoader;
+
+impl ResourceLoader for FileLoader {
+    fn fetch(&self, _method: &str, url: &Url) -> Result<ResponseStream, LoadError> {
+        if url.scheme != "file" {
+            return Err(format!("{} is not a file url", url).into());
+        }
+        let open = |path: &str| {
+            let file = fs::File::open(path)?;
+            let length = file.metadata()?.len();
+            Ok::<_, io::Error>((file, length))
+        };
+        let (file, length) = open(&url.path).map_err(|error| format!("{}: {}", url, error))?;
+        Ok(ResponseStream {
+            status: 200,
+            headers: vec![("content-length".to_string(), length.to_string())],
+            body: Box::new(file),
+            from_cache: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, collections::HashMap, env, process};
+
+    use crate::{html::html::parse, renderer::renderer::ResourceLimits};
+
+    use super::*;
+
+    /// Serves fixed bodies, from its cache after the first request for a URL
+    struct MockLoader {
+        resources: HashMap<String, &'static str>,
+        cached: RefCell<Vec<String>>,
+    }
+
+    impl MockLoader {
+        fn new(resources: &[(&str, &'static str)]) -> Self {
+            MockLoader {
+                resources: resources
+                    .iter()
+                    .map(|(url, body)| (url.to_string(), *body))
+                    .collect(),
+                cached: RefCell::new(vec![]),
+            }
+        }
+    }
+
+    impl ResourceLoader for MockLoader {
+        fn fetch(&self, _method: &str, url: &Url) -> Result<ResponseStream, LoadError> {
+            let url = url.to_string();
+            let body = self
+                .resources
+                .get(&url)
+                .ok_or_else(|| format!("connection refused: {}", url))?;
+            let from_cache = self.cached.borrow().contains(&url);
+            self.cached.borrow_mut().push(url);
+            Ok(Response {
+                status: 200,
+                headers: vec![("content-length".to_string(), body.len().to_string())],
+                body: body.as_bytes().to_vec(),
+                from_cache,
+            }
+            .into())
+        }
+    }
+
+    #[test]
+    fn test_logs_document_and_subresources() {
+        let log = RequestLog::default();
+        let loader = LoggingLoader::new(
+            MockLoader::new(&[
+                (
+                    "https://example.com/",
+                    r#"<head><link rel="stylesheet" href="a.css"><script src="/a.js"></script><link rel="stylesheet" href="a.css"></head>"#,
+                ),
+                ("https://example.com/a.css", "p {}"),
+                ("https://example.com/a.js", "let a = 1;"),
+            ]),
+            log.clone(),
+        );
+        let base = Url::parse("https://example.com/").unwrap();
+        let body = loader.load("GET", &base).unwrap().body;
+        let document = parse(&String::from_utf8(body).unwrap());
+        for node in document.query_selector_all("link, script") {
+            let href = node.get_attribute("href").or(node.get_attribute("src"));
+            loader
+                .load("GET", &base.join(href.unwrap()).unwrap())
+                .unwrap();
+        }
+        assert!(loader
+            .load("GET", &base.join("missing.png").unwrap())
+            .is_err());
+
+        let log = log.lock().unwrap();
+        assert_eq!(
+            log.iter()
+                .map(|r| (r.url.to_string(), r.status, r.size, r.cache_hit))
+                .collect::<Vec<_>>(),
+            vec![
+                ("https://example.com/".to_string(), Some(200), 114, false),
+                ("https://example.com/a.css".to_string(), Some(200), 4, false),
+                ("https://example.com/a.js".to_string(), Some(200), 10, false),
+                ("https://example.com/a.css".to_string(), Some(200), 4, true),
+                (
+                    "https://example.com/missing.png".to_string(),
+                    None,
+                    0,
+                    false
+                ),
+            ]
+        );
+        assert_eq!(
+            log[1].headers,
+            vec![("content-length".to_string(), "4".to_string())]
+        );
+        assert_eq!(
+            log[4].error.as_deref(),
+            Some("connection refused: https://example.com/missing.png")
+        );
+    }
+
+    #[test]
+    fn test_limited_loader() {
+        let log = RequestLog::default();
+        let loader = LoggingLoader::new(
+            LimitedLoader::new(
+                MockLoader::new(&[
+                    ("https://example.com/small", "12345"),
+                    ("https://example.com/large", "123456"),
+                ]),
+                5,
+            ),
+            log.clone(),
+        );
+        let url = |path: &str| Url::parse(&format!("https://example.com/{}", path)).unwrap();
+        assert_eq!(loader.load("GET", &url("small")).unwrap().body, b"12345");
+        assert_eq!(
+            loader.load("GET", &url("large")),
+            Err(LoadError::TooLarge { size: 6, limit: 5 })
+        );
+        assert_eq!(
+            loader.load("GET", &url("missing")),
+            Err(LoadError::Failed(
+                "connection refused: https://example.com/missing".to_string()
+            ))
+        );
+        assert_eq!(
+            log.lock().unwrap()[1].error.as_deref(),
+            Some("resource of 6 bytes is over the limit of 5 bytes")
+        );
+    }
+
+    #[test]
+    fn test_resource_limits() {
+        let resources = [
+            (
+                "https://example.com/",
+                "<p>a</p><script src=\"a.js\"></script>",
+            ),
+            ("https://example.com/a.css", "p {}"),
+            ("https://example.com/a.js", "let a = 1;"),
+        ];
+        let limits = ResourceLimits {
+            max_document_bytes: 32,
+            max_subresource_bytes: 4,
+            ..ResourceLimits::default()
+        };
+        let documents = limits.document_loader(MockLoader::new(&resources));
+        let subresources = limits.subresource_loader(MockLoader::new(&resources));
+        let url = |path: &str| Url::parse(&format!("https://example.com/{}", path)).unwrap();
+
+        assert_eq!(
+            documents.load("GET", &url("")),
+            Err(LoadError::TooLarge {
+                size: 36,
+                limit: 32
+            })
+        );
+        assert!(documents.load("GET", &url("a.js")).is_ok());
+        assert_eq!(
+            subresources.load("GET", &url("a.css")).unwrap().body,
+            b"p {}"
+        );
+        assert_eq!(
+            subresources.load("GET", &url("a.js")),
+            Err(LoadError::TooLarge { size: 10, limit: 4 })
+        );
+    }
+
+    /// Declares a length without sending a body
+    struct Declaring(&'static str);
+
+    impl ResourceLoader for Declaring {
+        fn fetch(&self, _method: &str, _url: &Url) -> Result<ResponseStream, LoadError> {
+            Ok(Response {
+                status: 200,
+                headers: vec![("Content-Length".to_string(), self.0.to_string())],
+                ..Response::default()
+            }
+            .into())
+        }
+    }
+
+    /// Sends a body that never ends, declaring the length given if any
+    struct Endless(Option<&'static str>);
+
+    impl ResourceLoader for Endless {
+        fn fetch(&self, _method: &str, _url: &Url) -> Result<ResponseStream, LoadError> {
+            Ok(ResponseStream {
+                status: 200,
+                headers: self
+                    .0
+                    .map(|length| ("content-length".to_string(), length.to_string()))
+                    .into_iter()
+                    .collect(),
+                body: Box::new(io::repeat(b'a')),
+                from_cache: false,
+            })
+        }
+    }
+
+    #[test]
+    fn test_limited_loader_caps_read() {
+        let url = Url::parse("https://example.com/").unwrap();
+        assert_eq!(
+            LimitedLoader::new(Endless(None), 1024).load("GET", &url),
+            Err(LoadError::TooLarge {
+                size: 1025,
+                limit: 1024
+            })
+        );
+        assert_eq!(
+            LimitedLoader::new(Endless(Some("1073741824")), 1024).load("GET", &url),
+            Err(LoadError::TooLarge {
+                size: 1073741824,
+                limit: 1024
+            })
+        );
+        let mut body = LimitedLoader::new(Endless(None), 4)
+            .fetch("GET", &url)
+            .unwrap()
+            .body;
+        let mut buf = [0; 8];
+        assert_eq!(body.read(&mut buf[..3]).unwrap(), 3);
+        assert_eq!(body.read(&mut buf[..1]).unwrap(), 1);
+        assert!(body.read(&mut buf).is_err());
+    }
+
+    #[test]
+    fn test_limited_loader_content_length() {
+        let url = Url::parse("https://example.com/").unwrap();
+        assert_eq!(
+            LimitedLoader::new(Declaring("1073741824"), 1024).load("GET", &url),
+            Err(LoadError::TooLarge {
+                size: 1073741824,
+                limit: 1024
+            })
+        );
+        assert!(LimitedLoader::new(Declaring("1024"), 1024)
+            .load("GET", &url)
+            .is_ok());
+        assert!(LimitedLoader::new(Declaring("unknown"), 1024)
+            .load("GET", &url)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_file_loader() {
+        let path = env::temp_dir().join(format!("tiny-browserbook-{}.html", process::id()));
+        fs::write(&path, "<p>local</p>").unwrap();
+        let url = Url::parse(&format!("file://{}", path.display())).unwrap();
+        let response = FileLoader.load("GET", &url);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(response.unwrap().body, b"<p>local</p>");
+
+        assert!(FileLoader.load("GET", &url).is_err());
+        let url = Url::parse("https://example.com/").unwrap();
+        assert_eq!(
+            FileLoader.load("GET", &url),
+            Err(LoadError::Failed(
+                "https://example.com/ is not a file url".to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,353 @@
+use std::fmt;
+
+/// Schemes whose URLs always have a host
+const HOST_SCHEMES: [&str; 2] = ["http", "https"];
+
+/// An absolute URL
+///
+/// URLs with an authority (`http:`, `https:`, `file:`) have a host and a
+/// path starting with `/`. Others, like `data:`, only have an opaque path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Url {
+    pub scheme: String,
+    pub host: Option<String>,
+    /// Explicit port, `None` when it is the scheme's default
+    pub port: Option<u16>,
+    pub path: String,
+    pub query: Option<String>,
+    pub fragment: Option<String>,
+}
+
+impl Url {
+    /// Parse an absolute URL
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::net::url::Url;
+    /// let url = Url::parse("HTTP://Example.com:8080/a/./b?q#top").unwrap();
+    /// assert_eq!(url.host.as_deref(), Some("example.com"));
+    /// assert_eq!(url.port, Some(8080));
+    /// assert_eq!(url.to_string(), "http://example.com:8080/a/b?q#top");
+    /// ```
+    pub fn parse(raw: &str) -> Result<Url, String> {
+        let raw = raw.trim();
+        let (scheme, rest) =
+            split_scheme(raw).ok_or_else(|| format!("{} is not an absolute url", raw))?;
+        let (rest, query, fragment) = split_suffixes(rest);
+
+        let (host, port, path) = match rest.strip_prefix("//") {
+            Some(rest) => {
+                let end = rest.find('/').unwrap_or(rest.len());
+                let (host, port) = parse_authority(&rest[..end], &scheme)?;
+                (Some(host), port, remove_dot_segments(&rest[end..]))
+            }
+            None => (None, None, rest.to_string()),
+        };
+        if HOST_SCHEMES.contains(&scheme.as_str()) && host.as_deref().unwrap_or("").is_empty() {
+            return Err(format!("{} has no host", raw));
+        }
+
+        Ok(Url {
+            scheme,
+            host,
+            port,
+            path,
+            query,
+            fragment,
+        })
+    }
+
+    /// Resolve a possibly relative reference against this URL
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::net::url::Url;
+    /// let base = Url::parse("https://example.com/docs/guide/intro.html").unwrap();
+    /// assert_eq!(
+    ///     base.join("../api/index.html").unwrap().to_string(),
+    ///     "https://example.com/docs/api/index.html"
+    /// );
+    /// assert_eq!(base.join("#setup").unwrap().fragment.as_deref(), Some("setup"));
+    /// ```
+    pub fn join(&self, relative: &str) -> Result<Url, String> {
+        let relative = relative.trim();
+        if split_scheme(relative).is_some() {
+            return Url::parse(relative);
+        }
+        if let Some(fragment) = relative.strip_prefix('#') {
+            return Ok(Url {
+                fragment: Some(fragment.to_string()),
+                ..self.clone()
+            });
+        }
+        if self.host.is_none() {
+            return Err(format!("cannot resolve {} against {}", relative, self));
+        }
+        if relative.starts_with("//") {
+            return Url::parse(&format!("{}:{}", self.scheme, relative));
+        }
+
+        let (path, query, fragment) = split_suffixes(relative);
+        let (path, query) = if path.is_empty() {
+            (self.path.clone(), query.or_else(|| self.query.clone()))
+        } else if path.starts_with('/') {
+            (remove_dot_segments(path), query)
+        } else {
+            let dir = &self.path[..self.path.rfind('/').map_or(0, |i| i + 1)];
+            (remove_dot_segments(&format!("{}{}", dir, path)), query)
+        };
+
+        Ok(Url {
+            path,
+            query,
+            fragment,
+            ..self.clone()
+        })
+    }
+}
+
+impl fmt::Display for Url {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.scheme)?;
+        if let Some(host) = &self.host {
+            write!(f, "//{}", host)?;
+        }
+        if let Some(port) = self.port {
+            write!(f, ":{}", port)?;
+        }
+        write!(f, "{}", self.path)?;
+        if let Some(query) = &self.query {
+            write!(f, "?{}", query)?;
+        }
+        if let Some(fragment) = &self.fragment {
+            write!(f, "#{}", fragment)?;
+        }
+        Ok(())
+    }
+}
+
+/// Split `scheme:rest`, lowercasing the scheme
+fn split_scheme(raw: &str) -> Option<(String, &str)> {
+    let (scheme, rest) = raw.split_once(':')?;
+    let mut chars = scheme.chars();
+    let valid = chars.next()?.is_ascii_alphabetic()
+        && chars.all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c));
+    valid.then(|| (scheme.to_ascii_lowercase(), rest))
+}
+
+/// Split off the query and fragment, without their `?` and `#`
+fn split_suffixes(raw: &str) -> (&str, Option<String>, Option<String>) {
+    let (rest, fragment) = match raw.split_once('#') {
+        Some((rest, fragment)) => (rest, Some(fragment.to_string())),
+        None => (raw, None),
+    };
+    match rest.split_once('?') {
+        Some((path, query)) => (path, Some(query.to_string()), fragment),
+        None => (rest, None, fragment),
+    }
+}
+
+fn default_port(scheme: &str) -> Option<u16> {
+    match scheme {
+        "http" => Some(80),
+        "https" => Some(443),
+        _ => None,
+    }
+}
+
+fn parse_authority(authority: &str, scheme: &str) -> Result<(String, Option<u16>), String> {
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => {
+            let port = match port {
+                "" => None,
+                port => Some(
+                    port.parse::<u16>()
+                        .map_err(|_| format!("invalid port {}", port))?,
+                ),
+            };
+            (host, port)
+        }
+        _ => (authority, None),
+    };
+    let port = port.filter(|&p| Some(p) != default_port(scheme));
+    Ok((host.to_ascii_lowercase(), port))
+}
+
+/// Resolve `.` and `..` segments of an absolute path
+fn remove_dot_segments(path: &str) -> String {
+    let segments = path.split('/').skip(1).collect::<Vec<_>>();
+    let mut out = vec![];
+    for (i, segment) in segments.iter().enumerate() {
+        let last = i + 1 == segments.len();
+        match *segment {
+            "." => {}
+            ".." => {
+                out.pop();
+            }
+            segment => {
+                out.push(segment);
+                continue;
+            }
+        }
+        if last {
+            out.push("");
+        }
+    }
+    format!("/{}", out.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Url::parse("https://Example.COM:8443/a/b?x=1&y=2#frag"),
+            Ok(Url {
+                scheme: "https".to_string(),
+                host: Some("example.com".to_string()),
+                port: Some(8443),
+                path: "/a/b".to_string(),
+                query: Some("x=1&y=2".to_string()),
+                fragment: Some("frag".to_string()),
+            })
+        );
+    }
+
+    #[rstest]
+    #[case("http://example.com", "http://example.com/")]
+    #[case("http://example.com:80/", "http://example.com/")]
+    #[case("https://example.com:443/", "https://example.com/")]
+    #[case("http://example.com:443/", "http://example.com:443/")]
+    #[case("http://example.com/a/../b/./c", "http://example.com/b/c")]
+    #[case("http://example.com/?", "http://example.com/?")]
+    #[case("  http://example.com/#  ", "http://example.com/#")]
+    #[case("file:///home/user/index.html", "file:///home/user/index.html")]
+    #[case("file://localhost/etc/hosts", "file://localhost/etc/hosts")]
+    #[case("data:text/html,<p>hi</p>", "data:text/html,<p>hi</p>")]
+    #[case("DATA:text/plain;base64,aGk=", "data:text/plain;base64,aGk=")]
+    #[case("mailto:someone@example.com", "mailto:someone@example.com")]
+    fn test_parse_display(#[case] raw: &str, #[case] expected: &str) {
+        assert_eq!(Url::parse(raw).unwrap().to_string(), expected);
+    }
+
+    #[test]
+    fn test_parse_opaque() {
+        let url = Url::parse("data:text/html,<p>hi</p>#x").unwrap();
+        assert_eq!(url.host, None);
+        assert_eq!(url.path, "text/html,<p>hi</p>");
+        assert_eq!(url.fragment.as_deref(), Some("x"));
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("example.com/index.html")]
+    #[case("/index.html")]
+    #[case("1http://example.com/")]
+    #[case("http://")]
+    #[case("https:example.com")]
+    #[case("http://example.com:http/")]
+    #[case("http://example.com:65536/")]
+    fn test_parse_error(#[case] raw: &str) {
+        assert!(Url::parse(raw).is_err(), "{raw:?}");
+    }
+
+    // RFC 3986 section 5.4, except that an empty http path is "/"
+    #[rstest]
+    #[case("g:h", "g:h")]
+    #[case("g", "http://a/b/c/g")]
+    #[case("./g", "http://a/b/c/g")]
+    #[case("g/", "http://a/b/c/g/")]
+    #[case("/g", "http://a/g")]
+    #[case("//g", "http://g/")]
+    #[case("?y", "http://a/b/c/d;p?y")]
+    #[case("g?y", "http://a/b/c/g?y")]
+    #[case("#s", "http://a/b/c/d;p?q#s")]
+    #[case("g#s", "http://a/b/c/g#s")]
+    #[case("g?y#s", "http://a/b/c/g?y#s")]
+    #[case(";x", "http://a/b/c/;x")]
+    #[case("g;x", "http://a/b/c/g;x")]
+    #[case("g;x?y#s", "http://a/b/c/g;x?y#s")]
+    #[case("", "http://a/b/c/d;p?q")]
+    #[case(".", "http://a/b/c/")]
+    #[case("./", "http://a/b/c/")]
+    #[case("..", "http://a/b/")]
+    #[case("../", "http://a/b/")]
+    #[case("../g", "http://a/b/g")]
+    #[case("../..", "http://a/")]
+    #[case("../../", "http://a/")]
+    #[case("../../g", "http://a/g")]
+    #[case("../../../g", "http://a/g")]
+    #[case("../../../../g", "http://a/g")]
+    #[case("/./g", "http://a/g")]
+    #[case("/../g", "http://a/g")]
+    #[case("g.", "http://a/b/c/g.")]
+    #[case(".g", "http://a/b/c/.g")]
+    #[case("g..", "http://a/b/c/g..")]
+    #[case("..g", "http://a/b/c/..g")]
+    #[case("./../g", "http://a/b/g")]
+    #[case("./g/.", "http://a/b/c/g/")]
+    #[case("g/./h", "http://a/b/c/g/h")]
+    #[case("g/../h", "http://a/b/c/h")]
+    #[case("g;x=1/./y", "http://a/b/c/g;x=1/y")]
+    #[case("g;x=1/../y", "http://a/b/c/y")]
+    #[case("g?y/./x", "http://a/b/c/g?y/./x")]
+    #[case("g?y/../x", "http://a/b/c/g?y/../x")]
+    #[case("g#s/./x", "http://a/b/c/g#s/./x")]
+    #[case("g#s/../x", "http://a/b/c/g#s/../x")]
+    fn test_join_rfc3986(#[case] relative: &str, #[case] expected: &str) {
+        let base = Url::parse("http://a/b/c/d;p?q").unwrap();
+        assert_eq!(
+            base.join(relative).unwrap().to_string(),
+            expected,
+            "{relative:?}"
+        );
+    }
+
+    #[rstest]
+    #[case(
+        "https://example.com:8443/a/b",
+        "//cdn.example.com/x.js",
+        "https://cdn.example.com/x.js"
+    )]
+    #[case("https://example.com:8443/a/b", "/c", "https://example.com:8443/c")]
+    #[case("https://example.com/a/b#top", "", "https://example.com/a/b")]
+    #[case("https://example.com/a/b#top", "#end", "https://example.com/a/b#end")]
+    #[case("https://example.com/a/b?q=1", "?q=2", "https://example.com/a/b?q=2")]
+    #[case("https://example.com", "page.html", "https://example.com/page.html")]
+    #[case(
+        "https://example.com/dir/",
+        "page.html",
+        "https://example.com/dir/page.html"
+    )]
+    #[case("https://example.com/a", "HTTP://other.org", "http://other.org/")]
+    #[case(
+        "file:///home/user/index.html",
+        "style.css",
+        "file:///home/user/style.css"
+    )]
+    #[case("file:///home/user/index.html", "../../etc/hosts", "file:///etc/hosts")]
+    #[case(
+        "file:///home/user/index.html",
+        "//server/share",
+        "file://server/share"
+    )]
+    #[case("data:text/html,hi", "#x", "data:text/html,hi#x")]
+    #[case("data:text/html,hi", "https://example.com/", "https://example.com/")]
+    fn test_join(#[case] base: &str, #[case] relative: &str, #[case] expected: &str) {
+        let base = Url::parse(base).unwrap();
+        assert_eq!(base.join(relative).unwrap().to_string(), expected);
+    }
+
+    #[rstest]
+    #[case("data:text/html,hi", "page.html")]
+    #[case("data:text/html,hi", "/page.html")]
+    #[case("mailto:someone@example.com", "?subject=x")]
+    #[case("https://example.com/", "http://")]
+    #[case("http://a/b/c/d;p?q", "http:g")]
+    fn test_join_error(#[case] base: &str, #[case] relative: &str) {
+        let base = Url::parse(base).unwrap();
+        assert!(base.join(relative).is_err(), "{relative:?}");
+    }
+}
@@ -1 +1,6 @@
+pub mod diff;
+pub mod network;
 pub mod render;
+pub mod source;
+pub mod theme;
+pub mod wrap;
@@ -0,0 +1,199 @@
+use cursive::{
+    theme::{BaseColor, Color},
+    utils::markup::StyledString,
+    view::{Nameable, Scrollable},
+    views::{Dialog, OnEventView, TextView},
+    View,
+};
+
+/// Name of the diff panel layer
+pub const DIFF_PANEL: &str = "diff";
+
+/// A line of the text compared, and on which side of the comparison it is
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffLine {
+    Same(String),
+    Added(String),
+    Removed(String),
+}
+
+impl DiffLine {
+    /// The line prefixed as in a unified diff
+    pub fn to_unified(&self) -> String {
+        match self {
+            DiffLine::Same(line) => format!(" {}", line),
+            DiffLine::Added(line) => format!("+{}", line),
+            DiffLine::Removed(line) => format!("-{}", line),
+        }
+    }
+}
+
+/// Compare `before` and `after` line by line, keeping their longest common
+/// subsequence of lines and marking the rest as removed or added
+///
+/// Where both sides changed, the removed lines come before the added ones.
+/// # Example
+/// ```
+/// use tiny_browserbook::render::diff::{diff_lines, DiffLine};
+/// assert_eq!(
+///     diff_lines("a\nb\nc", "a\nx\nc"),
+///     vec![
+///         DiffLine::Same("a".to_string()),
+///         DiffLine::Removed("b".to_string()),
+///         DiffLine::Added("x".to_string()),
+///         DiffLine::Same("c".to_string()),
+///     ]
+/// );
+/// ```
+pub fn diff_lines(before: &str, after: &str) -> Vec<DiffLine> {
+    let before: Vec<&str> = before.lines().collect();
+    let after: Vec<&str> = after.lines().collect();
+    // common[i][j] is the length of the longest common subsequence of before[i..]
+    // and after[j..]
+    let mut common = vec![vec![0; after.len() + 1]; before.len() + 1];
+    for i in (0..before.len()).rev() {
+        for j in (0..after.len()).rev() {
+            common[i][j] = if before[i] == after[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < before.len() && j < after.len() {
+        if before[i] == after[j] {
+            lines.push(DiffLine::Same(before[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if common[i + 1][j] >= common[i][j + 1] {
+            lines.push(DiffLine::Removed(before[i].to_string()));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(after[j].to_string()));
+            j += 1;
+        }
+    }
+    lines.extend(
+        before[i..]
+            .iter()
+            .map(|line| DiffLine::Removed(line.to_string())),
+    );
+    lines.extend(
+        after[j..]
+            .iter()
+            .map(|line| DiffLine::Added(line.to_string())),
+    );
+    lines
+}
+
+/// Build the panel showing `lines` as a unified diff, with the added lines in green
+/// and the removed ones in red, where `d` closes the panel
+pub fn diff_panel(lines: &[DiffLine]) -> impl View {
+    let content = if lines.iter().all(|line| matches!(line, DiffLine::Same(_))) {
+        StyledString::plain("No changes")
+    } else {
+        let mut content = StyledString::new();
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                content.append_plain("\n");
+            }
+            match line {
+                DiffLine::Same(_) => content.append_plain(line.to_unified()),
+                DiffLine::Added(_) => {
+                    content.append_styled(line.to_unified(), Color::Dark(BaseColor::Green))
+                }
+                DiffLine::Removed(_) => {
+                    content.append_styled(line.to_unified(), Color::Dark(BaseColor::Red))
+                }
+            }
+        }
+        content
+    };
+    OnEventView::new(
+        Dialog::around(TextView::new(content).scrollable())
+            .title("Changes")
+            .dismiss_button("Close"),
+    )
+    .on_event('d', |s| {
+        s.pop_layer();
+    })
+    .with_name(DIFF_PANEL)
+}
+
+#[cfg(test)]
+mod tests {
+    use cursive::{
+        backends::puppet::{observed::ObservedPieceInterface, Backend},
+        Cursive, Vec2,
+    };
+    use rstest::rstest;
+
+    use super::*;
+
+    fn same(line: &str) -> DiffLine {
+        DiffLine::Same(line.to_string())
+    }
+
+    fn added(line: &str) -> DiffLine {
+        DiffLine::Added(line.to_string())
+    }
+
+    fn removed(line: &str) -> DiffLine {
+        DiffLine::Removed(line.to_string())
+    }
+
+    #[rstest]
+    #[case("", "", vec![])]
+    #[case("a\nb", "a\nb", vec![same("a"), same("b")])]
+    #[case("", "a\nb", vec![added("a"), added("b")])]
+    #[case("a\nb", "", vec![removed("a"), removed("b")])]
+    #[case("a\nb\nc", "a\nc", vec![same("a"), removed("b"), same("c")])]
+    #[case("a\nc", "a\nb\nc", vec![same("a"), added("b"), same("c")])]
+    #[case(
+        "up 3\nok\nload 0.5",
+        "up 4\nok\nload 0.5\nwarn",
+        vec![removed("up 3"), added("up 4"), same("ok"), same("load 0.5"), added("warn")]
+    )]
+    #[case(
+        "a\nb\nc\nd",
+        "b\na\nd\nc",
+        vec![removed("a"), same("b"), removed("c"), added("a"), same("d"), added("c")]
+    )]
+    fn test_diff_lines(#[case] before: &str, #[case] after: &str, #[case] expected: Vec<DiffLine>) {
+        assert_eq!(diff_lines(before, after), expected);
+    }
+
+    #[test]
+    fn test_to_unified() {
+        let lines = diff_lines("│ up 3 │\n│ ok   │", "│ up 4 │\n│ ok   │");
+        assert_eq!(
+            lines.iter().map(DiffLine::to_unified).collect::<Vec<_>>(),
+            vec!["-│ up 3 │", "+│ up 4 │", " │ ok   │"]
+        );
+    }
+
+    fn draw(lines: &[DiffLine]) -> Vec<String> {
+        let backend = Backend::init(Some(Vec2::new(30, 10)));
+        let stream = backend.stream();
+        let mut siv = Cursive::new().into_runner(backend);
+        siv.add_layer(diff_panel(lines));
+        siv.refresh();
+        let screen = stream.try_iter().last().unwrap();
+        screen.as_strings()
+    }
+
+    #[test]
+    fn test_diff_panel() {
+        let screen = draw(&diff_lines("a\nb", "a\nc")).join("\n");
+        assert!(screen.contains("Changes"));
+        assert!(screen.contains(" a"));
+        assert!(screen.contains("-b"));
+        assert!(screen.contains("+c"));
+
+        let screen = draw(&diff_lines("a", "a")).join("\n");
+        assert!(screen.contains("No changes"));
+    }
+}
@@ -0,0 +1,138 @@
+use cursive::{
+    view::{IntoBoxedView, Nameable, Scrollable},
+    views::{Dialog, OnEventView, SelectView, TextView},
+    Cursive, View,
+};
+
+use crate::net::loader::{RequestLog, RequestRecord};
+
+/// Name of the network panel layer
+pub const NETWORK_PANEL: &str = "network";
+
+/// Text of the dialog opened for a request: its response headers, or why it failed
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use tiny_browserbook::net::loader::RequestRecord;
+/// use tiny_browserbook::net::url::Url;
+/// use tiny_browserbook::render::network::request_details;
+/// let record = RequestRecord {
+///     method: "GET".to_string(),
+///     url: Url::parse("https://example.com/a.js").unwrap(),
+///     status: Some(200),
+///     size: 10,
+///     duration: Duration::ZERO,
+///     cache_hit: false,
+///     headers: vec![("content-type".to_string(), "text/javascript".to_string())],
+///     error: None,
+/// };
+/// assert_eq!(request_details(&record), "content-type: text/javascript");
+/// ```
+pub fn request_details(record: &RequestRecord) -> String {
+    if let Some(error) = &record.error {
+        return error.clone();
+    }
+    if record.headers.is_empty() {
+        return "No response headers".to_string();
+    }
+    record
+        .headers
+        .iter()
+        .map(|(name, value)| format!("{}: {}", name, value))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Build the panel listing the requests in `log`, where selecting one shows its
+/// response headers and `n` closes the panel
+pub fn network_panel(log: &RequestLog) -> impl View {
+    let records = log.lock().unwrap().clone();
+    let mut list = SelectView::new().on_submit(|s: &mut Cursive, record: &RequestRecord| {
+        s.add_layer(Dialog::info(request_details(record)).title(record.url.to_string()))
+    });
+    for record in records {
+        list.add_item(record.summary(), record);
+    }
+    let content = if list.is_empty() {
+        TextView::new("No requests").into_boxed_view()
+    } else {
+        list.scrollable().into_boxed_view()
+    };
+    OnEventView::new(
+        Dialog::around(content)
+            .title("Network")
+            .dismiss_button("Close"),
+    )
+    .on_event('n', |s| {
+        s.pop_layer();
+    })
+    .with_name(NETWORK_PANEL)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use cursive::{backends::puppet::Backend, event::Event, Vec2};
+
+    use crate::net::url::Url;
+
+    use super::*;
+
+    fn record(path: &str, cache_hit: bool) -> RequestRecord {
+        RequestRecord {
+            method: "GET".to_string(),
+            url: Url::parse("https://example.com/")
+                .unwrap()
+                .join(path)
+                .unwrap(),
+            status: Some(200),
+            size: 4,
+            duration: Duration::from_millis(3),
+            cache_hit,
+            headers: vec![("content-type".to_string(), "text/css".to_string())],
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_network_panel_lists_requests() {
+        let log = RequestLog::default();
+        log.lock().unwrap().push(record("a.css", false));
+        log.lock().unwrap().push(record("a.css", true));
+
+        let backend = Backend::init(Some(Vec2::new(80, 20)));
+        let stream = backend.stream();
+        let input = backend.input();
+        let mut siv = Cursive::new().into_runner(backend);
+        siv.add_layer(network_panel(&log));
+        siv.refresh();
+        let screen = stream.try_iter().last().unwrap();
+        assert_eq!(
+            screen
+                .find_occurences("GET 200 https://example.com/a.css 4 B 3 ms")
+                .len(),
+            2
+        );
+        assert_eq!(screen.find_occurences("(cache)").len(), 1);
+
+        input
+            .send(Some(Event::Key(cursive::event::Key::Enter)))
+            .unwrap();
+        siv.step();
+        siv.refresh();
+        let screen = stream.try_iter().last().unwrap();
+        assert_eq!(screen.find_occurences("content-type: text/css").len(), 1);
+    }
+
+    #[test]
+    fn test_request_details_of_failure() {
+        let mut failed = record("a.css", false);
+        failed.status = None;
+        failed.error = Some("connection refused".to_string());
+        assert_eq!(request_details(&failed), "connection refused");
+        failed.error = None;
+        failed.headers.clear();
+        assert_eq!(request_details(&failed), "No response headers");
+    }
+}
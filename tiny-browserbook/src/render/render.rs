@@ -1,57 +1,891 @@
 use cursive::{
+    backends::puppet::{observed::ObservedPieceInterface, Backend},
+    theme::{Color, Effect, PaletteColor, Style},
     view::{IntoBoxedView, ViewWrapper},
-    views::{DummyView, LinearLayout, Panel, TextView},
-    View,
+    views::{BoxedView, Button, DummyView, EditView, LinearLayout, Panel, ScrollView, TextView},
+    wrap_impl, Printer, Vec2, View,
 };
 
 use crate::{
-    html::dom::NodeType,
-    layout::layout::{BoxProps, BoxType, LayoutBox},
+    css::css::{CSSValue, Stylesheet},
+    html::dom::{Element, Node, NodeType},
+    layout::layout::{to_layout_box, BoxProps, BoxType, LayoutBox},
+    style::style::to_styled_node,
 };
 
+use super::{theme::Theme, wrap::WrappedText};
+
 pub type ElementContainer = Box<dyn View>;
 
+/// Smallest viewport the document is drawn into
+pub const MIN_VIEWPORT_SIZE: Vec2 = Vec2::new(20, 5);
+
+/// Wraps the document view in a scroll view and shows a placeholder instead when
+/// the viewport is smaller than `MIN_VIEWPORT_SIZE`
+///
+/// When the viewport changes width the text re-wraps in place, and the scroll offset
+/// moves to the same fraction of the document so the reader stays about where they were.
+pub struct SizeGuard {
+    view: ScrollView<BoxedView>,
+    placeholder: TextView,
+    too_small: bool,
+    last_size: Option<Vec2>,
+}
+
+impl SizeGuard {
+    pub fn new(view: ElementContainer) -> Self {
+        SizeGuard {
+            view: ScrollView::new(BoxedView::new(view)),
+            placeholder: TextView::new("terminal too small").style(PaletteColor::Secondary),
+            too_small: false,
+            last_size: None,
+        }
+    }
+
+    /// Replace the document view, keeping the scroll offset
+    ///
+    /// The offset is clamped to the new content on the next layout.
+    pub fn set_view(&mut self, view: ElementContainer) {
+        *self.view.get_inner_mut() = BoxedView::new(view);
+    }
+
+    /// Size of the whole document as of the last layout, including what is scrolled out of view
+    pub fn document_size(&self) -> Vec2 {
+        self.view.inner_size()
+    }
+
+    pub fn scroll_offset(&self) -> Vec2 {
+        self.view.content_viewport().top_left()
+    }
+
+    pub fn set_scroll_offset(&mut self, offset: Vec2) {
+        self.view.set_offset(offset);
+    }
+
+    pub fn is_too_small(&self) -> bool {
+        self.too_small
+    }
+
+    /// Size of the viewport as of the last layout the document was drawn in
+    pub fn last_size(&self) -> Option<Vec2> {
+        self.last_size
+    }
+
+    fn fits(size: Vec2) -> bool {
+        size.x >= MIN_VIEWPORT_SIZE.x && size.y >= MIN_VIEWPORT_SIZE.y
+    }
+}
+
+impl ViewWrapper for SizeGuard {
+    wrap_impl!(self.view: ScrollView<BoxedView>);
+
+    fn wrap_draw(&self, printer: &Printer) {
+        if self.too_small {
+            self.placeholder.draw(printer)
+        } else {
+            self.view.draw(printer)
+        }
+    }
+
+    fn wrap_layout(&mut self, size: Vec2) {
+        self.too_small = !Self::fits(size);
+        if self.too_small {
+            self.placeholder.layout(size);
+            return;
+        }
+        let resized = self.last_size.is_some_and(|last| last.x != size.x);
+        let (offset, height) = (self.scroll_offset(), self.document_size().y);
+        self.view.layout(size);
+        if resized && height > 0 {
+            let y = offset.y * self.document_size().y / height;
+            self.view.set_offset(Vec2::new(offset.x, y));
+        }
+        self.last_size = Some(size);
+    }
+
+    fn wrap_required_size(&mut self, constraint: Vec2) -> Vec2 {
+        if Self::fits(constraint) {
+            self.view
+                .required_size(constraint)
+                .or_max(MIN_VIEWPORT_SIZE)
+        } else {
+            constraint
+        }
+    }
+}
+
 pub fn new_element_container() -> ElementContainer {
     (DummyView {}).into_boxed_view()
 }
 
-pub fn to_element_container<'a>(layout: LayoutBox<'a>) -> ElementContainer {
+/// Build the view of `layout`, laid out from `document`, which its widgets look up
+/// their labels in
+pub fn to_element_container<'a>(layout: LayoutBox<'a>, document: &Node) -> ElementContainer {
+    to_element_container_with_theme(layout, document, &Theme::default())
+}
+
+pub fn to_element_container_with_theme<'a>(
+    layout: LayoutBox<'a>,
+    document: &Node,
+    theme: &Theme,
+) -> ElementContainer {
+    build_element_container(layout, theme, Inherited::default(), document)
+}
+
+/// Draw `target`, a node of the tree rooted at `root`, and its descendants alone as
+/// text `width` cells wide
+///
+/// The whole tree is styled, and the ancestors of `target` are walked down first, so
+/// that it looks as it does in the page with what it inherits from them. Gets `None`
+/// when `target` isn't in the tree or isn't displayed.
+pub fn render_node_to_string(
+    root: &Node,
+    target: &Node,
+    stylesheet: &Stylesheet,
+    theme: &Theme,
+    width: usize,
+) -> Option<String> {
+    let ancestors = root.ancestors(target)?;
+    let mut styled = to_styled_node(root, stylesheet)?;
+    let mut inherited = Inherited::default();
+    for next in ancestors.iter().skip(1) {
+        if let NodeType::Element(element) = styled.node_type {
+            let props = BoxProps {
+                node_type: styled.node_type,
+                properties: styled.properties.clone(),
+            };
+            inherited = inherited.for_element(&props, element, theme);
+        }
+        let index = styled
+            .children
+            .iter()
+            .position(|child| std::ptr::eq(child.node_type, &next.node_type))?;
+        styled = styled.children.swap_remove(index);
+    }
+
+    Some(draw_to_string(
+        build_element_container(to_layout_box(styled), theme, inherited, root),
+        width,
+    ))
+}
+
+/// Draw `view` on a headless screen `width` cells wide and as tall as it asks for,
+/// getting its rows with the blanks at their ends trimmed
+pub fn draw_to_string(mut view: ElementContainer, width: usize) -> String {
+    let height = view.required_size(Vec2::new(width, usize::MAX)).y.max(1);
+    let backend = Backend::init(Some(Vec2::new(width, height)));
+    let stream = backend.stream();
+    let mut siv = cursive::Cursive::new().into_runner(backend);
+    siv.add_fullscreen_layer(view);
+    siv.refresh();
+    let screen = stream.try_iter().last().unwrap();
+    let mut lines: Vec<String> = screen
+        .as_strings()
+        .into_iter()
+        .map(|line| line.trim_end().to_string())
+        .collect();
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+/// Get the text of the label naming `element`, a node of `document`, as
+/// [`Node::label_text`] finds it
+fn label_of(document: &Node, element: &Element) -> Option<String> {
+    let target = std::iter::once(document)
+        .chain(document.descendants())
+        .find(|node| matches!(&node.node_type, NodeType::Element(e) if std::ptr::eq(e, element)))?;
+    document.label_text(target)
+}
+
+fn collapsed(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Text styling a box passes down to its descendants
+#[derive(Clone, Copy, Default)]
+struct Inherited {
+    color: Option<Color>,
+    /// Show text as written, like in `<pre>`
+    preserve: bool,
+    /// Show text in reverse video, like in `<mark>`
+    highlight: bool,
+}
+
+impl Inherited {
+    fn for_element(self, props: &BoxProps, element: &Element, theme: &Theme) -> Self {
+        Inherited {
+            color: css_color(props)
+                .or_else(|| tag_color(&element.tag_name, theme))
+                .or(self.color),
+            preserve: self.preserve || preserves_whitespace(props, element),
+            highlight: highlights(props, element).unwrap_or(self.highlight),
+        }
+    }
+
+    fn style(&self) -> Style {
+        let style = self.color.map(Style::from).unwrap_or_else(Style::none);
+        if self.highlight {
+            style.combine(Effect::Reverse)
+        } else {
+            style
+        }
+    }
+}
+
+/// The `color` the stylesheet gives the box, which wins over the theme
+fn css_color(props: &BoxProps) -> Option<Color> {
+    match props.properties.get("color") {
+        Some(&CSSValue::Color { r, g, b }) => Some(Color::Rgb(r, g, b)),
+        _ => None,
+    }
+}
+
+fn tag_color(tag_name: &str, theme: &Theme) -> Option<Color> {
+    match tag_name {
+        "a" => Some(theme.link),
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => Some(theme.heading),
+        _ => None,
+    }
+}
+
+/// Whether text inside the box is shown as written, like in `<pre>`
+fn preserves_whitespace(props: &BoxProps, element: &Element) -> bool {
+    element.tag_name == "pre"
+        || props.properties.get("white-space") == Some(&CSSValue::Keyword("pre".to_string()))
+}
+
+/// Whether text inside the box is highlighted, like in `<mark>`, or `None` to inherit it
+///
+/// Any `highlight` value other than `none` turns the highlight on.
+fn highlights(props: &BoxProps, element: &Element) -> Option<bool> {
+    match props.properties.get("highlight") {
+        Some(CSSValue::Keyword(k)) => Some(k != "none"),
+        Some(_) => Some(true),
+        None => (element.tag_name == "mark").then_some(true),
+    }
+}
+
+/// Elements whose subtree is not rendered but replaced by a text placeholder
+const OPAQUE_ELEMENTS: [&str; 2] = ["svg", "math"];
+
+fn box_node_type<'a>(layout: &LayoutBox<'a>) -> Option<&'a NodeType> {
+    match layout.box_type {
+        BoxType::BlockBox(ref p) | BoxType::InlineBox(ref p) => Some(p.node_type),
+        BoxType::AnonymousBox => None,
+    }
+}
+
+fn box_text(layout: &LayoutBox) -> String {
+    match box_node_type(layout) {
+        Some(NodeType::Text(t)) => t.data.clone(),
+        _ => layout.children.iter().map(box_text).collect(),
+    }
+}
+
+fn find_title(children: &[LayoutBox]) -> Option<String> {
+    children
+        .iter()
+        .find_map(|child| match box_node_type(child) {
+            Some(NodeType::Element(e)) if e.tag_name.as_str() == "title" => {
+                Some(box_text(child).trim().to_string())
+            }
+            Some(_) => None,
+            None => find_title(&child.children),
+        })
+        .filter(|title| !title.is_empty())
+}
+
+/// Build the widget standing for a button or input, labelled with its text or else
+/// its accessible name so that it can be told apart from other widgets
+fn build_widget(
+    children: &[LayoutBox],
+    element: &Element,
+    document: &Node,
+) -> Option<ElementContainer> {
+    let name = || {
+        element
+            .accessible_name(label_of(document, element).as_deref())
+            .filter(|name| !name.is_empty())
+    };
+    let input_type = element
+        .attributes
+        .get("type")
+        .map(|t| t.to_ascii_lowercase())
+        .unwrap_or_default();
+    match element.tag_name.as_str() {
+        "button" => {
+            let text = Some(collapsed(
+                &children.iter().map(box_text).collect::<String>(),
+            ))
+            .filter(|text| !text.is_empty());
+            let label = text.or_else(name).unwrap_or_else(|| "button".to_string());
+            Some(Button::new(label, |_| {}).into_boxed_view())
+        }
+        "input" if input_type == "hidden" => Some(DummyView {}.into_boxed_view()),
+        "input" if matches!(input_type.as_str(), "submit" | "reset" | "button") => {
+            let label = element
+                .attributes
+                .get("value")
+                .filter(|value| !value.trim().is_empty())
+                .cloned()
+                .or_else(name)
+                .unwrap_or(input_type);
+            Some(Button::new(label, |_| {}).into_boxed_view())
+        }
+        "input" => Some(
+            Panel::new(EditView::new())
+                .title(name().unwrap_or_else(|| "input".to_string()))
+                .into_boxed_view(),
+        ),
+        _ => None,
+    }
+}
+
+fn build_element_container<'a>(
+    layout: LayoutBox<'a>,
+    theme: &Theme,
+    inherited: Inherited,
+    document: &Node,
+) -> ElementContainer {
     match layout.box_type {
         BoxType::BlockBox(p) | BoxType::InlineBox(p) => match p {
             BoxProps {
                 node_type: NodeType::Element(ref element),
                 ..
             } => {
+                if OPAQUE_ELEMENTS.contains(&element.tag_name.as_str()) {
+                    let text = find_title(&layout.children)
+                        .or_else(|| element.attributes.get("aria-label").cloned())
+                        .unwrap_or_else(|| format!("[{}]", element.tag_name));
+                    return TextView::new(text).into_boxed_view();
+                }
+                if let Some(widget) = build_widget(&layout.children, element, document) {
+                    return widget;
+                }
+                let inherited = inherited.for_element(&p, element, theme);
                 let mut p = Panel::new(LinearLayout::vertical()).title(element.tag_name.clone());
-                match element.tag_name.as_str() {
-                    _ => {
-                        for child in layout.children.into_iter() {
-                            p.with_view_mut(|v| v.add_child(to_element_container(child)));
-                        }
-                    }
-                };
+                for child in layout.children.into_iter() {
+                    p.with_view_mut(|v| {
+                        v.add_child(build_element_container(child, theme, inherited, document))
+                    });
+                }
                 p.into_boxed_view()
             }
             BoxProps {
                 node_type: NodeType::Text(ref t),
                 ..
             } => {
-                let text_to_display = t.data.clone();
-                let text_to_display = text_to_display.replace("\n", "");
-                let text_to_display = text_to_display.trim();
-                if text_to_display != "" {
-                    TextView::new(text_to_display).into_boxed_view()
-                } else {
-                    (DummyView {}).into_boxed_view()
+                let style = inherited.style();
+                if inherited.preserve {
+                    return TextView::new(t.data.clone())
+                        .style(style)
+                        .no_wrap()
+                        .into_boxed_view();
                 }
+                WrappedText::new(&t.data, style).into_boxed_view()
             }
         },
         BoxType::AnonymousBox => {
             let mut p = Panel::new(LinearLayout::horizontal());
             for child in layout.children.into_iter() {
-                p.with_view_mut(|v| v.add_child(to_element_container(child)));
+                p.with_view_mut(|v| {
+                    v.add_child(build_element_container(child, theme, inherited, document))
+                });
             }
             p.into_boxed_view()
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use cursive::backends::puppet::{
+        observed::{ObservedCell, ObservedPieceInterface, ObservedScreen},
+        Backend,
+    };
+
+    use crate::{
+        css::css, html::html::parse, layout::layout::to_layout_box, style::style::to_styled_node,
+    };
+
+    use super::*;
+
+    const HTML: &str =
+        r#"<body><p>hello</p><p class="inline">world</p><div><p>nested</p></div></body>"#;
+    const STYLESHEET: &str = "head { display: none; } html, body, p, div { display: block; }";
+
+    fn render(view: impl IntoBoxedView, size: Vec2) -> ObservedScreen {
+        let backend = Backend::init(Some(size));
+        let stream = backend.stream();
+        let mut siv = cursive::Cursive::new().into_runner(backend);
+        siv.add_fullscreen_layer(view);
+        siv.refresh();
+        stream.try_iter().last().unwrap()
+    }
+
+    fn guarded_document() -> SizeGuard {
+        let node = parse(HTML);
+        let stylesheet = css::parse(STYLESHEET);
+        SizeGuard::new(to_element_container(
+            to_layout_box(to_styled_node(&node, &stylesheet).unwrap()),
+            &node,
+        ))
+    }
+
+    fn find_cell(screen: &ObservedScreen, text: &str) -> ObservedCell {
+        let pos = screen.find_occurences(text)[0].min();
+        screen[pos].clone().unwrap()
+    }
+
+    #[test]
+    fn test_theme_link_color() {
+        let node = parse(r#"<body><a href="x">link</a><p>text</p></body>"#);
+        let stylesheet = css::parse(STYLESHEET);
+        let dark = render(
+            to_element_container_with_theme(
+                to_layout_box(to_styled_node(&node, &stylesheet).unwrap()),
+                &node,
+                &Theme::dark(),
+            ),
+            Vec2::new(40, 20),
+        );
+        let light = render(
+            to_element_container_with_theme(
+                to_layout_box(to_styled_node(&node, &stylesheet).unwrap()),
+                &node,
+                &Theme::light(),
+            ),
+            Vec2::new(40, 20),
+        );
+
+        assert_eq!(
+            find_cell(&dark, "link").style.colors.front,
+            Theme::dark().link
+        );
+        assert_eq!(
+            find_cell(&light, "link").style.colors.front,
+            Theme::light().link
+        );
+        assert_eq!(
+            find_cell(&dark, "text").style.colors,
+            find_cell(&light, "text").style.colors
+        );
+    }
+
+    #[test]
+    fn test_css_color_overrides_theme() {
+        let node = parse(r#"<body><a href="x">link</a></body>"#);
+        let stylesheet = css::parse(&format!("{} a {{ color: green; }}", STYLESHEET));
+        for theme in [Theme::dark(), Theme::light()] {
+            let screen = render(
+                to_element_container_with_theme(
+                    to_layout_box(to_styled_node(&node, &stylesheet).unwrap()),
+                    &node,
+                    &theme,
+                ),
+                Vec2::new(40, 20),
+            );
+            assert_eq!(
+                find_cell(&screen, "link").style.colors.front,
+                Color::Rgb(0, 128, 0)
+            );
+        }
+    }
+
+    fn render_html(html: &str) -> ObservedScreen {
+        render_html_sized(html, Vec2::new(40, 20))
+    }
+
+    fn render_html_sized(html: &str, size: Vec2) -> ObservedScreen {
+        let node = parse(html);
+        let stylesheet = css::parse(STYLESHEET);
+        render(
+            to_element_container(
+                to_layout_box(to_styled_node(&node, &stylesheet).unwrap()),
+                &node,
+            ),
+            size,
+        )
+    }
+
+    #[test]
+    fn test_svg_title_placeholder() {
+        let html = r#"<body><svg viewBox="0"><title>Company logo</title><g><path d="M"></path></g></svg></body>"#;
+        let node = parse(html);
+        let svg = &node.body().unwrap().children[0];
+        assert_eq!(svg.children.len(), 2);
+        assert_eq!(svg.children[1].children.len(), 1);
+
+        let screen = render_html(html);
+        assert_eq!(screen.find_occurences("Company logo").len(), 1);
+        assert!(screen.find_occurences("svg").is_empty());
+        assert!(screen.find_occurences("path").is_empty());
+    }
+
+    #[test]
+    fn test_math_placeholder_without_title() {
+        let screen = render_html("<body><math><mi>x</mi></math></body>");
+        assert_eq!(screen.find_occurences("[math]").len(), 1);
+        assert!(screen.find_occurences("mi").is_empty());
+    }
+
+    #[test]
+    fn test_widgets_named_by_accessible_name() {
+        let screen = render_html_sized(
+            r#"<body>
+            <div><button><img alt=""></button></div>
+            <div><button aria-label="Close"><img alt=""></button></div>
+            <div><button title="Ignored">Send</button></div>
+            <div><input type="search" placeholder="Search the site"></div>
+            <div><label for="n">Full name</label></div>
+            <div><input id="n" name="n"></div>
+            <div><input type="submit"></div>
+            <div><input type="hidden" name="token"></div>
+            </body>"#,
+            Vec2::new(40, 60),
+        );
+        for text in ["<button>", "<Close>", "<Send>", "<submit>"] {
+            assert_eq!(screen.find_occurences(text).len(), 1, "{}", text);
+        }
+        assert!(screen.find_occurences("Ignored").is_empty());
+        assert_eq!(screen.find_occurences("Search the site").len(), 1);
+        assert_eq!(screen.find_occurences("Full name").len(), 2);
+        assert!(screen.find_occurences("token").is_empty());
+    }
+
+    #[test]
+    fn test_input_named_by_enclosing_label() {
+        let screen = render_html(r#"<body><label>Email <input name="e"></label></body>"#);
+        assert_eq!(screen.find_occurences("Email").len(), 2);
+    }
+
+    #[test]
+    fn test_input_label_matches_dom() {
+        let html = r#"<body><label for="other">Phone <input id="y"></label></body>"#;
+        let node = parse(html);
+        let input = node.get_element_by_id("y").unwrap();
+        assert_eq!(node.label_text(input), Some("Phone".to_string()));
+        let screen = render_html(html);
+        assert_eq!(screen.find_occurences("Phone").len(), 2);
+    }
+
+    fn paragraphs(count: usize) -> ElementContainer {
+        let html = format!("<body>{}</body>", "<p>line</p>".repeat(count));
+        let node = parse(&html);
+        let stylesheet = css::parse(STYLESHEET);
+        to_element_container(
+            to_layout_box(to_styled_node(&node, &stylesheet).unwrap()),
+            &node,
+        )
+    }
+
+    fn layout(guard: &mut SizeGuard, size: Vec2) {
+        guard.required_size(size);
+        guard.layout(size);
+    }
+
+    #[test]
+    fn test_size_guard_keeps_scroll_offset() {
+        let size = Vec2::new(40, 10);
+        let mut guard = SizeGuard::new(paragraphs(30));
+        layout(&mut guard, size);
+        guard.set_scroll_offset(Vec2::new(0, 12));
+        assert_eq!(guard.scroll_offset(), Vec2::new(0, 12));
+
+        guard.set_view(paragraphs(30));
+        layout(&mut guard, size);
+        assert_eq!(guard.scroll_offset(), Vec2::new(0, 12));
+    }
+
+    #[test]
+    fn test_size_guard_clamps_scroll_offset() {
+        let size = Vec2::new(40, 10);
+        let mut guard = SizeGuard::new(paragraphs(30));
+        layout(&mut guard, size);
+        guard.set_scroll_offset(Vec2::new(0, 12));
+
+        guard.set_view(paragraphs(1));
+        layout(&mut guard, size);
+        assert_eq!(guard.scroll_offset(), Vec2::new(0, 0));
+    }
+
+    fn long_paragraphs() -> ElementContainer {
+        let words = (0..120).map(|i| format!("w{}", i)).collect::<Vec<_>>();
+        let html = format!(
+            "<body><p>{}</p><div><p>{}</p></div></body>",
+            words.join(" "),
+            words[..40].join(" ")
+        );
+        let node = parse(&html);
+        let stylesheet = css::parse(STYLESHEET);
+        to_element_container(
+            to_layout_box(to_styled_node(&node, &stylesheet).unwrap()),
+            &node,
+        )
+    }
+
+    #[test]
+    fn test_size_guard_reflows_on_resize() {
+        let mut guard = SizeGuard::new(long_paragraphs());
+        layout(&mut guard, Vec2::new(100, 20));
+        assert_eq!(guard.last_size(), Some(Vec2::new(100, 20)));
+        let wide = guard.document_size();
+
+        let narrow = Vec2::new(40, 20);
+        let resized = render(guard, narrow);
+        let fresh = render(SizeGuard::new(long_paragraphs()), narrow);
+        assert_eq!(resized, fresh);
+
+        let mut guard = SizeGuard::new(long_paragraphs());
+        layout(&mut guard, narrow);
+        assert!(guard.document_size().y > wide.y);
+    }
+
+    #[test]
+    fn test_size_guard_keeps_reading_position_on_resize() {
+        let mut guard = SizeGuard::new(long_paragraphs());
+        layout(&mut guard, Vec2::new(40, 5));
+        let height = guard.document_size().y;
+        guard.set_scroll_offset(Vec2::new(0, height / 2));
+
+        layout(&mut guard, Vec2::new(20, 5));
+        let y = guard.scroll_offset().y;
+        let half = guard.document_size().y / 2;
+        assert!(y.abs_diff(half) <= 1, "{y} vs {half}");
+
+        // A height change alone keeps the offset
+        layout(&mut guard, Vec2::new(20, 8));
+        assert_eq!(guard.scroll_offset().y, y);
+    }
+
+    #[test]
+    fn test_size_guard_tiny_viewport() {
+        let screen = render(guarded_document(), Vec2::new(1, 1));
+        assert_eq!(screen.size(), Vec2::new(1, 1));
+        assert_eq!(screen.find_occurences("t").len(), 1);
+    }
+
+    #[test]
+    fn test_size_guard_small_viewport() {
+        let screen = render(guarded_document(), Vec2::new(10, 3));
+        assert_eq!(screen.find_occurences("terminal").len(), 1);
+        assert!(screen.find_occurences("body").is_empty());
+    }
+
+    #[test]
+    fn test_size_guard_minimum_viewport() {
+        let screen = render(guarded_document(), Vec2::new(20, 5));
+        assert!(screen.find_occurences("terminal").is_empty());
+        assert_eq!(screen.find_occurences("body").len(), 1);
+    }
+
+    #[test]
+    fn test_pre_keeps_whitespace() {
+        let diagram = [
+            "+-------+",
+            "|  box  |",
+            "+---+---+",
+            "    |",
+            "    v  done",
+        ];
+        let html = format!(
+            "<body><pre>{}</pre><p>  squashed\n  text</p></body>",
+            diagram.join("\n")
+        );
+        let node = parse(&html);
+        let stylesheet =
+            css::parse("head { display: none; } html, body, p, pre { display: block; }");
+        let screen = render(
+            to_element_container(
+                to_layout_box(to_styled_node(&node, &stylesheet).unwrap()),
+                &node,
+            ),
+            Vec2::new(40, 20),
+        );
+
+        let top = screen.find_occurences(diagram[0])[0].min();
+        for (i, line) in diagram.iter().enumerate() {
+            let found = screen.find_occurences(line);
+            assert_eq!(found.len(), 1, "{line:?}");
+            assert_eq!(found[0].min(), top + Vec2::new(0, i));
+        }
+        assert_eq!(screen.find_occurences("squashed text").len(), 1);
+    }
+
+    #[test]
+    fn test_white_space_pre_property() {
+        let html = "<body><div class=\"code\">a  b\nc</div></body>";
+        let node = parse(html);
+        let stylesheet = css::parse("head { display: none; } html, body, div { display: block; } .code { white-space: pre; }");
+        let screen = render(
+            to_element_container(
+                to_layout_box(to_styled_node(&node, &stylesheet).unwrap()),
+                &node,
+            ),
+            Vec2::new(40, 20),
+        );
+        let first = screen.find_occurences("a  b")[0].min();
+        assert_eq!(
+            screen.find_occurences("c")[0].min(),
+            first + Vec2::new(0, 1)
+        );
+    }
+
+    fn reversed(screen: &ObservedScreen, text: &str) -> Vec<bool> {
+        let start = screen.find_occurences(text)[0].min();
+        (0..text.len() + 2)
+            .map(|i| {
+                let cell = screen[start + Vec2::new(i, 0) - Vec2::new(1, 0)].clone();
+                cell.is_some_and(|c| c.style.effects.contains(Effect::Reverse))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_mark_highlight() {
+        let screen = render_html("<body><p>plain <mark>found</mark> text</p></body>");
+        assert_eq!(
+            reversed(&screen, "found"),
+            vec![false, true, true, true, true, true, false]
+        );
+        assert!(reversed(&screen, "plain").iter().all(|r| !r));
+        assert!(reversed(&screen, "text").iter().all(|r| !r));
+    }
+
+    #[test]
+    fn test_highlight_property() {
+        let node = parse(
+            r#"<body><p><span class="hit">one <b>two</b></span> <mark class="off">three</mark></p></body>"#,
+        );
+        let stylesheet = css::parse(
+            "head { display: none; } html, body, p { display: block; } .hit { highlight: reverse; } .off { highlight: none; }",
+        );
+        let screen = render(
+            to_element_container(
+                to_layout_box(to_styled_node(&node, &stylesheet).unwrap()),
+                &node,
+            ),
+            Vec2::new(60, 20),
+        );
+        assert_eq!(
+            reversed(&screen, "one"),
+            vec![false, true, true, true, false]
+        );
+        assert_eq!(
+            reversed(&screen, "two"),
+            vec![false, true, true, true, false]
+        );
+        assert!(reversed(&screen, "three").iter().all(|r| !r));
+    }
+
+    /// Number of rows down to the last one with anything drawn on it
+    fn drawn_rows(view: ElementContainer, width: usize) -> usize {
+        let height = 200;
+        let screen = render(view, Vec2::new(width, height));
+        (0..height)
+            .rev()
+            .find(|&y| {
+                (0..width).any(|x| {
+                    screen[Vec2::new(x, y)]
+                        .as_ref()
+                        .and_then(|cell| cell.letter.as_option())
+                        .is_some_and(|letter| letter != " ")
+                })
+            })
+            .map_or(0, |y| y + 1)
+    }
+
+    #[test]
+    fn test_document_size_matches_drawn_rows() {
+        let words = (0..80).map(|i| format!("w{}", i)).collect::<Vec<_>>();
+        let html = format!(
+            "<body><p>{}</p><div><p>nested {}</p><pre>a\n  b\nc</pre></div><p>end</p></body>",
+            words.join(" "),
+            words[..30].join(" ")
+        );
+        let node = parse(&html);
+        let stylesheet = css::parse(STYLESHEET);
+        let container = || {
+            to_element_container(
+                to_layout_box(to_styled_node(&node, &stylesheet).unwrap()),
+                &node,
+            )
+        };
+
+        for size in [Vec2::new(30, 10), Vec2::new(45, 8), Vec2::new(80, 24)] {
+            let mut guard = SizeGuard::new(container());
+            layout(&mut guard, size);
+            let document = guard.document_size();
+            assert!(document.y > size.y, "{size:?}");
+            assert_eq!(drawn_rows(container(), document.x), document.y, "{size:?}");
+        }
+    }
+
+    #[test]
+    fn test_render_is_deterministic() {
+        let html = r#"<body><p id="a" class="inline" title="t">hello</p><a href="x" rel="y">link</a><p>text</p></body>"#;
+        let first = render_html(html);
+        for _ in 0..50 {
+            assert_eq!(render_html(html), first);
+        }
+    }
+
+    const DEMO: &str = r#"<body>
+    <p>hello</p>
+    <p class="inline">world</p>
+    <div id="result">
+        <p>not loaded</p>
+    </div>
+</body>"#;
+
+    #[test]
+    fn test_render_node_to_string() {
+        let node = parse(DEMO);
+        let stylesheet = css::parse(STYLESHEET);
+        let result = node.get_element_by_id("result").unwrap();
+        let text =
+            render_node_to_string(&node, result, &stylesheet, &Theme::default(), 30).unwrap();
+        assert_eq!(
+            text,
+            [
+                "┌───┤ div ├────┐",
+                "│┌───┤ p ├────┐│",
+                "││┌──────────┐││",
+                "│││not loaded│││",
+                "││└──────────┘││",
+                "│└────────────┘│",
+                "└──────────────┘",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_render_node_to_string_inherits_from_ancestors() {
+        let node = parse("<body><pre><span id=\"a\">a   b\nc</span></pre></body>");
+        let stylesheet = css::parse(STYLESHEET);
+        let span = node.get_element_by_id("a").unwrap();
+        let text = render_node_to_string(&node, span, &stylesheet, &Theme::default(), 20).unwrap();
+        assert!(text.contains("a   b"));
+        assert!(!text.contains("pre"));
+    }
+
+    #[test]
+    fn test_render_node_to_string_not_rendered() {
+        let node = parse(r#"<body><div class="none"><p id="a">hidden</p></div></body>"#);
+        let stylesheet = css::parse(&format!("{} .none {{ display: none; }}", STYLESHEET));
+        let hidden = node.get_element_by_id("a").unwrap();
+        let theme = Theme::default();
+        assert_eq!(
+            render_node_to_string(&node, hidden, &stylesheet, &theme, 20),
+            None
+        );
+        let detached = parse("<p>elsewhere</p>");
+        assert_eq!(
+            render_node_to_string(&node, &detached, &stylesheet, &theme, 20),
+            None
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use cursive::{
+    view::{Nameable, Scrollable},
+    views::{Dialog, OnEventView, TextView},
+    View,
+};
+use unicode_width::UnicodeWidthChar;
+
+/// Name of the source panel layer
+pub const SOURCE_PANEL: &str = "source";
+
+/// Columns the dialog around the source takes from its width
+const DIALOG_BORDER: usize = 4;
+
+/// Marks the rows that a source line continues on once it is wrapped
+const CONTINUATION: char = '↪';
+
+const TAB_WIDTH: usize = 4;
+
+/// Lay out `source` to read in `width` columns, with every line numbered in a gutter
+///
+/// A line too long for the width continues on the next rows, which are marked with
+/// `↪` instead of a number.
+/// # Example
+/// ```
+/// use tiny_browserbook::render::source::view_source;
+/// let rows = view_source("<p>\n  hello world\n</p>", 12);
+/// assert_eq!(rows, vec!["1 │ <p>", "2 │   hello", "↪ │ world", "3 │ </p>"]);
+/// ```
+pub fn view_source(source: &str, width: usize) -> Vec<String> {
+    let gutter = gutter_width(source);
+    let mut rows = vec![];
+    for (i, line) in source.lines().enumerate() {
+        for (j, segment) in soft_wrap(line, text_width(source, width))
+            .iter()
+            .enumerate()
+        {
+            let marker = if j == 0 {
+                (i + 1).to_string()
+            } else {
+                CONTINUATION.to_string()
+            };
+            let row = format!("{:>gutter$} │ {}", marker, segment);
+            rows.push(row.trim_end().to_string());
+        }
+    }
+    rows
+}
+
+/// Build the panel showing `source` laid out by `view_source` to fit `width` columns,
+/// where `u` closes the panel
+pub fn source_panel(source: &str, width: usize) -> impl View {
+    let rows = view_source(source, width.saturating_sub(DIALOG_BORDER));
+    OnEventView::new(
+        Dialog::around(TextView::new(rows.join("\n")).scrollable())
+            .title("Source")
+            .dismiss_button("Close"),
+    )
+    .on_event('u', |s| {
+        s.pop_layer();
+    })
+    .with_name(SOURCE_PANEL)
+}
+
+fn gutter_width(source: &str) -> usize {
+    source.lines().count().max(1).to_string().len()
+}
+
+/// Columns left for the source after the gutter and its separator
+fn text_width(source: &str, width: usize) -> usize {
+    width.saturating_sub(gutter_width(source) + 3).max(1)
+}
+
+/// Break `line` into segments no wider than `width`, wherever they fill up
+fn soft_wrap(line: &str, width: usize) -> Vec<String> {
+    let mut segments = vec![];
+    let mut segment = String::new();
+    let mut used = 0;
+    for c in line.chars() {
+        let (text, w) = match c {
+            '\t' => (" ".repeat(TAB_WIDTH), TAB_WIDTH),
+            c => (c.to_string(), c.width().unwrap_or(0)),
+        };
+        if used + w > width && !segment.is_empty() {
+            segments.push(std::mem::take(&mut segment));
+            used = 0;
+        }
+        segment.push_str(&text);
+        used += w;
+    }
+    segments.push(segment);
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use cursive::{
+        backends::puppet::{observed::ObservedPieceInterface, Backend},
+        Cursive, Vec2,
+    };
+
+    use super::*;
+
+    const SOURCE: &str = "<html>
+<body>
+  <p id=\"intro\">a paragraph long enough to wrap</p>
+  <p>short</p>
+</body>
+</html>";
+
+    #[test]
+    fn test_view_source_gutter() {
+        let rows = view_source(SOURCE, 80);
+        assert_eq!(rows.len(), 6);
+        assert_eq!(rows[0], "1 │ <html>");
+        assert_eq!(rows[3], "4 │   <p>short</p>");
+        assert_eq!(rows[5], "6 │ </html>");
+    }
+
+    #[test]
+    fn test_view_source_wraps_long_lines() {
+        let rows = view_source(SOURCE, 24);
+        assert_eq!(
+            &rows[2..6],
+            [
+                "3 │   <p id=\"intro\">a pa",
+                "↪ │ ragraph long enough",
+                "↪ │ to wrap</p>",
+                "4 │   <p>short</p>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_view_source_pads_gutter() {
+        let source = ["x"; 10].join("\n");
+        let rows = view_source(&source, 80);
+        assert_eq!(rows[0], " 1 │ x");
+        assert_eq!(rows[9], "10 │ x");
+    }
+
+    #[test]
+    fn test_view_source_wide_characters() {
+        assert_eq!(
+            view_source("日本語テキスト", 12),
+            vec!["1 │ 日本語テ", "↪ │ キスト"]
+        );
+        assert_eq!(view_source("\tx", 80), vec!["1 │     x"]);
+    }
+
+    #[test]
+    fn test_source_panel() {
+        let backend = Backend::init(Some(Vec2::new(40, 12)));
+        let stream = backend.stream();
+        let mut siv = Cursive::new().into_runner(backend);
+        siv.add_layer(source_panel(SOURCE, 40));
+        siv.refresh();
+        let screen = stream.try_iter().last().unwrap().as_strings().join("\n");
+        assert!(screen.contains("Source"));
+        assert!(screen.contains("1 │ <html>"));
+        assert!(screen.contains("4 │   <p>short</p>"));
+    }
+}
@@ -0,0 +1,71 @@
+use cursive::theme::{BaseColor, Color, PaletteColor};
+
+/// Colors the render layer picks for the user agent's own styling
+#[derive(Debug, PartialEq, Clone)]
+pub struct Theme {
+    pub link: Color,
+    pub visited: Color,
+    pub heading: Color,
+    pub text: Color,
+    pub background: Color,
+    pub dim: Color,
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Theme {
+            link: Color::Light(BaseColor::Blue),
+            visited: Color::Light(BaseColor::Magenta),
+            heading: Color::Light(BaseColor::Yellow),
+            text: Color::Light(BaseColor::White),
+            background: Color::Dark(BaseColor::Black),
+            dim: Color::Light(BaseColor::Black),
+        }
+    }
+
+    pub fn light() -> Self {
+        Theme {
+            link: Color::Dark(BaseColor::Blue),
+            visited: Color::Dark(BaseColor::Magenta),
+            heading: Color::Dark(BaseColor::Red),
+            text: Color::Dark(BaseColor::Black),
+            background: Color::Light(BaseColor::White),
+            dim: Color::Dark(BaseColor::White),
+        }
+    }
+
+    /// Switch between the dark and light presets
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::render::theme::Theme;
+    /// assert_eq!(Theme::dark().toggled(), Theme::light());
+    /// assert_eq!(Theme::light().toggled(), Theme::dark());
+    /// ```
+    pub fn toggled(&self) -> Self {
+        if *self == Theme::dark() {
+            Theme::light()
+        } else {
+            Theme::dark()
+        }
+    }
+
+    /// Build a cursive theme whose palette follows this theme
+    pub fn to_cursive_theme(&self) -> cursive::theme::Theme {
+        let mut theme = cursive::theme::Theme {
+            shadow: false,
+            ..Default::default()
+        };
+        theme.palette[PaletteColor::Background] = self.background;
+        theme.palette[PaletteColor::View] = self.background;
+        theme.palette[PaletteColor::Primary] = self.text;
+        theme.palette[PaletteColor::Secondary] = self.dim;
+        theme.palette[PaletteColor::TitlePrimary] = self.heading;
+        theme
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::dark()
+    }
+}
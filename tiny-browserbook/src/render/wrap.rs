@@ -0,0 +1,225 @@
+use cursive::{theme::Style, Printer, Vec2, View};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+const NO_BREAK_SPACE: char = '\u{a0}';
+const SOFT_HYPHEN: char = '\u{ad}';
+
+/// Break text into lines no wider than `width`
+///
+/// Lines break at whitespace but never at no-break spaces. A soft hyphen is an
+/// invisible break opportunity that shows as `-` only when the line breaks
+/// there. A word wider than the line is broken wherever it has to be.
+/// # Example
+/// ```
+/// use tiny_browserbook::render::wrap::wrap;
+/// assert_eq!(wrap("hello wide world", 11), vec!["hello wide", "world"]);
+/// assert_eq!(wrap("ultra\u{ad}violet", 8), vec!["ultra-", "violet"]);
+/// ```
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = vec![];
+    let mut line = String::new();
+
+    for word in text
+        .split(|c: char| c.is_ascii_whitespace())
+        .filter(|w| !w.is_empty())
+    {
+        let mut segments = word
+            .replace(NO_BREAK_SPACE, " ")
+            .split(SOFT_HYPHEN)
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>();
+
+        while !segments.is_empty() {
+            let sep = if line.is_empty() { 0 } else { 1 };
+            let available = width.saturating_sub(line.width() + sep);
+
+            let whole = segments.concat();
+            if whole.width() <= available {
+                push_word(&mut line, &whole);
+                break;
+            }
+
+            let hyphenated = (1..segments.len())
+                .rev()
+                .find(|&k| segments[..k].concat().width() < available);
+            if let Some(k) = hyphenated {
+                push_word(&mut line, &format!("{}-", segments[..k].concat()));
+                lines.push(std::mem::take(&mut line));
+                segments.drain(..k);
+                continue;
+            }
+
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+                continue;
+            }
+
+            let (head, tail) = split_at_width(&segments[0], width);
+            lines.push(head);
+            segments[0] = tail;
+            if segments[0].is_empty() {
+                segments.remove(0);
+            }
+        }
+    }
+
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+fn push_word(line: &mut String, word: &str) {
+    if !line.is_empty() {
+        line.push(' ');
+    }
+    line.push_str(word);
+}
+
+fn split_at_width(s: &str, width: usize) -> (String, String) {
+    let mut used = 0;
+    let mut index = s.len();
+    for (i, c) in s.char_indices() {
+        let w = c.width().unwrap_or(0);
+        if used + w > width && i > 0 {
+            index = i;
+            break;
+        }
+        used += w;
+    }
+    (s[..index].to_string(), s[index..].to_string())
+}
+
+/// Text view that wraps its content with `wrap`
+///
+/// The lines are kept for the last width they were wrapped to, so measuring and
+/// laying out at the same width wraps only once and always agrees.
+pub struct WrappedText {
+    content: String,
+    style: Style,
+    width: Option<usize>,
+    lines: Vec<String>,
+}
+
+impl WrappedText {
+    pub fn new<S: Into<Style>>(content: &str, style: S) -> Self {
+        WrappedText {
+            content: content.to_string(),
+            style: style.into(),
+            width: None,
+            lines: vec![],
+        }
+    }
+
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    fn wrap_to(&mut self, width: usize) -> &[String] {
+        if self.width != Some(width) {
+            self.lines = wrap(&self.content, width);
+            self.width = Some(width);
+        }
+        &self.lines
+    }
+}
+
+impl View for WrappedText {
+    fn draw(&self, printer: &Printer) {
+        printer.with_style(self.style, |printer| {
+            for (y, line) in self.lines.iter().enumerate() {
+                printer.print((0, y), line);
+            }
+        });
+    }
+
+    fn layout(&mut self, size: Vec2) {
+        self.wrap_to(size.x);
+    }
+
+    fn required_size(&mut self, constraint: Vec2) -> Vec2 {
+        let lines = self.wrap_to(constraint.x);
+        let width = lines.iter().map(|l| l.width()).max().unwrap_or(0);
+        Vec2::new(width, lines.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_at_spaces() {
+        assert_eq!(
+            wrap("hello world this is text", 12),
+            vec!["hello world", "this is text"]
+        );
+    }
+
+    #[test]
+    fn test_wrap_collapses_spaces() {
+        assert_eq!(wrap("  hello   world  ", 12), vec!["hello world"]);
+        assert_eq!(wrap("hello\n  world\n", 12), vec!["hello world"]);
+    }
+
+    #[test]
+    fn test_wrap_never_breaks_at_nbsp() {
+        assert_eq!(
+            wrap("aaaa bbbb\u{a0}cccc dd", 12),
+            vec!["aaaa", "bbbb cccc dd"]
+        );
+        assert_eq!(wrap("10\u{a0}km", 12), vec!["10 km"]);
+    }
+
+    #[test]
+    fn test_wrap_breaks_at_soft_hyphen() {
+        assert_eq!(
+            wrap("extra\u{ad}ordinary things", 12),
+            vec!["extra-", "ordinary", "things"]
+        );
+        assert_eq!(
+            wrap("an extra\u{ad}ordinary day", 12),
+            vec!["an extra-", "ordinary day"]
+        );
+    }
+
+    #[test]
+    fn test_wrap_hides_unused_soft_hyphen() {
+        assert_eq!(wrap("un\u{ad}broken", 12), vec!["unbroken"]);
+    }
+
+    #[test]
+    fn test_wrap_hard_breaks_long_word() {
+        assert_eq!(
+            wrap("abcdefghijklmnopqrstuvwxyz", 12),
+            vec!["abcdefghijkl", "mnopqrstuvwx", "yz"]
+        );
+        assert_eq!(
+            wrap("a abcdefghijklmnop", 12),
+            vec!["a", "abcdefghijkl", "mnop"]
+        );
+    }
+
+    #[test]
+    fn test_wrap_wide_characters() {
+        assert_eq!(wrap("ちいさなブラウザ", 12), vec!["ちいさなブラ", "ウザ"]);
+    }
+
+    #[test]
+    fn test_wrap_empty() {
+        assert_eq!(wrap("", 12), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_wrapped_text_measures_at_layout_width() {
+        let mut text = WrappedText::new("hello wide world", Style::none());
+        assert_eq!(text.required_size(Vec2::new(11, 5)), Vec2::new(10, 2));
+        text.layout(Vec2::new(11, 2));
+        assert_eq!(text.lines(), ["hello wide", "world"]);
+
+        text.layout(Vec2::new(5, 3));
+        assert_eq!(text.lines(), ["hello", "wide", "world"]);
+        assert_eq!(text.required_size(Vec2::new(20, 1)), Vec2::new(16, 1));
+    }
+}
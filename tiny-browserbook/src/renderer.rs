@@ -1 +1,2 @@
+pub mod keyregistry;
 pub mod renderer;
@@ -0,0 +1,264 @@
+use std::rc::Rc;
+
+use cursive::event::{Event, EventResult, Key};
+
+use crate::renderer::renderer::Renderer;
+
+pub type KeyHandler = Rc<dyn Fn(&mut Renderer) -> EventResult>;
+
+pub struct KeyBinding {
+    pub key: Event,
+    pub description: String,
+    handler: KeyHandler,
+}
+
+#[derive(Default)]
+pub struct KeyRegistry {
+    bindings: Vec<KeyBinding>,
+}
+
+impl KeyRegistry {
+    pub fn new() -> Self {
+        KeyRegistry { bindings: vec![] }
+    }
+
+    /// Register a key binding
+    /// # Example
+    /// ```
+    /// use cursive::event::{Event, EventResult};
+    /// use tiny_browserbook::renderer::keyregistry::KeyRegistry;
+    /// let mut registry = KeyRegistry::new();
+    /// assert!(registry
+    ///     .register(Event::Char('r'), "Rerender", |_| EventResult::Consumed(None))
+    ///     .is_ok());
+    /// assert!(registry
+    ///     .register(Event::Char('r'), "Reload", |_| EventResult::Consumed(None))
+    ///     .is_err());
+    /// ```
+    pub fn register<F>(&mut self, key: Event, description: &str, handler: F) -> Result<(), String>
+    where
+        F: Fn(&mut Renderer) -> EventResult + 'static,
+    {
+        if self.bindings.iter().any(|b| b.key == key) {
+            return Err(format!("key {} is already registered", key_name(&key)));
+        }
+        self.bindings.push(KeyBinding {
+            key,
+            description: description.to_string(),
+            handler: Rc::new(handler),
+        });
+        Ok(())
+    }
+
+    pub fn handler(&self, key: &Event) -> Option<KeyHandler> {
+        self.bindings
+            .iter()
+            .find(|b| &b.key == key)
+            .map(|b| b.handler.clone())
+    }
+
+    /// Move the binding registered at `from` to `to`
+    /// # Example
+    /// ```
+    /// use cursive::event::{Event, EventResult};
+    /// use tiny_browserbook::renderer::keyregistry::KeyRegistry;
+    /// let mut registry = KeyRegistry::new();
+    /// registry
+    ///     .register(Event::Char('t'), "Toggle theme", |_| EventResult::Consumed(None))
+    ///     .unwrap();
+    /// registry.rebind(&Event::Char('t'), Event::Char('T')).unwrap();
+    /// assert!(registry.handler(&Event::Char('t')).is_none());
+    /// assert!(registry.handler(&Event::Char('T')).is_some());
+    /// ```
+    pub fn rebind(&mut self, from: &Event, to: Event) -> Result<(), String> {
+        if from != &to && self.bindings.iter().any(|b| b.key == to) {
+            return Err(format!("key {} is already registered", key_name(&to)));
+        }
+        let binding = self
+            .bindings
+            .iter_mut()
+            .find(|b| &b.key == from)
+            .ok_or_else(|| format!("key {} is not registered", key_name(from)))?;
+        binding.key = to;
+        Ok(())
+    }
+
+    pub fn bindings(&self) -> &[KeyBinding] {
+        &self.bindings
+    }
+
+    /// Text of the help overlay listing every registered binding
+    pub fn help_text(&self) -> String {
+        let names = self
+            .bindings
+            .iter()
+            .map(|b| key_name(&b.key))
+            .collect::<Vec<_>>();
+        let width = names.iter().map(|n| n.len()).max().unwrap_or(0);
+        names
+            .iter()
+            .zip(self.bindings.iter())
+            .map(|(name, b)| format!("{:width$}  {}", name, b.description, width = width))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Parse a key written as the help overlay names it, like `t`, `Ctrl-r` or `F5`
+/// # Example
+/// ```
+/// use cursive::event::{Event, Key};
+/// use tiny_browserbook::renderer::keyregistry::parse_key;
+/// assert_eq!(parse_key("Ctrl-r"), Some(Event::CtrlChar('r')));
+/// assert_eq!(parse_key("F5"), Some(Event::Key(Key::F5)));
+/// assert_eq!(parse_key("Hyper-x"), None);
+/// ```
+pub fn parse_key(name: &str) -> Option<Event> {
+    let single = |s: &str| {
+        let mut chars = s.chars();
+        chars.next().filter(|_| chars.next().is_none())
+    };
+    if let Some(c) = single(name) {
+        return Some(Event::Char(c));
+    }
+    if let Some(c) = name.strip_prefix("Ctrl-").and_then(single) {
+        return Some(Event::CtrlChar(c));
+    }
+    if let Some(c) = name.strip_prefix("Alt-").and_then(single) {
+        return Some(Event::AltChar(c));
+    }
+    let key = match name {
+        "Enter" => Key::Enter,
+        "Tab" => Key::Tab,
+        "Backspace" => Key::Backspace,
+        "Esc" => Key::Esc,
+        "Left" => Key::Left,
+        "Right" => Key::Right,
+        "Up" => Key::Up,
+        "Down" => Key::Down,
+        "Ins" => Key::Ins,
+        "Del" => Key::Del,
+        "Home" => Key::Home,
+        "End" => Key::End,
+        "PageUp" => Key::PageUp,
+        "PageDown" => Key::PageDown,
+        _ => {
+            let n: u8 = name.strip_prefix('F')?.parse().ok()?;
+            if !(1..=12).contains(&n) {
+                return None;
+            }
+            Key::from_f(n)
+        }
+    };
+    Some(Event::Key(key))
+}
+
+fn key_name(key: &Event) -> String {
+    match key {
+        Event::Char(c) => c.to_string(),
+        Event::CtrlChar(c) => format!("Ctrl-{}", c),
+        Event::AltChar(c) => format!("Alt-{}", c),
+        Event::Key(k) => format!("{:?}", k),
+        e => format!("{:?}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_duplicate_key() {
+        let mut registry = KeyRegistry::new();
+        assert!(registry
+            .register(Event::Char('r'), "Rerender", |_| EventResult::Consumed(
+                None
+            ))
+            .is_ok());
+        assert_eq!(
+            registry.register(Event::Char('r'), "Reload", |_| EventResult::Consumed(None)),
+            Err("key r is already registered".to_string())
+        );
+        assert_eq!(registry.bindings().len(), 1);
+    }
+
+    #[test]
+    fn test_handler() {
+        let mut registry = KeyRegistry::new();
+        registry
+            .register(Event::Char('r'), "Rerender", |_| {
+                EventResult::Consumed(None)
+            })
+            .unwrap();
+        assert!(registry.handler(&Event::Char('r')).is_some());
+        assert!(registry.handler(&Event::Char('x')).is_none());
+    }
+
+    #[test]
+    fn test_help_text() {
+        let mut registry = KeyRegistry::new();
+        registry
+            .register(Event::Char('?'), "Show this help", |_| {
+                EventResult::Consumed(None)
+            })
+            .unwrap();
+        registry
+            .register(Event::CtrlChar('r'), "Rerender", |_| {
+                EventResult::Consumed(None)
+            })
+            .unwrap();
+        registry
+            .register(Event::Key(Key::F5), "Reload", |_| {
+                EventResult::Consumed(None)
+            })
+            .unwrap();
+        assert_eq!(
+            registry.help_text(),
+            "?       Show this help\nCtrl-r  Rerender\nF5      Reload"
+        );
+    }
+
+    #[test]
+    fn test_rebind() {
+        let mut registry = KeyRegistry::new();
+        registry
+            .register(Event::Char('r'), "Rerender", |_| {
+                EventResult::Consumed(None)
+            })
+            .unwrap();
+        registry
+            .register(Event::Char('t'), "Toggle theme", |_| {
+                EventResult::Consumed(None)
+            })
+            .unwrap();
+        assert_eq!(
+            registry.rebind(&Event::Char('r'), Event::Char('t')),
+            Err("key t is already registered".to_string())
+        );
+        assert_eq!(
+            registry.rebind(&Event::Char('x'), Event::Char('y')),
+            Err("key x is not registered".to_string())
+        );
+        assert!(registry.rebind(&Event::Char('r'), Event::Char('r')).is_ok());
+        registry
+            .rebind(&Event::Char('r'), Event::Key(Key::F5))
+            .unwrap();
+        assert_eq!(registry.help_text(), "F5  Rerender\nt   Toggle theme");
+    }
+
+    #[test]
+    fn test_parse_key_round_trip() {
+        for key in [
+            Event::Char('?'),
+            Event::CtrlChar('r'),
+            Event::AltChar('x'),
+            Event::Key(Key::F12),
+            Event::Key(Key::PageDown),
+        ] {
+            assert_eq!(parse_key(&key_name(&key)), Some(key));
+        }
+        assert_eq!(parse_key(""), None);
+        assert_eq!(parse_key("F13"), None);
+        assert_eq!(parse_key("Ctrl-rr"), None);
+    }
+}
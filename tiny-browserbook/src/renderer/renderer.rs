@@ -1,96 +1,800 @@
 use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    panic::{self, AssertUnwindSafe},
     rc::Rc,
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, TryLockError},
 };
 
-use cursive::{CbSink, View};
+use cursive::{
+    event::{Event, EventResult},
+    views::{Dialog, LinearLayout, OnEventView, TextView},
+    CbSink, View,
+};
 
 use crate::{
-    css::css::parse,
-    html::dom::{Node, NodeType},
+    css::css::{parse, try_parse, Stylesheet},
+    error::error::{BrowserError, RenderError},
+    html::{
+        dom::{Element, Node, NodeType},
+        html::{truncate_tree, ParseOptions},
+        lint::{lint, LintWarning, DEFAULT_RULES},
+    },
     javascript::{javascript::JavascriptRuntime, renderapi::RendererAPI},
-    layout::layout::to_layout_box,
-    render::render::{to_element_container, ElementContainer},
-    style::style::to_styled_node,
+    layout::{layout::to_layout_box, units::UnitMetrics},
+    net::{
+        loader::{LimitedLoader, LoggingLoader, RequestLog, ResourceLoader},
+        url::Url,
+    },
+    render::{
+        diff::{diff_lines, diff_panel, DiffLine, DIFF_PANEL},
+        network::{network_panel, NETWORK_PANEL},
+        render::{
+            render_node_to_string, to_element_container_with_theme, ElementContainer, SizeGuard,
+        },
+        source::{source_panel, SOURCE_PANEL},
+        theme::Theme,
+    },
+    style::style::{to_styled_node_with_scoped, ScopedStylesheets},
 };
 
+use super::keyregistry::KeyRegistry;
+
 const DEFAULT_STYLESHEET: &str = r#"
-script, style {
+head, script, style {
     display: none;
 }
-p, div {
+html, body, p, div {
     display: block;
 }
 "#;
 
-fn collect_tag_inners(node: &Box<Node>, tag_name: &str) -> Vec<String> {
-    if let NodeType::Element(ref element) = node.node_type {
-        if element.tag_name.as_str() == tag_name {
-            return vec![node.inner_text()];
+/// Width of the text the renders are compared at, fixed so that resizing the
+/// terminal doesn't show up as a change
+const DIFF_WIDTH: usize = 80;
+
+const JAVASCRIPT_MIME_TYPES: [&str; 4] = [
+    "text/javascript",
+    "application/javascript",
+    "text/ecmascript",
+    "application/ecmascript",
+];
+
+fn script_type(element: &Element) -> Option<String> {
+    element
+        .attributes
+        .get("type")
+        .map(|t| t.trim().to_ascii_lowercase())
+}
+
+fn is_classic_script(element: &Element) -> bool {
+    match script_type(element) {
+        Some(t) => t.is_empty() || JAVASCRIPT_MIME_TYPES.contains(&t.as_str()),
+        None => true,
+    }
+}
+
+/// The default stylesheet, then the user's, then the contents of the `<style>` elements
+/// in the document's head, or in the whole tree when it has no head
+fn stylesheet_source(document_element: &Node, user_stylesheet: Option<&str>) -> String {
+    let head = document_element.head().unwrap_or(document_element);
+    format!(
+        "{}\n{}\n{}",
+        DEFAULT_STYLESHEET,
+        user_stylesheet.unwrap_or(""),
+        head.get_elements_by_tag_name("style")
+            .iter()
+            .map(|style| style.inner_text())
+            .collect::<Vec<_>>()
+            .join("\n")
+    )
+}
+
+/// Which scripts of a document may run, by where they come from
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptPolicy {
+    /// Whether scripts written in the document run
+    pub allow_inline: bool,
+    /// Whether scripts with a `src` run
+    pub allow_external: bool,
+    /// Hosts that scripts with a `src` may come from, any host when empty
+    pub allowed_hosts: Vec<String>,
+}
+
+impl Default for ScriptPolicy {
+    fn default() -> Self {
+        ScriptPolicy {
+            allow_inline: true,
+            allow_external: true,
+            allowed_hosts: vec![],
+        }
+    }
+}
+
+impl ScriptPolicy {
+    /// Check whether a script with `src`, or an inline one when it is `None`, may run,
+    /// failing with the reason it is blocked
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::renderer::renderer::ScriptPolicy;
+    /// let policy = ScriptPolicy {
+    ///     allowed_hosts: vec!["cdn.example.com".to_string()],
+    ///     ..ScriptPolicy::default()
+    /// };
+    /// assert!(policy.check(None).is_ok());
+    /// assert!(policy.check(Some("https://CDN.example.com/a.js")).is_ok());
+    /// assert_eq!(
+    ///     policy.check(Some("https://evil.example/a.js")),
+    ///     Err("host evil.example is not allowed".to_string())
+    /// );
+    /// ```
+    pub fn check(&self, src: Option<&str>) -> Result<(), String> {
+        let src = match src {
+            None if self.allow_inline => return Ok(()),
+            None => return Err("inline scripts are not allowed".to_string()),
+            Some(_) if !self.allow_external => {
+                return Err("external scripts are not allowed".to_string())
+            }
+            Some(src) => src,
+        };
+        if self.allowed_hosts.is_empty() {
+            return Ok(());
+        }
+        // The document has no URL of its own, so a relative source has no known host
+        let host = Url::parse(src)
+            .ok()
+            .and_then(|url| url.host)
+            .ok_or_else(|| format!("{} has no host to check", src))?;
+        if self
+            .allowed_hosts
+            .iter()
+            .any(|h| h.eq_ignore_ascii_case(&host))
+        {
+            Ok(())
+        } else {
+            Err(format!("host {} is not allowed", host))
+        }
+    }
+}
+
+/// How much of a page the browser takes in before giving up on the rest
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceLimits {
+    /// Size of the largest document loaded, in bytes
+    pub max_document_bytes: usize,
+    /// Size of the largest script, stylesheet or image loaded, in bytes
+    pub max_subresource_bytes: usize,
+    /// How many nodes of a document are rendered, the rest being dropped
+    pub max_nodes: usize,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        ResourceLimits {
+            max_document_bytes: 16 * 1024 * 1024,
+            max_subresource_bytes: 4 * 1024 * 1024,
+            max_nodes: 100_000,
+        }
+    }
+}
+
+impl ResourceLimits {
+    /// Wrap the loader of documents so that it refuses ones over `max_document_bytes`
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::{
+    ///     error::error::LoadError,
+    ///     net::{loader::{ResourceLoader, Response, ResponseStream}, url::Url},
+    ///     renderer::renderer::ResourceLimits,
+    /// };
+    /// struct Large;
+    /// impl ResourceLoader for Large {
+    ///     fn fetch(&self, _method: &str, _url: &Url) -> Result<ResponseStream, LoadError> {
+    ///         Ok(Response { body: vec![0; 11], ..Response::default() }.into())
+    ///     }
+    /// }
+    /// let limits = ResourceLimits {
+    ///     max_document_bytes: 10,
+    ///     ..ResourceLimits::default()
+    /// };
+    /// let url = Url::parse("https://example.com/").unwrap();
+    /// assert_eq!(
+    ///     limits.document_loader(Large).load("GET", &url),
+    ///     Err(LoadError::TooLarge { size: 11, limit: 10 })
+    /// );
+    /// ```
+    pub fn document_loader<L: ResourceLoader>(&self, loader: L) -> LimitedLoader<L> {
+        LimitedLoader::new(loader, self.max_document_bytes)
+    }
+
+    /// Get the options to parse a document to render with, which stop the parser one
+    /// node past `max_nodes` so that the renderer sees the document was cut and shows
+    /// its banner
+    pub fn parse_options(&self) -> ParseOptions {
+        ParseOptions {
+            max_nodes: Some(self.max_nodes.saturating_add(1)),
+            ..ParseOptions::default()
         }
     }
 
-    node.children
-        .iter()
-        .map(|child| collect_tag_inners(child, tag_name))
-        .collect::<Vec<Vec<String>>>()
+    /// Wrap the loader of subresources so that it refuses ones over `max_subresource_bytes`
+    pub fn subresource_loader<L: ResourceLoader>(&self, loader: L) -> LimitedLoader<L> {
+        LimitedLoader::new(loader, self.max_subresource_bytes)
+    }
+}
+
+/// A classic script the script policy lets run
+#[derive(Debug, PartialEq)]
+enum AllowedScript {
+    /// The source of a script written in the document
+    Inline(String),
+    /// The `src` of a script to load, the text between its tags never being run
+    External(String),
+}
+
+/// The classic scripts of a document, sorted by what the script policy lets them do
+#[derive(Debug, Default, PartialEq)]
+struct CollectedScripts {
+    /// The scripts allowed to run, in document order
+    allowed: Vec<AllowedScript>,
+    /// Why each blocked script was blocked
+    blocked: Vec<String>,
+}
+
+/// Sort the classic scripts of `node` by what `policy` lets them do
+fn collect_scripts(node: &Node, policy: &ScriptPolicy) -> CollectedScripts {
+    let mut scripts = CollectedScripts::default();
+    for script in node.get_elements_by_tag_name("script") {
+        match &script.node_type {
+            NodeType::Element(element) if is_classic_script(element) => {}
+            _ => continue,
+        }
+        let src = script.get_attribute("src");
+        match (policy.check(src), src) {
+            (Ok(()), Some(src)) => scripts
+                .allowed
+                .push(AllowedScript::External(src.to_string())),
+            (Ok(()), None) => scripts
+                .allowed
+                .push(AllowedScript::Inline(script.inner_text())),
+            (Err(reason), Some(src)) => scripts
+                .blocked
+                .push(format!("blocked script {}: {}", src, reason)),
+            (Err(reason), None) => scripts
+                .blocked
+                .push(format!("blocked inline script: {}", reason)),
+        }
+    }
+    scripts
+}
+
+fn find_json_data(node: &Node, id: &str) -> Option<String> {
+    node.get_elements_by_tag_name("script")
         .into_iter()
-        .flatten()
-        .collect()
+        .find(|script| match &script.node_type {
+            NodeType::Element(element) => {
+                script_type(element).as_deref() == Some("application/json")
+                    && script.get_attribute("id") == Some(id)
+            }
+            NodeType::Text(_) => false,
+        })
+        .map(|script| script.inner_text())
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct RendererOptions {
+    pub theme: Theme,
+    pub units: UnitMetrics,
+    /// Rules applied over the default stylesheet and under the document's own
+    pub user_stylesheet: Option<String>,
+    pub script_policy: ScriptPolicy,
+    pub limits: ResourceLimits,
+    /// Whether the document is checked for structural problems once parsed
+    pub lint: bool,
 }
 
 pub struct Renderer {
-    view: ElementContainer,
+    options: RendererOptions,
+    ui_cb_sink: Rc<CbSink>,
+    view: SizeGuard,
     document_element: Arc<Mutex<Box<Node>>>,
     js_runtime_instance: JavascriptRuntime,
+    key_registry: KeyRegistry,
+    rerenders: usize,
+    /// The parsed stylesheet and the hash of the source it was parsed from
+    stylesheet: (u64, Stylesheet),
+    scoped: ScopedStylesheets,
+    request_log: RequestLog,
+    /// Loads the external scripts, and the URL of the document their `src` is
+    /// resolved against
+    loader: Option<(Box<dyn ResourceLoader>, Url)>,
+    /// Why each script the policy kept from running was blocked
+    blocked_scripts: Vec<String>,
+    /// `src` of the external scripts the policy allows
+    external_scripts: Vec<String>,
+    /// Shown over the page when nodes past the limit were dropped
+    banner: Option<String>,
+    /// The document as rendered before the last rerender, and since
+    renders: (Option<RenderedDocument>, RenderedDocument),
+    lint_warnings: Vec<LintWarning>,
+    /// The markup the document was parsed from, when known
+    source: Option<String>,
+    /// Called with the layout of every rerender, for tests to break the pipeline with
+    #[cfg(test)]
+    layout_hook: Option<fn(&crate::layout::layout::LayoutBox)>,
+}
+
+fn source_hash(source: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Run the style, layout and view building pipeline, turning a panic in it into a
+/// `RenderError` so that one broken page can't take the whole browser down
+fn catch_render_panic<T, F>(render: F) -> Result<T, BrowserError>
+where
+    F: FnOnce() -> Option<T>,
+{
+    // The pipeline only reads the document and the stylesheet and builds a new view,
+    // so unwinding out of it can't leave anything it shares half updated
+    match panic::catch_unwind(AssertUnwindSafe(render)) {
+        Ok(Some(view)) => Ok(view),
+        Ok(None) => Err(RenderError {
+            message: "document has nothing to display".to_string(),
+        }
+        .into()),
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            Err(RenderError {
+                message: format!("rendering panicked: {}", message),
+            }
+            .into())
+        }
+    }
+}
+
+/// Put `banner`, if any, over `view`
+fn with_banner(view: ElementContainer, banner: Option<&str>) -> ElementContainer {
+    match banner {
+        Some(banner) => Box::new(
+            LinearLayout::vertical()
+                .child(TextView::new(banner))
+                .child(view),
+        ),
+        None => view,
+    }
+}
+
+/// Build the dialog `error` is shown in over the page
+pub fn error_layer(error: &BrowserError) -> Dialog {
+    Dialog::info(error.to_string()).title("Error")
+}
+
+/// Show `error` in a dialog over the page
+fn error_dialog(error: BrowserError) -> EventResult {
+    EventResult::with_cb(move |s| s.add_layer(error_layer(&error)))
+}
+
+fn default_key_registry() -> KeyRegistry {
+    let mut registry = KeyRegistry::new();
+    registry
+        .register(Event::Char('?'), "Show keyboard shortcuts", |r| {
+            let text = r.key_registry().help_text();
+            EventResult::with_cb(move |s| {
+                s.add_layer(Dialog::info(text.clone()).title("Keyboard shortcuts"))
+            })
+        })
+        .unwrap();
+    registry
+        .register(Event::CtrlChar('r'), "Rerender the document", |r| {
+            match r.try_rerender() {
+                Ok(()) => EventResult::Consumed(None),
+                Err(error) => error_dialog(error),
+            }
+        })
+        .unwrap();
+    registry
+        .register(Event::Char('t'), "Toggle dark/light theme", |r| {
+            r.options.theme = r.options.theme.toggled();
+            if let Err(error) = r.try_rerender() {
+                return error_dialog(error);
+            }
+            let theme = r.options.theme.to_cursive_theme();
+            EventResult::with_cb(move |s| s.set_theme(theme.clone()))
+        })
+        .unwrap();
+    registry
+        .register(Event::Char('n'), "Toggle network requests", |r| {
+            let log = r.request_log.clone();
+            EventResult::with_cb(move |s| {
+                if s.find_name::<OnEventView<Dialog>>(NETWORK_PANEL).is_some() {
+                    s.pop_layer();
+                } else {
+                    s.add_layer(network_panel(&log));
+                }
+            })
+        })
+        .unwrap();
+    registry
+        .register(
+            Event::Char('d'),
+            "Toggle changes since the last rerender",
+            |r| {
+                let lines = match r.render_diff() {
+                    Ok(lines) => lines,
+                    Err(error) => return error_dialog(error),
+                };
+                EventResult::with_cb(move |s| {
+                    if s.find_name::<OnEventView<Dialog>>(DIFF_PANEL).is_some() {
+                        s.pop_layer();
+                    } else {
+                        match &lines {
+                            Some(lines) => s.add_layer(diff_panel(lines)),
+                            None => {
+                                s.add_layer(Dialog::info("Not rerendered yet").title("Changes"))
+                            }
+                        }
+                    }
+                })
+            },
+        )
+        .unwrap();
+    registry
+        .register(Event::Char('u'), "Toggle the page source", |r| {
+            let source = r.source.clone();
+            let width = r.view.last_size().map_or(DIFF_WIDTH, |size| size.x);
+            EventResult::with_cb(move |s| {
+                if s.find_name::<OnEventView<Dialog>>(SOURCE_PANEL).is_some() {
+                    s.pop_layer();
+                } else {
+                    match &source {
+                        Some(source) => s.add_layer(source_panel(source, width)),
+                        None => s.add_layer(Dialog::info("No source to show").title("Source")),
+                    }
+                }
+            })
+        })
+        .unwrap();
+    registry
+}
+
+/// A copy of the document as it was rendered and the source of its stylesheet, drawn
+/// as text only when renders are compared
+struct RenderedDocument {
+    document: Box<Node>,
+    stylesheet: String,
+}
+
+impl RenderedDocument {
+    /// Draw the whole document as text to compare renders by
+    fn text(&self, theme: &Theme) -> Result<String, BrowserError> {
+        catch_render_panic(|| {
+            Some(
+                render_node_to_string(
+                    &self.document,
+                    &self.document,
+                    &parse(&self.stylesheet),
+                    theme,
+                    DIFF_WIDTH,
+                )
+                .unwrap_or_default(),
+            )
+        })
+    }
 }
 
 impl Renderer {
-    pub fn new(ui_cb_sink: Rc<CbSink>, document_element: Box<Node>) -> Self {
-        let stylesheet = parse(&format!(
-            "{}\n{}",
-            DEFAULT_STYLESHEET,
-            collect_tag_inners(&document_element, "style".into()).join("\n")
+    /// Create a renderer, skipping what can't be parsed in the stylesheet and failing
+    /// on a document with nothing to display
+    pub fn new(ui_cb_sink: Rc<CbSink>, document_element: Box<Node>) -> Result<Self, BrowserError> {
+        Self::with_options(ui_cb_sink, document_element, RendererOptions::default())
+    }
+
+    pub fn with_options(
+        ui_cb_sink: Rc<CbSink>,
+        document_element: Box<Node>,
+        options: RendererOptions,
+    ) -> Result<Self, BrowserError> {
+        let stylesheet = parse(&stylesheet_source(
+            &document_element,
+            options.user_stylesheet.as_deref(),
         ));
+        Self::build(ui_cb_sink, document_element, options, stylesheet)
+    }
 
-        let view = to_styled_node(&document_element, &stylesheet)
-            .and_then(|styled_node| Some(to_layout_box(styled_node)))
-            .and_then(|layout_box| Some(to_element_container(layout_box)))
-            .unwrap();
+    /// Create a renderer, failing on an invalid stylesheet or a document with nothing to display
+    pub fn try_new(
+        ui_cb_sink: Rc<CbSink>,
+        document_element: Box<Node>,
+    ) -> Result<Self, BrowserError> {
+        Self::try_with_options(ui_cb_sink, document_element, RendererOptions::default())
+    }
+
+    pub fn try_with_options(
+        ui_cb_sink: Rc<CbSink>,
+        document_element: Box<Node>,
+        options: RendererOptions,
+    ) -> Result<Self, BrowserError> {
+        let stylesheet = try_parse(&stylesheet_source(
+            &document_element,
+            options.user_stylesheet.as_deref(),
+        ))?;
+        Self::build(ui_cb_sink, document_element, options, stylesheet)
+    }
+
+    fn build(
+        ui_cb_sink: Rc<CbSink>,
+        mut document_element: Box<Node>,
+        options: RendererOptions,
+        stylesheet: Stylesheet,
+    ) -> Result<Self, BrowserError> {
+        let banner = truncate_tree(&mut document_element, options.limits.max_nodes).map(|_| {
+            format!(
+                "[document truncated after {} nodes]",
+                options.limits.max_nodes
+            )
+        });
+        let mut scoped = ScopedStylesheets::default();
+        scoped.update(&document_element);
+        let view = catch_render_panic(|| {
+            to_styled_node_with_scoped(&document_element, &stylesheet, &scoped)
+                .map(to_layout_box)
+                .map(|layout_box| {
+                    to_element_container_with_theme(layout_box, &document_element, &options.theme)
+                })
+        })?;
+        let view = with_banner(view, banner.as_deref());
+        let lint_warnings = if options.lint {
+            lint(&document_element, &DEFAULT_RULES)
+        } else {
+            vec![]
+        };
 
+        let source = stylesheet_source(&document_element, options.user_stylesheet.as_deref());
+        let stylesheet = (source_hash(&source), stylesheet);
+        let rendered = RenderedDocument {
+            document: document_element.clone(),
+            stylesheet: source,
+        };
         let document_element = Arc::new(Mutex::new(document_element));
         let document_element_ref = document_element.clone();
-        Self {
+        Ok(Self {
+            options,
+            ui_cb_sink: ui_cb_sink.clone(),
             document_element,
-            view,
+            view: SizeGuard::new(view),
             js_runtime_instance: JavascriptRuntime::new(
                 document_element_ref,
                 Arc::new(RendererAPI::new(ui_cb_sink)),
             ),
-        }
+            key_registry: default_key_registry(),
+            rerenders: 0,
+            stylesheet,
+            scoped,
+            request_log: RequestLog::default(),
+            loader: None,
+            blocked_scripts: vec![],
+            external_scripts: vec![],
+            banner,
+            renders: (None, rendered),
+            lint_warnings,
+            source: None,
+            #[cfg(test)]
+            layout_hook: None,
+        })
+    }
+
+    pub fn options(&self) -> &RendererOptions {
+        &self.options
+    }
+
+    /// Get the log that the loader of the current document records its requests in
+    pub fn request_log(&self) -> &RequestLog {
+        &self.request_log
+    }
+
+    pub fn key_registry(&self) -> &KeyRegistry {
+        &self.key_registry
     }
 
+    pub fn key_registry_mut(&mut self) -> &mut KeyRegistry {
+        &mut self.key_registry
+    }
+
+    /// Restyle and lay out the document again, keeping the current view and showing
+    /// the error over it if that fails
     pub fn rerender(&mut self) {
+        if let Err(error) = self.try_rerender() {
+            // Sending fails only once the UI is gone, when there is nowhere to show it
+            let _ = self.ui_cb_sink.send(Box::new(move |s| {
+                s.add_layer(error_layer(&error));
+            }));
+        }
+    }
+
+    /// Restyle and lay out the document again, reparsing the stylesheet only if the
+    /// text of its style elements changed
+    ///
+    /// On failure, including a panic in the pipeline, the current view is kept.
+    pub fn try_rerender(&mut self) -> Result<(), BrowserError> {
+        self.rerenders += 1;
         let document_element = self.document_element.lock().unwrap();
-        let stylesheet = parse(&format!(
-            "{}\n{}",
-            DEFAULT_STYLESHEET,
-            collect_tag_inners(&document_element, "style".into()).join("\n")
-        ));
-        self.view = to_styled_node(&document_element, &stylesheet)
-            .and_then(|styled_node| Some(to_layout_box(styled_node)))
-            .and_then(|layout_box| Some(to_element_container(layout_box)))
-            .unwrap();
+        let source = stylesheet_source(&document_element, self.options.user_stylesheet.as_deref());
+        let hash = source_hash(&source);
+        if hash != self.stylesheet.0 {
+            self.stylesheet = (hash, parse(&source));
+        }
+        self.scoped.update(&document_element);
+        let view = catch_render_panic(|| {
+            to_styled_node_with_scoped(&document_element, &self.stylesheet.1, &self.scoped)
+                .map(to_layout_box)
+                .map(|layout_box| {
+                    #[cfg(test)]
+                    if let Some(hook) = self.layout_hook {
+                        hook(&layout_box);
+                    }
+                    to_element_container_with_theme(
+                        layout_box,
+                        &document_element,
+                        &self.options.theme,
+                    )
+                })
+        })?;
+        self.view
+            .set_view(with_banner(view, self.banner.as_deref()));
+        let rendered = RenderedDocument {
+            document: document_element.clone(),
+            stylesheet: source,
+        };
+        let previous = std::mem::replace(&mut self.renders.1, rendered);
+        self.renders.0 = Some(previous);
+        Ok(())
+    }
+
+    /// Mutate the document and rerender once after the closure returns
+    ///
+    /// Panics if the document is already locked, e.g. when called from a script callback.
+    pub fn with_document_mut<F: FnOnce(&mut Node) -> R, R>(&mut self, f: F) -> R {
+        let result = {
+            let mut document_element = match self.document_element.try_lock() {
+                Ok(document_element) => document_element,
+                Err(TryLockError::Poisoned(error)) => error.into_inner(),
+                Err(TryLockError::WouldBlock) => {
+                    panic!("with_document_mut called while the document is already locked")
+                }
+            };
+            f(&mut document_element)
+        };
+        self.rerender();
+        result
     }
 
+    /// Run the scripts of the document that the script policy allows, in document order
+    ///
+    /// Scripts with a `src` are loaded through the loader given to
+    /// [`Renderer::set_loader`], under the subresource size limit and into the request
+    /// log, and the text between their tags is never run. Without a loader, or when
+    /// one fails to load, they are skipped.
     pub fn execute_inline_scripts(&mut self) {
         let scripts = {
             let document_element = self.document_element.lock().unwrap();
-            collect_tag_inners(&document_element, "script".into()).join("\n")
+            collect_scripts(&document_element, &self.options.script_policy)
         };
-        self.js_runtime_instance
-            .execute("(inline)", scripts.as_str())
-            .unwrap();
+        self.blocked_scripts.extend(scripts.blocked);
+        for script in scripts.allowed {
+            match script {
+                AllowedScript::Inline(source) => {
+                    self.js_runtime_instance
+                        .execute("(inline)", &source)
+                        .unwrap();
+                }
+                AllowedScript::External(src) => {
+                    let loaded = self.load_script(&src);
+                    self.external_scripts.push(src);
+                    if let Some((url, source)) = loaded {
+                        self.js_runtime_instance
+                            .execute(&url.to_string(), &source)
+                            .unwrap();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Load the script at `src`, getting its URL and source unless it fails to load
+    fn load_script(&self, src: &str) -> Option<(Url, String)> {
+        let (loader, base) = self.loader.as_ref()?;
+        let url = base.join(src).ok()?;
+        let loader = LoggingLoader::new(
+            self.options.limits.subresource_loader(loader.as_ref()),
+            self.request_log.clone(),
+        );
+        let response = loader.load("GET", &url).ok()?;
+        (200..300).contains(&response.status).then(|| {
+            let source = String::from_utf8_lossy(&response.body).into_owned();
+            (url, source)
+        })
+    }
+
+    /// Load the external scripts of the document through `loader`, resolving their
+    /// `src` against `url`, where the document was loaded from
+    pub fn set_loader(&mut self, loader: impl ResourceLoader + 'static, url: Url) {
+        self.loader = Some((Box::new(loader), url));
+    }
+
+    /// Get why each script kept from running by the script policy was blocked
+    pub fn blocked_scripts(&self) -> &[String] {
+        &self.blocked_scripts
+    }
+
+    /// Get the `src` of the external scripts the script policy allows, in document order
+    pub fn external_scripts(&self) -> &[String] {
+        &self.external_scripts
+    }
+
+    /// Show the requests in `log` in the network panel, e.g. the one the document was
+    /// loaded through
+    pub fn set_request_log(&mut self, log: RequestLog) {
+        self.request_log = log;
+    }
+
+    /// Keep `source` as the markup the document was parsed from, to show as the page source
+    pub fn set_source(&mut self, source: &str) {
+        self.source = Some(source.to_string());
+    }
+
+    /// Get the structural problems found in the document when it was parsed, if the
+    /// `lint` option is on
+    pub fn lint_warnings(&self) -> &[LintWarning] {
+        &self.lint_warnings
+    }
+
+    /// Get the accessible name of the element with id `id`, as its widget is labelled
+    /// when it has no text of its own
+    pub fn accessible_name(&self, id: &str) -> Option<String> {
+        let document_element = self.document_element.lock().unwrap();
+        let target = document_element.get_element_by_id(id)?;
+        document_element.accessible_name(target)
+    }
+
+    /// Draw the element with id `id` and its descendants alone as text `width` cells
+    /// wide, styled as they are in the page
+    pub fn render_node_to_string(&self, id: &str, width: usize) -> Option<String> {
+        let document_element = self.document_element.lock().unwrap();
+        let target = document_element.get_element_by_id(id)?;
+        render_node_to_string(
+            &document_element,
+            target,
+            &self.stylesheet.1,
+            &self.options.theme,
+            width,
+        )
+    }
+
+    /// Compare the document as drawn before the last rerender with how it is drawn
+    /// now, line by line, or get `None` before the first rerender
+    ///
+    /// Both are drawn as text only now, failing when drawing them panics.
+    pub fn render_diff(&self) -> Result<Option<Vec<DiffLine>>, BrowserError> {
+        let (previous, current) = &self.renders;
+        let Some(previous) = previous else {
+            return Ok(None);
+        };
+        let theme = &self.options.theme;
+        Ok(Some(diff_lines(
+            &previous.text(theme)?,
+            &current.text(theme)?,
+        )))
+    }
+
+    /// Get the raw text of a `<script type="application/json">` data block by id
+    pub fn json_data(&self, id: &str) -> Option<String> {
+        let document_element = self.document_element.lock().unwrap();
+        find_json_data(&document_element, id)
     }
 }
 
@@ -112,7 +816,13 @@ impl View for Renderer {
     }
 
     fn on_event(&mut self, e: cursive::event::Event) -> cursive::event::EventResult {
-        self.view.on_event(e)
+        match self.view.on_event(e.clone()) {
+            EventResult::Ignored => match self.key_registry.handler(&e) {
+                Some(handler) => handler(self),
+                None => EventResult::Ignored,
+            },
+            result => result,
+        }
     }
 
     fn call_on_any<'a>(&mut self, s: &cursive::view::Selector<'_>, cb: cursive::event::AnyCb<'a>) {
@@ -144,3 +854,583 @@ impl View for Renderer {
 
 unsafe impl Send for Renderer {}
 unsafe impl Sync for Renderer {}
+
+#[cfg(test)]
+mod tests {
+    use cursive::backends::puppet::{observed::ObservedPieceInterface, Backend};
+    use rstest::rstest;
+
+    use crate::{
+        css::css::{named_color, parse as parse_stylesheet, CSSValue},
+        error::error::LoadError,
+        html::{
+            dom::Text,
+            html::{parse, parse_fragment, parse_with_options},
+        },
+        layout::layout::{BoxType, LayoutBox},
+        net::loader::{Response, ResponseStream},
+        render::render::draw_to_string,
+        style::style::to_styled_node,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_collect_scripts_skips_data_blocks() {
+        let node = parse(
+            r#"<body><script>let a = 1;</script><script type="application/json" id="data">{"a":1}</script><script type="text/javascript">let b = 2;</script></body>"#,
+        );
+        assert_eq!(
+            collect_scripts(&node, &ScriptPolicy::default()).allowed,
+            vec![inline("let a = 1;"), inline("let b = 2;")]
+        );
+    }
+
+    #[test]
+    fn test_collect_scripts_in_document_order() {
+        let node = parse(
+            r#"<head><script>let a = 1;</script></head><body><div><script type="module">m</script><script type=" TEXT/JavaScript ">let b = 2;</script></div></body>"#,
+        );
+        assert_eq!(
+            collect_scripts(&node, &ScriptPolicy::default()).allowed,
+            vec![inline("let a = 1;"), inline("let b = 2;")]
+        );
+    }
+
+    fn inline(source: &str) -> AllowedScript {
+        AllowedScript::Inline(source.to_string())
+    }
+
+    fn external(src: &str) -> AllowedScript {
+        AllowedScript::External(src.to_string())
+    }
+
+    const SCRIPTS: &str = r#"<body>
+        <script>var inline = 1;</script>
+        <script src="https://cdn.example.com/a.js">var cdn = 1;</script>
+        <script src="https://evil.example/b.js">var evil = 1;</script>
+        <script src="/c.js">var relative = 1;</script>
+    </body>"#;
+
+    fn no_inline() -> ScriptPolicy {
+        ScriptPolicy {
+            allow_inline: false,
+            ..ScriptPolicy::default()
+        }
+    }
+
+    fn cdn_only() -> ScriptPolicy {
+        ScriptPolicy {
+            allow_inline: false,
+            allow_external: true,
+            allowed_hosts: vec!["cdn.example.com".to_string()],
+        }
+    }
+
+    const CDN: &str = "https://cdn.example.com/a.js";
+    const EVIL: &str = "https://evil.example/b.js";
+
+    #[rstest]
+    #[case(
+        ScriptPolicy::default(),
+        vec![inline("var inline = 1;"), external(CDN), external(EVIL), external("/c.js")],
+        vec![]
+    )]
+    #[case(
+        no_inline(),
+        vec![external(CDN), external(EVIL), external("/c.js")],
+        vec!["blocked inline script: inline scripts are not allowed"]
+    )]
+    #[case(
+        cdn_only(),
+        vec![external(CDN)],
+        vec![
+            "blocked inline script: inline scripts are not allowed",
+            "blocked script https://evil.example/b.js: host evil.example is not allowed",
+            "blocked script /c.js: /c.js has no host to check",
+        ]
+    )]
+    fn test_collect_scripts_under_policy(
+        #[case] policy: ScriptPolicy,
+        #[case] allowed: Vec<AllowedScript>,
+        #[case] blocked: Vec<&str>,
+    ) {
+        let scripts = collect_scripts(&parse(SCRIPTS), &policy);
+        assert_eq!(scripts.allowed, allowed);
+        assert_eq!(scripts.blocked, blocked);
+    }
+
+    #[test]
+    fn test_collect_scripts_blocks_external() {
+        let policy = ScriptPolicy {
+            allow_external: false,
+            ..ScriptPolicy::default()
+        };
+        let scripts = collect_scripts(&parse(SCRIPTS), &policy);
+        assert_eq!(scripts.allowed, vec![inline("var inline = 1;")]);
+        assert_eq!(scripts.blocked.len(), 3);
+        assert!(scripts.blocked[0].ends_with("external scripts are not allowed"));
+    }
+
+    /// Serves the scripts of `SCRIPTS`, each defining a variable the text between its
+    /// tags doesn't
+    struct ScriptServer;
+
+    impl ResourceLoader for ScriptServer {
+        fn fetch(&self, _method: &str, url: &Url) -> Result<ResponseStream, LoadError> {
+            let body = match url.to_string().as_str() {
+                CDN => "var cdnLoaded = 1;",
+                EVIL => "var evilLoaded = 1;",
+                "https://example.com/c.js" => "var relativeLoaded = 1;",
+                url => return Err(format!("connection refused: {}", url).into()),
+            };
+            Ok(Response {
+                status: 200,
+                body: body.as_bytes().to_vec(),
+                ..Response::default()
+            }
+            .into())
+        }
+    }
+
+    #[rstest]
+    #[case(
+        ScriptPolicy::default(),
+        vec!["inline", "cdnLoaded", "evilLoaded", "relativeLoaded"]
+    )]
+    #[case(no_inline(), vec!["cdnLoaded", "evilLoaded", "relativeLoaded"])]
+    #[case(cdn_only(), vec!["cdnLoaded"])]
+    fn test_execute_scripts_under_policy(#[case] policy: ScriptPolicy, #[case] run: Vec<&str>) {
+        let options = RendererOptions {
+            script_policy: policy,
+            ..RendererOptions::default()
+        };
+        let mut renderer = Renderer::with_options(ui_cb_sink(), parse(SCRIPTS), options).unwrap();
+        let url = Url::parse("https://example.com/index.html").unwrap();
+        renderer.set_loader(ScriptServer, url);
+        renderer.execute_inline_scripts();
+        let loaded = run.iter().filter(|name| name.ends_with("Loaded")).count();
+        assert_eq!(renderer.request_log().lock().unwrap().len(), loaded);
+        for name in [
+            "inline",
+            "cdn",
+            "evil",
+            "relative",
+            "cdnLoaded",
+            "evilLoaded",
+            "relativeLoaded",
+        ] {
+            let defined = renderer
+                .js_runtime_instance
+                .execute("", &format!("typeof {}", name))
+                .unwrap();
+            let expected = if run.contains(&name) {
+                "number"
+            } else {
+                "undefined"
+            };
+            assert_eq!(defined, expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_external_scripts_skipped_without_loader() {
+        let mut renderer = Renderer::new(ui_cb_sink(), parse(SCRIPTS)).unwrap();
+        renderer.execute_inline_scripts();
+        assert_eq!(renderer.external_scripts(), [CDN, EVIL, "/c.js"]);
+        assert!(renderer.request_log().lock().unwrap().is_empty());
+        let defined = renderer
+            .js_runtime_instance
+            .execute("", "typeof cdn")
+            .unwrap();
+        assert_eq!(defined, "undefined");
+    }
+
+    #[test]
+    fn test_find_json_data() {
+        let json = r#"{"a": "b & c", "d": "e > f", "g": "x < y"}"#;
+        let node = parse(&format!(
+            r#"<body><script type="application/json" id="data">{}</script></body>"#,
+            json
+        ));
+        assert_eq!(find_json_data(&node, "data"), Some(json.to_string()));
+        assert_eq!(find_json_data(&node, "invalid"), None);
+    }
+
+    #[test]
+    fn test_find_json_data_ignores_classic_scripts() {
+        let node = parse(r#"<body><script id="data">let a = 1;</script></body>"#);
+        assert_eq!(find_json_data(&node, "data"), None);
+    }
+
+    fn ui_cb_sink() -> Rc<CbSink> {
+        let (cb_sink, _cb_recv) = cursive::reexports::crossbeam_channel::unbounded();
+        Rc::new(cb_sink)
+    }
+
+    #[test]
+    fn test_stylesheet_source_joins_styles() {
+        let node =
+            parse("<style>a {}</style><p>x</p><style>b {}</style><div><style>c {}</style></div>");
+        assert_eq!(
+            stylesheet_source(&node, None),
+            format!("{}\n\na {{}}\nb {{}}", DEFAULT_STYLESHEET)
+        );
+    }
+
+    #[test]
+    fn test_stylesheet_source_reads_head() {
+        let node = parse("<p>a</p><style>p { color: red; }</style>");
+        assert!(stylesheet_source(&node, None).ends_with("p { color: red; }"));
+        assert!(stylesheet_source(&node, None).starts_with(DEFAULT_STYLESHEET));
+    }
+
+    #[test]
+    fn test_stylesheet_source_puts_user_stylesheet_first() {
+        let node = parse("<style>p { color: red; }</style>");
+        assert_eq!(
+            stylesheet_source(&node, Some("p { color: blue; }")),
+            format!(
+                "{}\np {{ color: blue; }}\np {{ color: red; }}",
+                DEFAULT_STYLESHEET
+            )
+        );
+    }
+
+    fn set_text(node: &mut Node, id: &str, text: &str) {
+        node.get_element_by_id_mut(id).unwrap().children = vec![Text::new(text.to_string())];
+    }
+
+    #[test]
+    fn test_with_document_mut_rerenders_once() {
+        let node = parse(r#"<body><p id="a">before</p><p id="b">before</p></body>"#);
+        let mut renderer = Renderer::new(ui_cb_sink(), node).unwrap();
+        let count = renderer.with_document_mut(|document| {
+            set_text(document, "a", "first");
+            set_text(document, "b", "second");
+            document.inner_text().len()
+        });
+        assert_eq!(count, "firstsecond".len());
+        assert_eq!(renderer.rerenders, 1);
+
+        let backend = Backend::init(Some(cursive::Vec2::new(20, 20)));
+        let stream = backend.stream();
+        let mut siv = cursive::Cursive::new().into_runner(backend);
+        siv.add_fullscreen_layer(renderer);
+        siv.refresh();
+        let screen = stream.try_iter().last().unwrap();
+        assert_eq!(screen.find_occurences("first").len(), 1);
+        assert_eq!(screen.find_occurences("second").len(), 1);
+        assert!(screen.find_occurences("before").is_empty());
+    }
+
+    #[test]
+    fn test_render_diff() {
+        let node = parse(r#"<body><p id="up">up 3 days</p><p>ok</p></body>"#);
+        let mut renderer = Renderer::new(ui_cb_sink(), node).unwrap();
+        assert_eq!(renderer.render_diff(), Ok(None));
+
+        renderer.with_document_mut(|document| set_text(document, "up", "up 4 days"));
+        let lines = renderer.render_diff().unwrap().unwrap();
+        let changed: Vec<_> = lines
+            .iter()
+            .filter(|line| !matches!(line, DiffLine::Same(_)))
+            .collect();
+        assert!(matches!(
+            changed[..],
+            [DiffLine::Removed(before), DiffLine::Added(after)]
+                if before.contains("up 3 days") && after.contains("up 4 days")
+        ));
+        assert!(lines
+            .iter()
+            .any(|line| matches!(line, DiffLine::Same(line) if line.contains("ok"))));
+
+        renderer.rerender();
+        assert!(renderer
+            .render_diff()
+            .unwrap()
+            .unwrap()
+            .iter()
+            .all(|line| matches!(line, DiffLine::Same(_))));
+    }
+
+    #[test]
+    fn test_rerender_after_mutation_behind_lock() {
+        let node = parse(r#"<body><div id="list"><p>first</p></div></body>"#);
+        let mut renderer = Renderer::new(ui_cb_sink(), node).unwrap();
+        {
+            let mut document_element = renderer.document_element.lock().unwrap();
+            let list = document_element.get_element_by_id_mut("list").unwrap();
+            let p = parse_fragment("<p>inserted</p>").remove(0);
+            list.append_child(p).unwrap();
+            let first = list.remove_child(0).unwrap();
+            list.append_child(first).unwrap();
+
+            let stylesheet = &renderer.stylesheet.1;
+            let layout_box = to_layout_box(to_styled_node(&document_element, stylesheet).unwrap());
+            assert!(layout_box_texts(&layout_box).contains(&"inserted".to_string()));
+        }
+        renderer.rerender();
+
+        let backend = Backend::init(Some(cursive::Vec2::new(20, 20)));
+        let stream = backend.stream();
+        let mut siv = cursive::Cursive::new().into_runner(backend);
+        siv.add_fullscreen_layer(renderer);
+        siv.refresh();
+        let screen = stream.try_iter().last().unwrap();
+        let inserted = screen.find_occurences("inserted");
+        let first = screen.find_occurences("first");
+        assert_eq!(inserted.len(), 1);
+        assert_eq!(first.len(), 1);
+        assert!(inserted[0].min().y < first[0].min().y);
+    }
+
+    fn layout_box_texts(layout_box: &LayoutBox) -> Vec<String> {
+        let mut texts = match &layout_box.box_type {
+            BoxType::BlockBox(props) | BoxType::InlineBox(props) => match props.node_type {
+                NodeType::Text(t) => vec![t.data.clone()],
+                NodeType::Element(_) => vec![],
+            },
+            BoxType::AnonymousBox => vec![],
+        };
+        for child in layout_box.children.iter() {
+            texts.extend(layout_box_texts(child));
+        }
+        texts
+    }
+
+    #[test]
+    fn test_renderer_accessible_name() {
+        let node = parse(
+            r#"<body><label for="q">Query</label><input id="q" title="t"><button id="b"></button></body>"#,
+        );
+        let renderer = Renderer::new(ui_cb_sink(), node).unwrap();
+        assert_eq!(renderer.accessible_name("q"), Some("Query".to_string()));
+        assert_eq!(renderer.accessible_name("b"), None);
+        assert_eq!(renderer.accessible_name("missing"), None);
+    }
+
+    #[test]
+    fn test_render_node_to_string() {
+        let node = parse(
+            r#"<body><p>hello</p><div id="result"><p>not loaded</p></div><style>.x { display: none; }</style></body>"#,
+        );
+        let renderer = Renderer::new(ui_cb_sink(), node).unwrap();
+        let text = renderer.render_node_to_string("result", 20).unwrap();
+        assert!(text.contains("not loaded"));
+        assert!(!text.contains("hello"));
+        assert_eq!(renderer.render_node_to_string("missing", 20), None);
+    }
+
+    /// Get the color `p` elements are styled with, in document order
+    fn paragraph_colors(renderer: &Renderer) -> Vec<Option<CSSValue>> {
+        let document_element = renderer.document_element.lock().unwrap();
+        let styled =
+            to_styled_node_with_scoped(&document_element, &renderer.stylesheet.1, &renderer.scoped)
+                .unwrap();
+        let mut colors = vec![];
+        let mut stack = vec![&styled];
+        while let Some(node) = stack.pop() {
+            if matches!(node.node_type, NodeType::Element(e) if e.tag_name == "p") {
+                colors.push(node.properties.get("color").cloned());
+            }
+            stack.extend(node.children.iter().rev());
+        }
+        colors
+    }
+
+    #[test]
+    fn test_with_document_mut_restyles() {
+        let node = parse(r#"<style id="s">p { color: red; }</style><p>a</p>"#);
+        let mut renderer = Renderer::new(ui_cb_sink(), node).unwrap();
+        assert_eq!(paragraph_colors(&renderer), vec![named_color("red")]);
+        renderer.with_document_mut(|document| set_text(document, "s", "p { color: green; }"));
+        assert_eq!(paragraph_colors(&renderer), vec![named_color("green")]);
+    }
+
+    #[test]
+    fn test_with_document_mut_restyles_scoped() {
+        let node = parse(
+            r#"<div><style id="s" scoped>p { color: red; }</style><p>in</p></div><p>out</p>"#,
+        );
+        let mut renderer = Renderer::new(ui_cb_sink(), node).unwrap();
+        assert_eq!(paragraph_colors(&renderer), vec![named_color("red"), None]);
+        renderer.with_document_mut(|document| set_text(document, "s", "p { color: green; }"));
+        assert_eq!(
+            paragraph_colors(&renderer),
+            vec![named_color("green"), None]
+        );
+        assert_eq!(renderer.scoped.len(), 1);
+    }
+
+    #[test]
+    fn test_catch_render_panic() {
+        let error = catch_render_panic::<(), _>(|| panic!("layout bug"))
+            .err()
+            .unwrap();
+        assert_eq!(
+            error,
+            BrowserError::Render(RenderError {
+                message: "rendering panicked: layout bug".to_string()
+            })
+        );
+        let error = catch_render_panic::<(), _>(|| panic!("{} bug", "render"))
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "render error: rendering panicked: render bug"
+        );
+        assert!(catch_render_panic::<(), _>(|| None).is_err());
+    }
+
+    #[test]
+    fn test_rerender_shows_pipeline_panic() {
+        let backend = Backend::init(Some(cursive::Vec2::new(60, 20)));
+        let stream = backend.stream();
+        let mut siv = cursive::Cursive::new().into_runner(backend);
+        let node = parse(r#"<body><p id="a">before</p></body>"#);
+        let mut renderer = Renderer::new(Rc::new(siv.cb_sink().clone()), node).unwrap();
+        renderer.layout_hook = Some(|_| panic!("layout bug"));
+        renderer.with_document_mut(|document| set_text(document, "a", "after"));
+        siv.add_fullscreen_layer(renderer);
+        siv.step();
+        siv.refresh();
+
+        let screen = stream.try_iter().last().unwrap();
+        assert_eq!(
+            screen
+                .find_occurences("rendering panicked: layout bug")
+                .len(),
+            1
+        );
+        assert_eq!(screen.find_occurences("before").len(), 1);
+        assert!(siv.is_running());
+
+        let renderer: &mut Renderer = siv
+            .screen_mut()
+            .get_mut(cursive::views::LayerPosition::FromBack(0))
+            .unwrap()
+            .downcast_mut()
+            .unwrap();
+        renderer.layout_hook = None;
+        renderer.rerender();
+        siv.pop_layer();
+        siv.refresh();
+        let screen = stream.try_iter().last().unwrap();
+        assert_eq!(screen.find_occurences("after").len(), 1);
+    }
+
+    fn press(siv: &mut cursive::CursiveRunner<cursive::Cursive>, key: char) {
+        siv.on_event(Event::Char(key));
+        siv.refresh();
+    }
+
+    #[test]
+    fn test_view_source_key() {
+        let backend = Backend::init(Some(cursive::Vec2::new(60, 20)));
+        let stream = backend.stream();
+        let mut siv = cursive::Cursive::new().into_runner(backend);
+        let html = "<body>\n<p>hello</p>\n</body>";
+        let mut renderer = Renderer::new(Rc::new(siv.cb_sink().clone()), parse(html)).unwrap();
+        renderer.set_source(html);
+        siv.add_fullscreen_layer(renderer);
+        siv.refresh();
+
+        press(&mut siv, 'u');
+        let screen = stream.try_iter().last().unwrap();
+        assert_eq!(screen.find_occurences("2 │ <p>hello</p>").len(), 1);
+        press(&mut siv, 'u');
+        let screen = stream.try_iter().last().unwrap();
+        assert!(screen.find_occurences("│ <p>hello</p>").is_empty());
+    }
+
+    #[test]
+    fn test_with_banner() {
+        let raw = (0..1000)
+            .map(|i| format!("<p>{}</p>", i))
+            .collect::<String>();
+        let mut node = parse(&raw);
+        let limits = ResourceLimits {
+            max_nodes: 8,
+            ..ResourceLimits::default()
+        };
+        assert!(truncate_tree(&mut node, limits.max_nodes).is_some());
+        let stylesheet = parse_stylesheet(DEFAULT_STYLESHEET);
+        let view = to_element_container_with_theme(
+            to_layout_box(to_styled_node(&node, &stylesheet).unwrap()),
+            &node,
+            &Theme::default(),
+        );
+        let text = draw_to_string(with_banner(view, Some("[truncated]")), 20);
+        assert!(text.starts_with("[truncated]\n"));
+        assert!(text.contains("1 "));
+        assert!(!text.contains('2'));
+    }
+
+    #[test]
+    fn test_parse_options_show_banner() {
+        let raw = "<p>0</p>".repeat(1000);
+        let limits = ResourceLimits {
+            max_nodes: 8,
+            ..ResourceLimits::default()
+        };
+        let (node, warnings) = parse_with_options(&raw, limits.parse_options());
+        assert_eq!(warnings.len(), 1);
+        let options = RendererOptions {
+            limits,
+            ..RendererOptions::default()
+        };
+        let renderer = Renderer::with_options(ui_cb_sink(), node, options).unwrap();
+        assert_eq!(
+            renderer.banner.as_deref(),
+            Some("[document truncated after 8 nodes]")
+        );
+    }
+
+    #[test]
+    fn test_lint_option() {
+        let html = r#"<body><img src="a.png"><p id="a">a</p><p id="a">b</p></body>"#;
+        let renderer = Renderer::new(ui_cb_sink(), parse(html)).unwrap();
+        assert!(renderer.lint_warnings().is_empty());
+
+        let options = RendererOptions {
+            lint: true,
+            ..RendererOptions::default()
+        };
+        let renderer = Renderer::with_options(ui_cb_sink(), parse(html), options).unwrap();
+        let rules: Vec<_> = renderer
+            .lint_warnings()
+            .iter()
+            .map(|warning| warning.rule)
+            .collect();
+        assert_eq!(rules, vec!["img-alt", "duplicate-ids"]);
+    }
+
+    #[test]
+    fn test_try_new_invalid_stylesheet() {
+        let node = parse("<body><style>p { display: block; </style><p>hello</p></body>");
+        assert!(matches!(
+            Renderer::try_new(ui_cb_sink(), node).err(),
+            Some(BrowserError::Css(_))
+        ));
+    }
+
+    #[test]
+    fn test_new_nothing_to_display() {
+        let node = parse("<style>html { display: none; }</style><p>hidden</p>");
+        assert!(matches!(
+            Renderer::new(ui_cb_sink(), node).err(),
+            Some(BrowserError::Render(_))
+        ));
+    }
+
+    #[test]
+    fn test_try_new_nothing_to_display() {
+        let node = parse("<style>html { display: none; }</style><p>hidden</p>");
+        assert!(matches!(
+            Renderer::try_new(ui_cb_sink(), node).err(),
+            Some(BrowserError::Render(_))
+        ));
+    }
+}
@@ -1 +1,2 @@
 pub mod style;
+pub mod text_model;
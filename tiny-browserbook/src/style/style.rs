@@ -1,8 +1,9 @@
 use std::collections::HashMap;
 
 use crate::{
-    css::css::{CSSValue, Stylesheet},
+    css::css::{named_color, parse, CSSValue, Stylesheet},
     html::dom::{Node, NodeType},
+    layout::units::Length,
 };
 
 #[derive(Debug, PartialEq)]
@@ -19,17 +20,156 @@ pub struct StyledNode<'a> {
     pub properties: HashMap<String, CSSValue>,
 }
 
-pub fn to_styled_node<'a>(node: &'a Box<Node>, stylesheet: &Stylesheet) -> Option<StyledNode<'a>> {
-    let properties: HashMap<String, CSSValue> = stylesheet
-        .rules
+/// Initial values of properties, used by the `initial` keyword
+const INITIAL_VALUES: [(&str, &str); 2] = [("display", "inline"), ("color", "black")];
+
+fn initial_value(name: &str) -> Option<CSSValue> {
+    INITIAL_VALUES
         .iter()
-        .filter(|rule| rule.matches(node))
-        .flat_map(|rule| {
+        .find(|(n, _)| *n == name)
+        .map(|(_, v)| named_color(v).unwrap_or_else(|| CSSValue::Keyword(v.to_string())))
+}
+
+/// Resolve `inherit` and `initial` keywords after the cascade
+fn resolve_keywords(
+    properties: HashMap<String, CSSValue>,
+    parent: Option<&HashMap<String, CSSValue>>,
+) -> HashMap<String, CSSValue> {
+    properties
+        .into_iter()
+        .filter_map(|(name, value)| {
+            let resolved = match &value {
+                CSSValue::Keyword(k) if k == "inherit" => parent
+                    .and_then(|p| p.get(&name).cloned())
+                    .or_else(|| initial_value(&name)),
+                CSSValue::Keyword(k) if k == "initial" => initial_value(&name),
+                _ => Some(value),
+            };
+            resolved.map(|v| (name, v))
+        })
+        .collect()
+}
+
+/// Style the tree rooted at `node`
+///
+/// The rules of a `<style scoped>` element also apply to its parent and the parent's
+/// descendants, after the rules of `stylesheet`.
+pub fn to_styled_node<'a>(node: &'a Node, stylesheet: &Stylesheet) -> Option<StyledNode<'a>> {
+    let mut scoped = ScopedStylesheets::default();
+    scoped.update(node);
+    to_styled_node_with_scoped(node, stylesheet, &scoped)
+}
+
+/// Style the tree rooted at `node` as `to_styled_node` does, taking the rules of its
+/// `<style scoped>` elements from `scoped`, which must be up to date with the tree
+pub fn to_styled_node_with_scoped<'a>(
+    node: &'a Node,
+    stylesheet: &Stylesheet,
+    scoped: &ScopedStylesheets,
+) -> Option<StyledNode<'a>> {
+    to_styled_node_with_parent(node, &[stylesheet], scoped, None)
+}
+
+/// The parsed rules of `<style scoped>` elements, by the text they were parsed from,
+/// so that a scope is parsed once and again only when its text changes
+/// # Example
+/// ```
+/// use tiny_browserbook::html::html::parse;
+/// use tiny_browserbook::style::style::ScopedStylesheets;
+/// let mut scoped = ScopedStylesheets::default();
+/// scoped.update(&parse("<div><style scoped>p { color: red; }</style><p>a</p></div>"));
+/// assert_eq!(scoped.len(), 1);
+/// scoped.update(&parse("<p>a</p>"));
+/// assert_eq!(scoped.len(), 0);
+/// ```
+#[derive(Debug, Default)]
+pub struct ScopedStylesheets {
+    parsed: HashMap<String, Stylesheet>,
+}
+
+impl ScopedStylesheets {
+    /// Parse the scoped styles of the tree rooted at `node` that aren't parsed yet,
+    /// and forget those no longer in it
+    pub fn update(&mut self, node: &Node) {
+        let mut sources = vec![];
+        collect_scoped_sources(node, &mut sources);
+        self.parsed.retain(|source, _| sources.contains(source));
+        for source in sources {
+            self.parsed
+                .entry(source)
+                .or_insert_with_key(|source| parse(source));
+        }
+    }
+
+    /// Get the number of scopes parsed
+    pub fn len(&self) -> usize {
+        self.parsed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parsed.is_empty()
+    }
+
+    fn get(&self, node: &Node) -> Option<&Stylesheet> {
+        scoped_source(node).and_then(|source| self.parsed.get(&source))
+    }
+}
+
+/// Get the text of the `<style scoped>` children of `node`, if it has any
+fn scoped_source(node: &Node) -> Option<String> {
+    let sources: Vec<String> = node
+        .children
+        .iter()
+        .filter(|child| match &child.node_type {
+            NodeType::Element(e) => e.tag_name == "style" && child.has_attribute("scoped"),
+            NodeType::Text(_) => false,
+        })
+        .map(|child| child.inner_text())
+        .collect();
+    if sources.is_empty() {
+        None
+    } else {
+        Some(sources.join("\n"))
+    }
+}
+
+fn collect_scoped_sources(node: &Node, sources: &mut Vec<String>) {
+    sources.extend(scoped_source(node));
+    for child in node.children.iter() {
+        collect_scoped_sources(child, sources);
+    }
+}
+
+fn to_styled_node_with_parent<'a>(
+    node: &'a Node,
+    stylesheets: &[&Stylesheet],
+    scoped: &ScopedStylesheets,
+    parent: Option<&HashMap<String, CSSValue>>,
+) -> Option<StyledNode<'a>> {
+    let extended: Vec<&Stylesheet>;
+    let stylesheets = match scoped.get(node) {
+        Some(rules) => {
+            extended = stylesheets.iter().copied().chain([rules]).collect();
+            &extended
+        }
+        None => stylesheets,
+    };
+    let mut matched: Vec<_> = stylesheets
+        .iter()
+        .flat_map(|stylesheet| stylesheet.rules.iter())
+        .filter_map(|rule| Some((rule.specificity_for(node)?, rule)))
+        .collect();
+    // The sort is stable, so of rules as specific the later still wins
+    matched.sort_by_key(|(specificity, _)| *specificity);
+    let properties: HashMap<String, CSSValue> = matched
+        .into_iter()
+        .flat_map(|(_, rule)| {
             rule.declarations
                 .iter()
                 .map(|declaration| (declaration.name.clone(), declaration.value.clone()))
         })
         .collect();
+    let properties = resolve_keywords(properties, parent);
     if properties.get("display") == Some(&CSSValue::Keyword("none".to_string())) {
         return None;
     }
@@ -37,7 +177,7 @@ pub fn to_styled_node<'a>(node: &'a Box<Node>, stylesheet: &Stylesheet) -> Optio
     let children = node
         .children
         .iter()
-        .filter_map(|x| to_styled_node(x, stylesheet))
+        .filter_map(|x| to_styled_node_with_parent(x, stylesheets, scoped, Some(&properties)))
         .collect();
 
     Some(StyledNode {
@@ -58,6 +198,27 @@ impl<'a> StyledNode<'a> {
             _ => Display::Inline,
         }
     }
+
+    /// Get the value of the property `name` if it is a length
+    /// # Example
+    /// ```
+    /// use tiny_browserbook::css::css::parse;
+    /// use tiny_browserbook::html::html::parse_fragment;
+    /// use tiny_browserbook::layout::units::{Length, Unit};
+    /// use tiny_browserbook::style::style::to_styled_node;
+    /// let node = &parse_fragment("<p>a</p>")[0];
+    /// let stylesheet = parse("p { width: 50%; color: red; }");
+    /// let styled = to_styled_node(node, &stylesheet).unwrap();
+    /// assert_eq!(styled.length_of("width"), Some(Length::new(50.0, Unit::Percent)));
+    /// assert_eq!(styled.length_of("color"), None);
+    /// assert_eq!(styled.length_of("margin"), None);
+    /// ```
+    pub fn length_of(&self, name: &str) -> Option<Length> {
+        match self.properties.get(name) {
+            Some(CSSValue::Length(value, unit)) => Some(Length::new(*value, *unit)),
+            _ => None,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -68,6 +229,7 @@ mod tests {
     use crate::{
         css::css::{AttributeSelectorOp, Declaration, Rule, SimpleSelector},
         html::dom::Element,
+        layout::units::Unit,
     };
 
     use super::*;
@@ -169,7 +331,7 @@ mod tests {
             },
             Rule {
                 selectors: vec![SimpleSelector::AttributeSelector {
-                    tag_name: "p".into(),
+                    tag_name: Some("p".into()),
                     op: AttributeSelectorOp::Eq,
                     attribute: "id".into(),
                     value: "hello".into(),
@@ -196,7 +358,7 @@ mod tests {
             },
             Rule {
                 selectors: vec![SimpleSelector::AttributeSelector {
-                    tag_name: "p".into(),
+                    tag_name: Some("p".into()),
                     op: AttributeSelectorOp::Eq,
                     attribute: "id".into(),
                     value: "test".into(),
@@ -222,7 +384,7 @@ mod tests {
         #[case] stylesheet: Stylesheet,
         #[case] properties: Vec<(String, CSSValue)>,
     ) {
-        let e = &Element::new(
+        let e: &Node = &Element::new(
             "p".to_string(),
             [("id".to_string(), "test".to_string())]
                 .iter()
@@ -271,7 +433,7 @@ mod tests {
         #[case] stylesheet: Stylesheet,
         #[case] properties: Vec<(String, CSSValue)>,
     ) {
-        let parent = &Element::new(
+        let parent: &Node = &Element::new(
             "div".to_string(),
             [("id".to_string(), "test".to_string())]
                 .iter()
@@ -318,7 +480,7 @@ mod tests {
 
     #[test]
     fn test_to_styled_node_nested_single() {
-        let parent = &Element::new(
+        let parent: &Node = &Element::new(
             "div".to_string(),
             [("id".to_string(), "test".to_string())]
                 .iter()
@@ -339,4 +501,203 @@ mod tests {
 
         assert_eq!(to_styled_node(parent, &stylesheet), None);
     }
+
+    #[test]
+    fn test_to_styled_node_upper_case_tag() {
+        let node = &crate::html::html::parse_fragment("<DIV>hello</DIV>")[0];
+
+        let stylesheet = Stylesheet::new(vec![Rule {
+            selectors: vec![SimpleSelector::TypeSelector {
+                tag_name: "div".into(),
+            }],
+            declarations: vec![Declaration {
+                name: "display".to_string(),
+                value: CSSValue::Keyword("none".to_string()),
+            }],
+        }]);
+
+        assert_eq!(to_styled_node(node, &stylesheet), None);
+    }
+
+    #[test]
+    fn test_to_styled_node_inherit() {
+        let node = &crate::html::html::parse_fragment("<div><p>hello</p></div>")[0];
+
+        let stylesheet = Stylesheet::new(vec![
+            Rule {
+                selectors: vec![SimpleSelector::TypeSelector {
+                    tag_name: "div".into(),
+                }],
+                declarations: vec![Declaration {
+                    name: "color".to_string(),
+                    value: CSSValue::Keyword("green".to_string()),
+                }],
+            },
+            Rule {
+                selectors: vec![SimpleSelector::TypeSelector {
+                    tag_name: "p".into(),
+                }],
+                declarations: vec![Declaration {
+                    name: "color".to_string(),
+                    value: CSSValue::Keyword("inherit".to_string()),
+                }],
+            },
+        ]);
+
+        let styled = to_styled_node(node, &stylesheet).unwrap();
+        assert_eq!(
+            styled.children[0].properties.get("color"),
+            Some(&CSSValue::Keyword("green".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_to_styled_node_inherit_without_parent_value() {
+        let node = &crate::html::html::parse_fragment("<div><p>hello</p></div>")[0];
+
+        let stylesheet = Stylesheet::new(vec![Rule {
+            selectors: vec![SimpleSelector::TypeSelector {
+                tag_name: "p".into(),
+            }],
+            declarations: vec![Declaration {
+                name: "display".to_string(),
+                value: CSSValue::Keyword("inherit".to_string()),
+            }],
+        }]);
+
+        let styled = to_styled_node(node, &stylesheet).unwrap();
+        assert_eq!(styled.children[0].display(), Display::Inline);
+    }
+
+    #[test]
+    fn test_to_styled_node_initial() {
+        let node = &crate::html::html::parse_fragment("<div>hello</div>")[0];
+
+        let stylesheet = Stylesheet::new(vec![
+            Rule {
+                selectors: vec![SimpleSelector::UniversalSelector],
+                declarations: vec![Declaration {
+                    name: "display".to_string(),
+                    value: CSSValue::Keyword("block".to_string()),
+                }],
+            },
+            Rule {
+                selectors: vec![SimpleSelector::TypeSelector {
+                    tag_name: "div".into(),
+                }],
+                declarations: vec![Declaration {
+                    name: "display".to_string(),
+                    value: CSSValue::Keyword("initial".to_string()),
+                }],
+            },
+        ]);
+
+        let styled = to_styled_node(node, &stylesheet).unwrap();
+        assert_eq!(
+            styled.properties.get("display"),
+            Some(&CSSValue::Keyword("inline".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_to_styled_node_scoped_style() {
+        let node = crate::html::html::parse(
+            "<div><style scoped>p { color: red; }</style><p>in</p></div><p>out</p>",
+        );
+        let stylesheet = parse("style { display: none; } p { color: blue; }");
+
+        let styled = to_styled_node(&node, &stylesheet).unwrap();
+        let body = &styled.children[1];
+        let div = &body.children[0];
+        assert_eq!(div.children.len(), 1);
+        assert_eq!(
+            div.children[0].properties.get("color"),
+            named_color("red").as_ref()
+        );
+        assert_eq!(
+            body.children[1].properties.get("color"),
+            named_color("blue").as_ref()
+        );
+    }
+
+    #[test]
+    fn test_to_styled_node_id_selector() {
+        let node = &crate::html::html::parse_fragment(
+            r#"<div><div id="result">a</div><div>b</div></div>"#,
+        )[0];
+        let stylesheet = parse("#result { color: red; } div { color: blue; display: block; }");
+
+        let styled = to_styled_node(node, &stylesheet).unwrap();
+        assert_eq!(
+            styled.children[0].properties.get("color"),
+            named_color("red").as_ref()
+        );
+        assert_eq!(
+            styled.children[0].properties.get("display"),
+            Some(&CSSValue::Keyword("block".to_string()))
+        );
+        assert_eq!(
+            styled.children[1].properties.get("color"),
+            named_color("blue").as_ref()
+        );
+    }
+
+    #[test]
+    fn test_to_styled_node_class_selector() {
+        let node = &crate::html::html::parse_fragment(
+            r#"<div><p class="a inline b">a</p><p class="none">b</p><p>c</p></div>"#,
+        )[0];
+        let stylesheet =
+            parse("p { display: block; } .inline { display: inline; } .none { display: none; }");
+
+        let styled = to_styled_node(node, &stylesheet).unwrap();
+        assert_eq!(styled.children.len(), 2);
+        assert_eq!(styled.children[0].display(), Display::Inline);
+        assert_eq!(
+            styled.children[0].properties.get("display"),
+            Some(&CSSValue::Keyword("inline".to_string()))
+        );
+        assert_eq!(styled.children[1].display(), Display::Block);
+    }
+
+    #[test]
+    fn test_to_styled_node_hyphenated_value() {
+        let node = &crate::html::html::parse_fragment("<span>a</span>")[0];
+        let stylesheet = parse("span { display: inline-block; font-weight: bold; }");
+
+        let styled = to_styled_node(node, &stylesheet).unwrap();
+        assert_eq!(
+            styled.properties.get("display"),
+            Some(&CSSValue::Keyword("inline-block".to_string()))
+        );
+        assert_eq!(
+            styled.properties.get("font-weight"),
+            Some(&CSSValue::Keyword("bold".to_string()))
+        );
+        assert_eq!(styled.display(), Display::Inline);
+    }
+
+    #[test]
+    fn test_length_of() {
+        let node = &crate::html::html::parse_fragment("<div><p>a</p></div>")[0];
+        let stylesheet = parse(
+            "div { width: 40px; margin: 0; line-height: 1.5; padding: px; } p { margin: inherit; }",
+        );
+
+        let styled = to_styled_node(node, &stylesheet).unwrap();
+        assert_eq!(styled.length_of("width"), Some(Length::new(40.0, Unit::Px)));
+        assert_eq!(
+            styled.length_of("margin"),
+            Some(Length::new(0.0, Unit::Number))
+        );
+        assert_eq!(
+            styled.length_of("line-height"),
+            Some(Length::new(1.5, Unit::Number))
+        );
+        assert_eq!(styled.length_of("padding"), None);
+        assert_eq!(
+            styled.children[0].length_of("margin"),
+            Some(Length::new(0.0, Unit::Number))
+        );
+    }
 }
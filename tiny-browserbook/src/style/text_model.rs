@@ -0,0 +1,405 @@
+use crate::{
+    css::css::CSSValue,
+    html::dom::NodeType,
+    style::style::{Display, StyledNode},
+};
+
+/// Elements that start a block of their own however they are styled
+const BLOCK_TAGS: [&str; 28] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "tr",
+    "ul",
+];
+
+/// What a block of text is
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockKind {
+    Paragraph,
+    /// A heading from `<h1>` to `<h6>`, with its level
+    Heading(usize),
+    /// A list item, with the number of lists it is nested in
+    ListItem {
+        depth: usize,
+    },
+    /// Preformatted text, whose whitespace is kept as written
+    Pre,
+}
+
+/// A piece of text with the same inline styling throughout
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Run {
+    pub text: String,
+    pub bold: bool,
+    pub italic: bool,
+    /// Target of the link the text is in
+    pub href: Option<String>,
+}
+
+/// A block of text, such as a paragraph or a list item, made of inline runs
+#[derive(Debug, PartialEq)]
+pub struct TextBlock<'a> {
+    /// The node the block was found in
+    pub node: &'a NodeType,
+    pub kind: BlockKind,
+    pub runs: Vec<Run>,
+}
+
+impl<'a> TextBlock<'a> {
+    /// Get the text of all the runs in the block
+    pub fn text(&self) -> String {
+        self.runs.iter().map(|run| run.text.as_str()).collect()
+    }
+}
+
+/// Inline styling and nesting inherited from the ancestors of a node
+#[derive(Clone, Default)]
+struct Context {
+    bold: bool,
+    italic: bool,
+    href: Option<String>,
+    list_depth: usize,
+}
+
+/// Split the text of a styled tree into blocks of inline runs, in document order
+///
+/// Whitespace is collapsed to single spaces and trimmed from the ends of each block
+/// except in preformatted blocks. Blocks holding no text are left out.
+/// # Example
+/// ```
+/// use tiny_browserbook::css::css::parse;
+/// use tiny_browserbook::html::html::parse as parse_html;
+/// use tiny_browserbook::style::style::to_styled_node;
+/// use tiny_browserbook::style::text_model::{extract, BlockKind};
+/// let node = parse_html("<h2>Title</h2><p>some <b>bold</b> text</p>");
+/// let stylesheet = parse("head { display: none; }");
+/// let blocks = extract(&to_styled_node(&node, &stylesheet).unwrap());
+/// assert_eq!(blocks[0].kind, BlockKind::Heading(2));
+/// assert_eq!(blocks[1].text(), "some bold text");
+/// assert!(blocks[1].runs[1].bold);
+/// ```
+pub fn extract<'a>(root: &StyledNode<'a>) -> Vec<TextBlock<'a>> {
+    let mut blocks = vec![];
+    let mut runs = vec![];
+    let block = (root.node_type, BlockKind::Paragraph);
+    extract_into(root, &Context::default(), &block, &mut runs, &mut blocks);
+    flush(&block, &mut runs, &mut blocks);
+    blocks
+}
+
+/// Write `blocks` as plain text, with a blank line between blocks except between the
+/// items of a list, and list items marked with `-` and indented by their depth
+/// # Example
+/// ```
+/// use tiny_browserbook::css::css::parse;
+/// use tiny_browserbook::html::html::parse as parse_html;
+/// use tiny_browserbook::style::style::to_styled_node;
+/// use tiny_browserbook::style::text_model::{extract, to_plain_text};
+/// let node = parse_html("<h2>Title</h2><ul><li>a<ul><li>b</li></ul></li></ul>");
+/// let stylesheet = parse("head { display: none; }");
+/// let blocks = extract(&to_styled_node(&node, &stylesheet).unwrap());
+/// assert_eq!(to_plain_text(&blocks), "Title\n\n- a\n  - b\n");
+/// ```
+pub fn to_plain_text(blocks: &[TextBlock]) -> String {
+    let mut text = String::new();
+    for (i, block) in blocks.iter().enumerate() {
+        let in_list = |block: &TextBlock| matches!(block.kind, BlockKind::ListItem { .. });
+        if i > 0 && !(in_list(&blocks[i - 1]) && in_list(block)) {
+            text.push('\n');
+        }
+        if let BlockKind::ListItem { depth } = block.kind {
+            text.push_str(&"  ".repeat(depth.saturating_sub(1)));
+            text.push_str("- ");
+        }
+        text.push_str(&block.text());
+        text.push('\n');
+    }
+    text
+}
+
+/// Append the runs of `node` to `runs`, which are the text so far of `block`, and the
+/// blocks `node` holds to `blocks`
+fn extract_into<'a>(
+    node: &StyledNode<'a>,
+    context: &Context,
+    block: &(&'a NodeType, BlockKind),
+    runs: &mut Vec<Run>,
+    blocks: &mut Vec<TextBlock<'a>>,
+) {
+    let element = match node.node_type {
+        NodeType::Text(t) => {
+            push_text(runs, &t.data, context, block.1 == BlockKind::Pre);
+            return;
+        }
+        NodeType::Element(e) => e,
+    };
+    let tag_name = element.tag_name.as_str();
+    let mut context = context.clone();
+    context.bold |= matches!(tag_name, "b" | "strong")
+        || node.properties.get("font-weight") == Some(&CSSValue::Keyword("bold".to_string()));
+    context.italic |= matches!(tag_name, "i" | "em")
+        || node.properties.get("font-style") == Some(&CSSValue::Keyword("italic".to_string()));
+    if let Some(href) = element.attributes.get("href").filter(|_| tag_name == "a") {
+        context.href = Some(href.clone());
+    }
+    if matches!(tag_name, "ul" | "ol") {
+        context.list_depth += 1;
+    }
+
+    if node.display() != Display::Block && !BLOCK_TAGS.contains(&tag_name) {
+        for child in node.children.iter() {
+            extract_into(child, &context, block, runs, blocks);
+        }
+        return;
+    }
+
+    let kind = match tag_name {
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+            BlockKind::Heading(tag_name[1..].parse().unwrap())
+        }
+        "li" => BlockKind::ListItem {
+            depth: context.list_depth,
+        },
+        "pre" => BlockKind::Pre,
+        _ if block.1 == BlockKind::Pre => BlockKind::Pre,
+        _ => BlockKind::Paragraph,
+    };
+    // Text after the element goes on with the block it interrupted
+    flush(block, runs, blocks);
+    let inner = (node.node_type, kind);
+    for child in node.children.iter() {
+        extract_into(child, &context, &inner, runs, blocks);
+    }
+    flush(&inner, runs, blocks);
+}
+
+fn push_text(runs: &mut Vec<Run>, text: &str, context: &Context, preformatted: bool) {
+    let text = if preformatted {
+        text.to_string()
+    } else {
+        collapse_whitespace(text)
+    };
+    if let Some(last) = runs.last_mut() {
+        if last.bold == context.bold && last.italic == context.italic && last.href == context.href {
+            last.text.push_str(&text);
+            return;
+        }
+    }
+    runs.push(Run {
+        text,
+        bold: context.bold,
+        italic: context.italic,
+        href: context.href.clone(),
+    });
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut in_space = false;
+    for c in text.chars() {
+        if c.is_ascii_whitespace() {
+            if !in_space {
+                out.push(' ');
+            }
+            in_space = true;
+        } else {
+            out.push(c);
+            in_space = false;
+        }
+    }
+    out
+}
+
+/// Turn `runs` into a block of the kind of `block`, leaving them empty
+fn flush<'a>(
+    block: &(&'a NodeType, BlockKind),
+    runs: &mut Vec<Run>,
+    blocks: &mut Vec<TextBlock<'a>>,
+) {
+    let mut runs = std::mem::take(runs);
+    if block.1 != BlockKind::Pre {
+        // Spaces collapse across runs, and mean nothing at the ends of the block
+        for i in 1..runs.len() {
+            if runs[i - 1].text.ends_with(' ') && runs[i].text.starts_with(' ') {
+                runs[i].text.remove(0);
+            }
+        }
+        if let Some(first) = runs.first_mut() {
+            first.text = first.text.trim_start().to_string();
+        }
+        if let Some(last) = runs.last_mut() {
+            last.text = last.text.trim_end().to_string();
+        }
+        runs.retain(|run| !run.text.is_empty());
+    }
+    if runs.iter().any(|run| !run.text.is_empty()) {
+        blocks.push(TextBlock {
+            node: block.0,
+            kind: block.1.clone(),
+            runs,
+        });
+    }
+}
+#[cfg(test)]
+mod tests {
+    use crate::{css::css::parse, html::html::parse as parse_html, style::style::to_styled_node};
+
+    use super::*;
+
+    const HTML: &str = r#"<body>
+    <h1>Guide</h1>
+    <p>Read the <a href="/docs">docs <em>first</em></a>, then   <strong>practise</strong>.</p>
+    <ul>
+        <li>one</li>
+        <li>two
+            <ol>
+                <li>two <i>point</i> one</li>
+            </ol>
+            after
+        </li>
+    </ul>
+    <pre>  keep
+    this</pre>
+</body>"#;
+
+    fn blocks_of(html: &str, css: &str) -> Vec<(BlockKind, String)> {
+        let node = parse_html(html);
+        let stylesheet = parse(css);
+        let styled = to_styled_node(&node, &stylesheet).unwrap();
+        extract(&styled)
+            .into_iter()
+            .map(|block| (block.kind.clone(), block.text()))
+            .collect()
+    }
+
+    #[test]
+    fn test_extract_block_kinds() {
+        assert_eq!(
+            blocks_of(HTML, "head { display: none; }"),
+            vec![
+                (BlockKind::Heading(1), "Guide".to_string()),
+                (
+                    BlockKind::Paragraph,
+                    "Read the docs first, then practise.".to_string()
+                ),
+                (BlockKind::ListItem { depth: 1 }, "one".to_string()),
+                (BlockKind::ListItem { depth: 1 }, "two".to_string()),
+                (
+                    BlockKind::ListItem { depth: 2 },
+                    "two point one".to_string()
+                ),
+                (BlockKind::ListItem { depth: 1 }, "after".to_string()),
+                (BlockKind::Pre, "  keep\n    this".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_runs() {
+        let node = parse_html(HTML);
+        let stylesheet = parse("head { display: none; }");
+        let styled = to_styled_node(&node, &stylesheet).unwrap();
+        let blocks = extract(&styled);
+        let link = Some("/docs".to_string());
+        assert_eq!(
+            blocks[1].runs,
+            vec![
+                Run {
+                    text: "Read the ".to_string(),
+                    ..Run::default()
+                },
+                Run {
+                    text: "docs ".to_string(),
+                    href: link.clone(),
+                    ..Run::default()
+                },
+                Run {
+                    text: "first".to_string(),
+                    italic: true,
+                    href: link,
+                    ..Run::default()
+                },
+                Run {
+                    text: ", then ".to_string(),
+                    ..Run::default()
+                },
+                Run {
+                    text: "practise".to_string(),
+                    bold: true,
+                    ..Run::default()
+                },
+                Run {
+                    text: ".".to_string(),
+                    ..Run::default()
+                },
+            ]
+        );
+        assert!(matches!(blocks[1].node, NodeType::Element(e) if e.tag_name == "p"));
+        assert!(blocks[4].runs[1].italic);
+    }
+
+    #[test]
+    fn test_extract_styled_blocks_and_runs() {
+        assert_eq!(
+            blocks_of(
+                r#"<span class="card">a <span class="loud">b</span></span>c"#,
+                ".card { display: block; } .loud { font-weight: bold; }",
+            ),
+            vec![
+                (BlockKind::Paragraph, "a b".to_string()),
+                (BlockKind::Paragraph, "c".to_string()),
+            ]
+        );
+        let node = parse_html(r#"<span class="loud">b</span>"#);
+        let stylesheet = parse(".loud { font-weight: bold; }");
+        let styled = to_styled_node(&node, &stylesheet).unwrap();
+        assert!(extract(&styled)[0].runs[0].bold);
+    }
+
+    #[test]
+    fn test_to_plain_text() {
+        let node = parse_html(HTML);
+        let stylesheet = parse("head { display: none; }");
+        let styled = to_styled_node(&node, &stylesheet).unwrap();
+        assert_eq!(
+            to_plain_text(&extract(&styled)),
+            "Guide\n\nRead the docs first, then practise.\n\n- one\n- two\n  - two point one\n- after\n\n  keep\n    this\n"
+        );
+        assert_eq!(to_plain_text(&[]), "");
+    }
+
+    #[test]
+    fn test_extract_skips_hidden_and_empty() {
+        assert_eq!(
+            blocks_of(
+                "<title>t</title><p> </p><div><p>x</p></div>",
+                "head { display: none; }"
+            ),
+            vec![(BlockKind::Paragraph, "x".to_string())]
+        );
+    }
+}